@@ -0,0 +1,751 @@
+use crate::renderer::canvas::{Canvas, Color, VertexSimpleAttributes};
+use crate::renderer::linalg::{Matrix4, Quat, Vec2, Vec3, Vec4, EPS};
+use crate::renderer::scene::{NodeId, Scene, Texture, TextureMap, Transform};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+// Axes of the translation and rotation gizmos. The scale gizmo always uses
+// the local axes, in which the scale is applied.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GizmoSpace {
+    Local,
+    World,
+}
+
+// Gizmo options that can be changed from the GUI.
+#[derive(Clone, Copy)]
+pub struct GizmoSettings {
+    pub mode: GizmoMode,
+    pub space: GizmoSpace,
+    // Rounds the change made by each drag to multiples of the steps.
+    pub snapping: bool,
+    pub translation_step: f32,
+    // In degrees.
+    pub rotation_step: f32,
+    pub scale_step: f32,
+}
+
+impl Default for GizmoSettings {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snapping: false,
+            translation_step: 10.0,
+            rotation_step: 15.0,
+            scale_step: 0.1,
+        }
+    }
+}
+
+// Parte do gizmo que pode ser arrastada.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Handle {
+    // Seta de translação, anel de rotação ou eixo de escala.
+    Axis(usize),
+    // Quadrado da translação no plano perpendicular ao eixo.
+    Plane(usize),
+    // Quadrado central da escala uniforme.
+    Center,
+}
+
+// Posição e eixos do gizmo no mundo e na tela.
+#[derive(Clone, Copy)]
+struct Frame {
+    origin: Vec3,
+    // Unitários, em coordenadas de mundo.
+    axes: [Vec3; 3],
+    // Comprimento dos eixos no mundo, que mantém o tamanho do gizmo na
+    // tela constante.
+    length: f32,
+    screen_origin: Vec2,
+}
+
+struct Drag {
+    handle: Handle,
+    frame: Frame,
+    // Local transform of the node when the drag started.
+    transform: Transform,
+    // Leva as direções do mundo para o espaço do pai do nó, em que a
+    // transformação local é definida.
+    parent_inverse: Matrix4,
+    // Point of the axis or of the plane of the handle under the mouse when
+    // the drag started.
+    start_point: Vec3,
+    start_mouse: Vec2,
+}
+
+// Translate, rotate and scale gizmos of a node of the scene graph, drawn over
+// the rendered frame. The mouse positions are in the screen coordinates of the
+// projected triangles (see `Scene::project_point`).
+pub struct Gizmo {
+    pub settings: GizmoSettings,
+
+    hovered: Option<Handle>,
+    drag: Option<Drag>,
+
+    // Textura branca, que deixa os triângulos com a cor dos vértices.
+    texture: Texture,
+}
+
+impl Gizmo {
+    // Length of the axes, in pixels.
+    const SIZE: f32 = 90.0;
+    // Largest distance, in pixels, from the mouse to a handle it picks.
+    const PICK_DISTANCE: f32 = 6.0;
+    const RING_SEGMENTS: usize = 48;
+    // Início e fim, em frações do eixo, dos quadrados dos planos.
+    const PLANE_HANDLE: (f32, f32) = (0.25, 0.45);
+    // Half of the side, in pixels, of the squares of the scale gizmo.
+    const BOX_SIZE: f32 = 5.0;
+    const MIN_SCALE_FACTOR: f32 = 0.01;
+
+    pub fn new() -> Self {
+        Self {
+            settings: GizmoSettings::default(),
+            hovered: None,
+            drag: None,
+            texture: Texture::with_diffuse_map(TextureMap::new(vec![255; 4], 1, 1, 4)),
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    // Highlights the handle under the mouse.
+    pub fn hover(&mut self, scene: &Scene, node: NodeId, mouse: Vec2) {
+        if self.drag.is_none() {
+            self.hovered = self
+                .frame(scene, node)
+                .and_then(|frame| self.pick_handle(scene, &frame, mouse));
+        }
+    }
+
+    // Starts dragging the handle under the mouse. Returns false when there
+    // is no handle there.
+    pub fn begin_drag(&mut self, scene: &Scene, node: NodeId, mouse: Vec2) -> bool {
+        let Some(frame) = self.frame(scene, node) else {
+            return false;
+        };
+        let Some(handle) = self.pick_handle(scene, &frame, mouse) else {
+            return false;
+        };
+        let Some(scene_node) = scene.graph().node(node) else {
+            return false;
+        };
+
+        let parent_matrix = scene_node
+            .parent()
+            .map_or(Matrix4::identity(), |parent| scene.world_matrix(parent));
+        let Some(parent_inverse) = parent_matrix.inverse() else {
+            return false;
+        };
+
+        let Some(start_point) = self.handle_point(scene, &frame, handle, mouse) else {
+            return false;
+        };
+
+        self.hovered = Some(handle);
+        self.drag = Some(Drag {
+            handle,
+            frame,
+            transform: scene_node.transform,
+            parent_inverse,
+            start_point,
+            start_mouse: mouse,
+        });
+
+        true
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    // Local transform of the dragged node for the mouse at `mouse`, or None
+    // when no handle is being dragged or the mouse ray misses its axis or
+    // plane.
+    pub fn drag(&self, scene: &Scene, mouse: Vec2) -> Option<Transform> {
+        let drag = self.drag.as_ref()?;
+        let frame = &drag.frame;
+        let settings = &self.settings;
+
+        let mut transform = drag.transform;
+
+        // Direção do mundo no espaço do pai.
+        let to_parent = |dir: Vec3| {
+            (drag.parent_inverse * Vec4::new([dir.x(), dir.y(), dir.z(), 0.0])).as_vec3()
+        };
+
+        match (settings.mode, drag.handle) {
+            (GizmoMode::Translate, Handle::Axis(axis)) => {
+                let point = self.handle_point(scene, frame, drag.handle, mouse)?;
+                let axis = frame.axes[axis];
+
+                let distance = (point - drag.start_point).dot(axis);
+                let delta = axis * self.snap(distance, settings.translation_step);
+
+                transform.translation += to_parent(delta);
+            }
+
+            (GizmoMode::Translate, Handle::Plane(axis)) => {
+                let point = self.handle_point(scene, frame, drag.handle, mouse)?;
+                let offset = point - drag.start_point;
+
+                let delta = [(axis + 1) % 3, (axis + 2) % 3]
+                    .iter()
+                    .map(|i| frame.axes[*i])
+                    .fold(Vec3::zeros(), |delta, dir| {
+                        delta + dir * self.snap(offset.dot(dir), settings.translation_step)
+                    });
+
+                transform.translation += to_parent(delta);
+            }
+
+            (GizmoMode::Rotate, Handle::Axis(axis)) => {
+                let point = self.handle_point(scene, frame, drag.handle, mouse)?;
+                let axis = frame.axes[axis];
+
+                // Ângulo entre as direções do centro ao ponto inicial e ao
+                // atual, no plano do anel.
+                let from = drag.start_point - frame.origin;
+                let to = point - frame.origin;
+                let angle = from.cross(to).dot(axis).atan2(from.dot(to));
+                let angle = self
+                    .snap(angle.to_degrees(), settings.rotation_step)
+                    .to_radians();
+
+                let rotation = Quat::from_axis_angle(to_parent(axis), angle);
+                transform.rotation = (rotation * transform.rotation).normalized();
+            }
+
+            (GizmoMode::Scale, Handle::Axis(axis)) => {
+                let point = self.handle_point(scene, frame, drag.handle, mouse)?;
+                let dir = frame.axes[axis];
+
+                let start = (drag.start_point - frame.origin).dot(dir);
+                let current = (point - frame.origin).dot(dir);
+                if start.abs() <= EPS {
+                    return None;
+                }
+
+                let factor = self
+                    .snap(current / start, settings.scale_step)
+                    .max(Self::MIN_SCALE_FACTOR);
+
+                let mut scale = [
+                    transform.scale.x(),
+                    transform.scale.y(),
+                    transform.scale.z(),
+                ];
+                scale[axis] *= factor;
+                transform.scale = Vec3::new(scale);
+            }
+
+            (GizmoMode::Scale, Handle::Center) => {
+                // Arrastar o tamanho do gizmo para a direita dobra a escala.
+                let factor = 1.0 + (mouse.x - drag.start_mouse.x) / Self::SIZE;
+                let factor = self
+                    .snap(factor, settings.scale_step)
+                    .max(Self::MIN_SCALE_FACTOR);
+
+                transform.scale = transform.scale * factor;
+            }
+
+            _ => return None,
+        }
+
+        Some(transform)
+    }
+
+    fn snap(&self, value: f32, step: f32) -> f32 {
+        if self.settings.snapping && step > 0.0 {
+            (value / step).round() * step
+        } else {
+            value
+        }
+    }
+
+    fn frame(&self, scene: &Scene, node: NodeId) -> Option<Frame> {
+        let world = scene.world_matrix(node);
+        let origin = (world * Vec3::zeros().as_vec4()).as_vec3();
+        let screen_origin = scene.project_point(origin)?;
+
+        let local =
+            self.settings.space == GizmoSpace::Local || self.settings.mode == GizmoMode::Scale;
+
+        let axes = std::array::from_fn(|i| {
+            let mut unit = [0.0; 3];
+            unit[i] = 1.0;
+            let unit = Vec3::new(unit);
+
+            let dir = (world * Vec4::new([unit.x(), unit.y(), unit.z(), 0.0])).as_vec3();
+            if local && dir.norm() > EPS {
+                dir.normalized()
+            } else {
+                unit
+            }
+        });
+
+        // O vetor v da câmera é paralelo à tela, então a sua projeção dá o
+        // número de pixeis por unidade do mundo na profundidade do gizmo.
+        let (_, up, _) = scene.camera.basis();
+        let pixels_per_unit = scene.project_point(origin + up)?.dist(screen_origin);
+        if pixels_per_unit <= EPS {
+            return None;
+        }
+
+        Some(Frame {
+            origin,
+            axes,
+            length: Self::SIZE / pixels_per_unit,
+            screen_origin,
+        })
+    }
+
+    // Point of the axis or of the plane of the handle hit by the ray through
+    // the mouse: the point of the axis closest to the ray, or the
+    // intersection with the plane. None when the ray is parallel to them.
+    fn handle_point(
+        &self,
+        scene: &Scene,
+        frame: &Frame,
+        handle: Handle,
+        mouse: Vec2,
+    ) -> Option<Vec3> {
+        let ray_origin = scene.camera.get_pos();
+        let ray = scene.screen_ray(mouse.x, mouse.y);
+
+        let plane_hit = |normal: Vec3| {
+            let cos = ray.dot(normal);
+            if cos.abs() <= 1e-4 {
+                return None;
+            }
+
+            let t = (frame.origin - ray_origin).dot(normal) / cos;
+            (t > 0.0).then(|| ray_origin + ray * t)
+        };
+
+        match (self.settings.mode, handle) {
+            (GizmoMode::Rotate, Handle::Axis(axis)) => plane_hit(frame.axes[axis]),
+
+            (_, Handle::Axis(axis)) => {
+                // Pontos mais próximos entre a reta do eixo e a do raio,
+                // ambas com direções unitárias.
+                let axis = frame.axes[axis];
+                let cos = axis.dot(ray);
+                let denominator = 1.0 - cos * cos;
+                if denominator <= 1e-4 {
+                    return None;
+                }
+
+                let offset = frame.origin - ray_origin;
+                let s = (cos * ray.dot(offset) - axis.dot(offset)) / denominator;
+
+                Some(frame.origin + axis * s)
+            }
+
+            (_, Handle::Plane(axis)) => {
+                let normal = frame.axes[(axis + 1) % 3]
+                    .cross(frame.axes[(axis + 2) % 3])
+                    .normalized();
+                plane_hit(normal)
+            }
+
+            (_, Handle::Center) => Some(frame.origin),
+        }
+    }
+
+    fn project(scene: &Scene, frame: &Frame, offset: Vec3) -> Option<Vec2> {
+        scene.project_point(frame.origin + offset * frame.length)
+    }
+
+    // Corners of the square of the handle of the plane perpendicular to
+    // `axis`, on the screen.
+    fn plane_corners(scene: &Scene, frame: &Frame, axis: usize) -> Option<[Vec2; 4]> {
+        let u = frame.axes[(axis + 1) % 3];
+        let v = frame.axes[(axis + 2) % 3];
+        let (start, end) = Self::PLANE_HANDLE;
+
+        Some([
+            Self::project(scene, frame, u * start + v * start)?,
+            Self::project(scene, frame, u * end + v * start)?,
+            Self::project(scene, frame, u * end + v * end)?,
+            Self::project(scene, frame, u * start + v * end)?,
+        ])
+    }
+
+    // Points of the rotation ring around `axis`, on the screen.
+    fn ring_points(scene: &Scene, frame: &Frame, axis: usize) -> Vec<Vec2> {
+        let u = frame.axes[(axis + 1) % 3];
+        let v = frame.axes[(axis + 2) % 3];
+
+        (0..=Self::RING_SEGMENTS)
+            .filter_map(|i| {
+                let angle = i as f32 / Self::RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                Self::project(scene, frame, u * angle.cos() + v * angle.sin())
+            })
+            .collect()
+    }
+
+    fn pick_handle(&self, scene: &Scene, frame: &Frame, mouse: Vec2) -> Option<Handle> {
+        let mut candidates: Vec<(Handle, f32)> = vec![];
+
+        match self.settings.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                // Os eixos apontados para a câmera não podem ser arrastados.
+                for axis in 0..3 {
+                    let tip = Self::project(scene, frame, frame.axes[axis])
+                        .filter(|tip| tip.dist(frame.screen_origin) >= Self::PICK_DISTANCE);
+
+                    if let Some(tip) = tip {
+                        let distance = distance_to_segment(mouse, frame.screen_origin, tip);
+                        candidates.push((Handle::Axis(axis), distance));
+                    }
+                }
+            }
+            GizmoMode::Rotate => {
+                // Nem os anéis vistos de lado, cujo plano contém o raio.
+                let ray = scene.screen_ray(frame.screen_origin.x, frame.screen_origin.y);
+
+                for axis in (0..3).filter(|&axis| ray.dot(frame.axes[axis]).abs() > 0.05) {
+                    let points = Self::ring_points(scene, frame, axis);
+                    let distance = points
+                        .windows(2)
+                        .map(|segment| distance_to_segment(mouse, segment[0], segment[1]))
+                        .fold(f32::INFINITY, f32::min);
+                    candidates.push((Handle::Axis(axis), distance));
+                }
+            }
+        }
+
+        // Os quadrados ficam na frente dos eixos.
+        match self.settings.mode {
+            GizmoMode::Translate => {
+                // Nem os planos vistos de lado.
+                for axis in 0..3 {
+                    if Self::plane_corners(scene, frame, axis).is_some_and(|corners| {
+                        quad_area(&corners) >= Self::PICK_DISTANCE * Self::PICK_DISTANCE
+                            && inside_quad(mouse, &corners)
+                    }) {
+                        return Some(Handle::Plane(axis));
+                    }
+                }
+            }
+            GizmoMode::Scale => {
+                let offset = mouse - frame.screen_origin;
+                if offset.x.abs() <= Self::BOX_SIZE * 1.5 && offset.y.abs() <= Self::BOX_SIZE * 1.5
+                {
+                    return Some(Handle::Center);
+                }
+            }
+            GizmoMode::Rotate => {}
+        }
+
+        candidates
+            .into_iter()
+            .filter(|(_, distance)| *distance <= Self::PICK_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(handle, _)| handle)
+    }
+
+    fn color(&self, handle: Handle) -> Color {
+        let active = match &self.drag {
+            Some(drag) => drag.handle == handle,
+            None => self.hovered == Some(handle),
+        };
+
+        if active {
+            return Color::Custom([250, 220, 40]);
+        }
+
+        match handle {
+            Handle::Axis(0) | Handle::Plane(0) => Color::Custom([220, 50, 50]),
+            Handle::Axis(1) | Handle::Plane(1) => Color::Custom([60, 200, 60]),
+            Handle::Axis(_) | Handle::Plane(_) => Color::Custom([60, 90, 230]),
+            Handle::Center => Color::Custom([220, 220, 220]),
+        }
+    }
+
+    // Draws the gizmo of the node over the canvas. Must be called after the
+    // scene is rendered.
+    pub fn draw(&self, scene: &Scene, node: NodeId, canvas: &mut Canvas) {
+        let Some(frame) = self.frame(scene, node) else {
+            return;
+        };
+
+        // Coordenadas do canvas, sem o deslocamento da tela.
+        let offset = canvas.offset();
+        let origin = frame.screen_origin - offset;
+
+        canvas.disable_depth_update();
+
+        match self.settings.mode {
+            GizmoMode::Translate => {
+                for axis in 0..3 {
+                    if let Some(corners) = Self::plane_corners(scene, &frame, axis) {
+                        let [a, b, c, d] = corners.map(|corner| corner - offset);
+                        let color = self.color(Handle::Plane(axis));
+
+                        self.fill_triangle(canvas, [a, b, c], color, 0.4);
+                        self.fill_triangle(canvas, [a, c, d], color, 0.4);
+                    }
+                }
+
+                for axis in 0..3 {
+                    let Some(tip) = Self::project(scene, &frame, frame.axes[axis]) else {
+                        continue;
+                    };
+                    let tip = tip - offset;
+                    let color = self.color(Handle::Axis(axis));
+
+                    draw_thick_line(canvas, origin, tip, color);
+
+                    // Ponta da seta, na direção do eixo na tela.
+                    let length = tip.dist(origin);
+                    if length > 1.0 {
+                        let dir = (tip - origin) / length;
+                        let side = Vec2::new(-dir.y, dir.x) * 5.0;
+
+                        self.fill_triangle(
+                            canvas,
+                            [tip + side, tip - side, tip + dir * 14.0],
+                            color,
+                            1.0,
+                        );
+                    }
+                }
+            }
+
+            GizmoMode::Rotate => {
+                for axis in 0..3 {
+                    let color = self.color(Handle::Axis(axis));
+                    let points = Self::ring_points(scene, &frame, axis);
+
+                    for segment in points.windows(2) {
+                        draw_thick_line(canvas, segment[0] - offset, segment[1] - offset, color);
+                    }
+                }
+            }
+
+            GizmoMode::Scale => {
+                for axis in 0..3 {
+                    let Some(tip) = Self::project(scene, &frame, frame.axes[axis]) else {
+                        continue;
+                    };
+                    let tip = tip - offset;
+                    let color = self.color(Handle::Axis(axis));
+
+                    draw_thick_line(canvas, origin, tip, color);
+                    self.fill_square(canvas, tip, Self::BOX_SIZE, color);
+                }
+
+                self.fill_square(
+                    canvas,
+                    origin,
+                    Self::BOX_SIZE * 1.5,
+                    self.color(Handle::Center),
+                );
+            }
+        }
+    }
+
+    fn fill_triangle(&self, canvas: &mut Canvas, vertices: [Vec2; 3], color: Color, alpha: f32) {
+        let [a, b, c] = vertices.map(|screen_coord| VertexSimpleAttributes {
+            screen_coord,
+            texture_coord: Vec2::new(0.0, 0.0),
+            color,
+            alpha,
+        });
+
+        canvas.draw_triangle(a, b, c, Some(&self.texture), None);
+    }
+
+    fn fill_square(&self, canvas: &mut Canvas, center: Vec2, half_size: f32, color: Color) {
+        let a = center + Vec2::new(-half_size, -half_size);
+        let b = center + Vec2::new(half_size, -half_size);
+        let c = center + Vec2::new(half_size, half_size);
+        let d = center + Vec2::new(-half_size, half_size);
+
+        self.fill_triangle(canvas, [a, b, c], color, 1.0);
+        self.fill_triangle(canvas, [a, c, d], color, 1.0);
+    }
+}
+
+// Linha de dois pixeis de largura.
+fn draw_thick_line(canvas: &mut Canvas, a: Vec2, b: Vec2, color: Color) {
+    let delta = b - a;
+    let step = if delta.x.abs() > delta.y.abs() {
+        Vec2::new(0.0, 1.0)
+    } else {
+        Vec2::new(1.0, 0.0)
+    };
+
+    canvas.draw_line(a, b, color);
+    canvas.draw_line(a + step, b + step, color);
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.x * ab.x + ab.y * ab.y;
+    if length_squared <= EPS {
+        return point.dist(a);
+    }
+
+    let ap = point - a;
+    let t = ((ap.x * ab.x + ap.y * ab.y) / length_squared).clamp(0.0, 1.0);
+
+    point.dist(a + ab * t)
+}
+
+fn quad_area(corners: &[Vec2; 4]) -> f32 {
+    let twice_area: f32 = (0..4)
+        .map(|i| {
+            let a = corners[i];
+            let b = corners[(i + 1) % 4];
+            a.x * b.y - b.x * a.y
+        })
+        .sum();
+
+    twice_area.abs() / 2.0
+}
+
+// Whether `point` is inside the convex quadrilateral, in either orientation.
+fn inside_quad(point: Vec2, corners: &[Vec2; 4]) -> bool {
+    let sides: Vec<f32> = (0..4)
+        .map(|i| {
+            let a = corners[i];
+            let b = corners[(i + 1) % 4];
+            (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x)
+        })
+        .collect();
+
+    sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::scene::{Camera, SceneGraph};
+
+    const TOLERANCE: f32 = 1e-3;
+
+    fn assert_vec3_close(a: Vec3, b: Vec3) {
+        assert!((a - b).norm() < TOLERANCE, "{:?} != {:?}", a, b);
+    }
+
+    // Cena com um nó na origem, vista de cima e de lado, para que nenhum
+    // eixo, plano ou anel do gizmo fique de perfil.
+    fn scene_with_node() -> (Scene, NodeId) {
+        let mut scene = Scene::new(400, 400);
+
+        let camera_pos = Vec3::new([4.0, 5.0, 10.0]);
+        scene.camera = Camera::with_frustum(camera_pos, -camera_pos, 1.0, 60.0, -1.0, -100.0);
+        scene.camera.update_basis();
+
+        let node = scene.add_node("node".to_string(), SceneGraph::ROOT, Transform::identity());
+
+        (scene, node)
+    }
+
+    fn gizmo(mode: GizmoMode) -> Gizmo {
+        let mut gizmo = Gizmo::new();
+        gizmo.settings.mode = mode;
+        gizmo
+    }
+
+    // Drags the handle under the projection of `from` to the projection of
+    // `to`.
+    fn drag(gizmo: &mut Gizmo, scene: &Scene, node: NodeId, from: Vec3, to: Vec3) -> Transform {
+        let from = scene.project_point(from).unwrap();
+        let to = scene.project_point(to).unwrap();
+
+        assert!(gizmo.begin_drag(scene, node, from), "no handle picked");
+        gizmo.drag(scene, to).unwrap()
+    }
+
+    fn dragged_handle(gizmo: &Gizmo) -> Handle {
+        gizmo.drag.as_ref().unwrap().handle
+    }
+
+    #[test]
+    fn snapping() {
+        let mut gizmo = Gizmo::new();
+        assert_eq!(gizmo.snap(1.26, 0.5), 1.26);
+
+        gizmo.settings.snapping = true;
+        assert_eq!(gizmo.snap(1.26, 0.5), 1.5);
+        assert_eq!(gizmo.snap(-1.26, 0.5), -1.5);
+        assert_eq!(gizmo.snap(1.26, 0.0), 1.26);
+    }
+
+    #[test]
+    fn axis_drag_follows_the_axis() {
+        let (scene, node) = scene_with_node();
+        let mut gizmo = gizmo(GizmoMode::Translate);
+        let length = gizmo.frame(&scene, node).unwrap().length;
+
+        let x = Vec3::new([1.0, 0.0, 0.0]);
+        let start = x * (length * 0.6);
+
+        let transform = drag(&mut gizmo, &scene, node, start, start + x * 2.0);
+        assert_eq!(dragged_handle(&gizmo), Handle::Axis(0));
+        assert_vec3_close(transform.translation, x * 2.0);
+
+        // O mouse fora do eixo só move o nó ao longo dele.
+        let off_axis = scene
+            .project_point(start + Vec3::new([2.0, 0.7, -0.4]))
+            .unwrap();
+        let transform = gizmo.drag(&scene, off_axis).unwrap();
+        assert_eq!(transform.translation.y(), 0.0);
+        assert_eq!(transform.translation.z(), 0.0);
+
+        gizmo.settings.snapping = true;
+        gizmo.settings.translation_step = 1.5;
+        let transform = drag(&mut gizmo, &scene, node, start, start + x * 2.0);
+        assert_vec3_close(transform.translation, x * 1.5);
+    }
+
+    #[test]
+    fn plane_drag_follows_the_plane() {
+        let (scene, node) = scene_with_node();
+        let mut gizmo = gizmo(GizmoMode::Translate);
+        let length = gizmo.frame(&scene, node).unwrap().length;
+
+        // Centro do quadrado do plano xy, perpendicular ao eixo z.
+        let start = Vec3::new([1.0, 1.0, 0.0]) * (length * 0.35);
+        let offset = Vec3::new([1.0, -0.5, 0.0]);
+
+        let transform = drag(&mut gizmo, &scene, node, start, start + offset);
+        assert_eq!(dragged_handle(&gizmo), Handle::Plane(2));
+        assert_vec3_close(transform.translation, offset);
+    }
+
+    #[test]
+    fn rotation_angle_from_drag() {
+        let (scene, node) = scene_with_node();
+        let mut gizmo = gizmo(GizmoMode::Rotate);
+        let length = gizmo.frame(&scene, node).unwrap().length;
+
+        // Pontos do anel em torno de z, longe dos outros anéis.
+        let ring = |angle: f32| Vec3::new([angle.cos(), angle.sin(), 0.0]) * length;
+        let z = Vec3::new([0.0, 0.0, 1.0]);
+
+        let transform = drag(&mut gizmo, &scene, node, ring(0.8), ring(1.3));
+        assert_eq!(dragged_handle(&gizmo), Handle::Axis(2));
+        let expected = Quat::from_axis_angle(z, 0.5);
+        assert!((transform.rotation.dot(expected).abs() - 1.0).abs() < TOLERANCE);
+
+        // 0.5 rad ≈ 28.6°, arredondado para 30°.
+        gizmo.settings.snapping = true;
+        let transform = drag(&mut gizmo, &scene, node, ring(0.8), ring(1.3));
+        let expected = Quat::from_axis_angle(z, 30f32.to_radians());
+        assert!((transform.rotation.dot(expected).abs() - 1.0).abs() < TOLERANCE);
+    }
+}
@@ -5,17 +5,78 @@ use material_icons::Icon;
 
 use super::window::{Button, Event, Key, MouseCursor, Window};
 
-use super::{GuiAction, ImgFileFormat};
-
-use crate::renderer::canvas::{Canvas, Color, Rectangle, VertexSimpleAttributes};
-use crate::renderer::linalg::Vec2;
-use crate::renderer::scene::ObjectInfo;
-use crate::renderer::scene::{Texture, TextureMap};
+use super::gizmo::{GizmoMode, GizmoSettings, GizmoSpace};
+use super::{GuiAction, ImgFileFormat, RenderSettings};
+
+use crate::renderer::canvas::{
+    Canvas, Color, Msaa, Rectangle, SimdLevel, ToneMapping, VertexSimpleAttributes,
+};
+use crate::renderer::environment::Background;
+use crate::renderer::light::{Light, LightKind};
+use crate::renderer::linalg::{Quat, Vec2, Vec3};
+use crate::renderer::scene::Transform;
+use crate::renderer::scene::{
+    MaterialModel, NodeId, ObjectInfo, PickHit, RenderStats, SceneGraph, ShadingModel, SurfaceInfo,
+};
+use crate::renderer::scene::{Texture, TextureFilter, TextureMap, WrapMode};
 
 #[derive(Default)]
 struct GuiWindowsVisibility {
     models: bool,
     shortcuts: bool,
+    lights: bool,
+    background: bool,
+    stats: bool,
+    inspector: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackgroundKind {
+    Color,
+    Gradient,
+    Panorama,
+    CubeMap,
+}
+
+// Background shown by the Background window. The images are kept only by
+// the scene.
+struct BackgroundSettings {
+    kind: BackgroundKind,
+    color: Vec3,
+    top: Vec3,
+    bottom: Vec3,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self {
+            kind: BackgroundKind::Color,
+            color: Canvas::clear_color(),
+            top: Vec3::new([0.3, 0.5, 0.9]),
+            bottom: Vec3::new([0.05, 0.05, 0.05]),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Selection {
+    // Id of the object selected in the Models window.
+    object: Option<u32>,
+    // Triangle clicked in the viewport, of the selected object.
+    picked: Option<PickHit>,
+    // Whether the Models window must open and scroll to the selected object.
+    reveal: bool,
+}
+
+// Pixel under the mouse, shown by the Inspector window.
+pub struct PixelInfo {
+    // Position in the canvas, from its bottom left corner.
+    pub x: usize,
+    pub y: usize,
+    pub color: [u8; 3],
+    // None when no triangle wrote the depth of the pixel.
+    pub depth: Option<f32>,
+    pub surface: Option<SurfaceInfo>,
 }
 
 pub struct Gui {
@@ -25,11 +86,24 @@ pub struct Gui {
     font_texture: Texture,
 
     windows_visibility: GuiWindowsVisibility,
+    render_settings: RenderSettings,
+    gizmo_settings: GizmoSettings,
 
     hide_native_cursor: bool,
     current_mouse_cursor: Option<ig::MouseCursor>,
 
     objects_list: Vec<ObjectInfo>,
+    selection: Selection,
+
+    // Copy of the scene lights edited by the Lights window.
+    lights: Vec<Light>,
+    ambient_light: Vec3,
+
+    background: BackgroundSettings,
+
+    // Counters of the last frame rendered, shown by the Stats window.
+    render_stats: RenderStats,
+    inspected_pixel: Option<PixelInfo>,
 }
 
 impl Gui {
@@ -39,6 +113,94 @@ impl Gui {
         self.objects_list.push(obj_info);
     }
 
+    pub fn selected_object(&self) -> Option<&ObjectInfo> {
+        let id = self.selection.object?;
+        self.objects_list.iter().find(|obj_info| obj_info.id == id)
+    }
+
+    pub fn picked(&self) -> Option<&PickHit> {
+        self.selection.picked.as_ref()
+    }
+
+    // Selects the object of the triangle clicked in the viewport, and shows
+    // it in the Models window, or clears the selection on a miss.
+    pub fn set_picked(&mut self, hit: Option<PickHit>) {
+        if hit.is_some() {
+            self.windows_visibility.models = true;
+        }
+
+        self.selection = Selection {
+            object: hit.as_ref().map(|hit| hit.object_id),
+            reveal: hit.is_some(),
+            picked: hit,
+        };
+    }
+
+    // Transform shown for the object of the node `node`.
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) {
+        if let Some(obj_info) = self.objects_list.iter_mut().find(|obj| obj.node == node) {
+            obj_info.transform = transform;
+        }
+    }
+
+    // Parent node shown for the object of the node `node`.
+    pub fn set_parent(&mut self, node: NodeId, parent: NodeId) {
+        if let Some(obj_info) = self.objects_list.iter_mut().find(|obj| obj.node == node) {
+            obj_info.parent = parent;
+        }
+    }
+
+    pub fn render_settings(&self) -> &RenderSettings {
+        &self.render_settings
+    }
+
+    pub fn gizmo_settings(&self) -> &GizmoSettings {
+        &self.gizmo_settings
+    }
+
+    pub fn gizmo_settings_mut(&mut self) -> &mut GizmoSettings {
+        &mut self.gizmo_settings
+    }
+
+    // Whether the mouse is over a window of the GUI or in use by it.
+    pub fn wants_mouse(&self) -> bool {
+        self.imgui.io().want_capture_mouse
+    }
+
+    // Whether a widget of the GUI, like a text field, takes the keyboard.
+    pub fn wants_keyboard(&self) -> bool {
+        self.imgui.io().want_capture_keyboard
+    }
+
+    pub fn set_lights(&mut self, lights: &[Light], ambient_light: Vec3) {
+        self.lights = lights.to_vec();
+        self.ambient_light = ambient_light;
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn ambient_light(&self) -> Vec3 {
+        self.ambient_light
+    }
+
+    pub fn set_render_stats(&mut self, stats: RenderStats) {
+        self.render_stats = stats;
+    }
+
+    pub fn inspector_open(&self) -> bool {
+        self.windows_visibility.inspector
+    }
+
+    pub fn set_inspected_pixel(&mut self, pixel: Option<PixelInfo>) {
+        self.inspected_pixel = pixel;
+    }
+
+    pub fn set_background_kind(&mut self, kind: BackgroundKind) {
+        self.background.kind = kind;
+    }
+
     pub fn update_display_size(&mut self, width: usize, height: usize) {
         self.width = width;
         self.height = height;
@@ -98,12 +260,15 @@ impl Gui {
         );
         */
 
-        let f_texture = Texture::with_diffuse_map(TextureMap::new(
+        let mut font_map = TextureMap::new(
             font_atlas_texture.data.to_vec(),
             font_atlas_texture.width as _,
             font_atlas_texture.height as _,
             4,
-        ));
+        );
+        font_map.set_wrap(WrapMode::ClampToEdge);
+
+        let f_texture = Texture::with_diffuse_map(font_map);
 
         assert!(font_atlas.is_built());
 
@@ -116,11 +281,22 @@ impl Gui {
             font_texture: f_texture,
 
             windows_visibility: Default::default(),
+            render_settings: Default::default(),
+            gizmo_settings: Default::default(),
 
             hide_native_cursor: false,
             current_mouse_cursor: None,
 
             objects_list: vec![],
+            selection: Selection::default(),
+
+            lights: vec![],
+            ambient_light: Vec3::zeros(),
+
+            background: Default::default(),
+
+            render_stats: RenderStats::default(),
+            inspected_pixel: None,
         }
     }
 
@@ -274,6 +450,8 @@ impl Gui {
         ui: &mut ig::Ui,
         width: usize,
         windows_visibility: &mut GuiWindowsVisibility,
+        render_settings: &mut RenderSettings,
+        gizmo_settings: &mut GizmoSettings,
         user_action: &mut Option<GuiAction>,
     ) {
         ui.window("top_menu")
@@ -324,6 +502,219 @@ impl Gui {
                         {
                             windows_visibility.shortcuts = !windows_visibility.shortcuts;
                         }
+                        if ui
+                            .menu_item_config("Lights")
+                            .selected(windows_visibility.lights)
+                            .build()
+                        {
+                            windows_visibility.lights = !windows_visibility.lights;
+                        }
+                        if ui
+                            .menu_item_config("Background")
+                            .selected(windows_visibility.background)
+                            .build()
+                        {
+                            windows_visibility.background = !windows_visibility.background;
+                        }
+                        if ui
+                            .menu_item_config("Stats")
+                            .selected(windows_visibility.stats)
+                            .build()
+                        {
+                            windows_visibility.stats = !windows_visibility.stats;
+                        }
+                        if ui
+                            .menu_item_config("Inspector")
+                            .selected(windows_visibility.inspector)
+                            .build()
+                        {
+                            windows_visibility.inspector = !windows_visibility.inspector;
+                        }
+                    }
+
+                    // Render Menu
+                    if let Some(_render_menu) = ui.begin_menu("Render") {
+                        ui.menu_item_config("Perspective-correct interpolation")
+                            .build_with_ref(&mut render_settings.perspective_correction);
+                        ui.menu_item_config("Depth prepass")
+                            .build_with_ref(&mut render_settings.depth_prepass);
+                        ui.menu_item_config("Guard band clipping")
+                            .build_with_ref(&mut render_settings.guard_band);
+                        ui.menu_item_config("Frustum culling")
+                            .build_with_ref(&mut render_settings.frustum_culling);
+
+                        if let Some(_simd_menu) = ui.begin_menu("SIMD") {
+                            let levels = [
+                                ("Off", SimdLevel::Scalar),
+                                ("SSE2 (4 pixels)", SimdLevel::Sse2),
+                                ("AVX2 (8 pixels)", SimdLevel::Avx2),
+                            ];
+
+                            for (label, level) in levels {
+                                if ui
+                                    .menu_item_config(label)
+                                    .selected(render_settings.simd == level)
+                                    .enabled(level <= SimdLevel::detect())
+                                    .build()
+                                {
+                                    render_settings.simd = level;
+                                }
+                            }
+                        }
+
+                        if let Some(_aa_menu) = ui.begin_menu("Anti-aliasing") {
+                            let modes = [
+                                ("Off", Msaa::Off),
+                                ("MSAA 2x", Msaa::X2),
+                                ("MSAA 4x", Msaa::X4),
+                                ("MSAA 8x", Msaa::X8),
+                            ];
+
+                            for (label, msaa) in modes {
+                                if ui
+                                    .menu_item_config(label)
+                                    .selected(render_settings.msaa == msaa)
+                                    .build()
+                                {
+                                    render_settings.msaa = msaa;
+                                }
+                            }
+                        }
+
+                        if let Some(_shading_menu) = ui.begin_menu("Shading") {
+                            let models = [
+                                ("Phong", ShadingModel::Phong),
+                                ("Flat", ShadingModel::Flat),
+                                ("Unlit", ShadingModel::Unlit),
+                                ("Normals", ShadingModel::Normal),
+                            ];
+
+                            for (label, shading) in models {
+                                if ui
+                                    .menu_item_config(label)
+                                    .selected(render_settings.shading == shading)
+                                    .build()
+                                {
+                                    render_settings.shading = shading;
+                                }
+                            }
+                        }
+
+                        ui.menu_item_config("Shadows")
+                            .build_with_ref(&mut render_settings.shadows);
+
+                        if let Some(_shadow_menu) =
+                            ui.begin_menu_with_enabled("Shadow map size", render_settings.shadows)
+                        {
+                            for size in [256, 512, 1024, 2048] {
+                                if ui
+                                    .menu_item_config(format!("{size} x {size}"))
+                                    .selected(render_settings.shadow_map_size == size)
+                                    .build()
+                                {
+                                    render_settings.shadow_map_size = size;
+                                }
+                            }
+                        }
+
+                        ui.menu_item_config("Order-independent transparency")
+                            .build_with_ref(&mut render_settings.oit);
+
+                        ui.menu_item_config("Environment lighting")
+                            .build_with_ref(&mut render_settings.environment_lighting);
+
+                        ui.disabled(!render_settings.environment_lighting, || {
+                            ui.slider(
+                                "Environment intensity",
+                                0.0,
+                                4.0,
+                                &mut render_settings.environment_intensity,
+                            );
+                        });
+
+                        ui.separator();
+
+                        ui.menu_item_config("HDR")
+                            .build_with_ref(&mut render_settings.hdr);
+
+                        if let Some(_tm_menu) =
+                            ui.begin_menu_with_enabled("Tone mapping", render_settings.hdr)
+                        {
+                            let operators = [
+                                ("Clamp", ToneMapping::Clamp),
+                                ("Reinhard", ToneMapping::Reinhard),
+                                ("ACES filmic", ToneMapping::AcesFilmic),
+                            ];
+
+                            for (label, tone_mapping) in operators {
+                                if ui
+                                    .menu_item_config(label)
+                                    .selected(render_settings.tone_mapping == tone_mapping)
+                                    .build()
+                                {
+                                    render_settings.tone_mapping = tone_mapping;
+                                }
+                            }
+                        }
+
+                        ui.disabled(!render_settings.hdr, || {
+                            ui.slider("Exposure", 0.1, 8.0, &mut render_settings.exposure);
+                        });
+                    }
+
+                    // Gizmo Menu
+                    if let Some(_gizmo_menu) = ui.begin_menu("Gizmo") {
+                        let modes = [
+                            ("Translate", "T", GizmoMode::Translate),
+                            ("Rotate", "R", GizmoMode::Rotate),
+                            ("Scale", "S", GizmoMode::Scale),
+                        ];
+
+                        for (label, shortcut, mode) in modes {
+                            if ui
+                                .menu_item_config(label)
+                                .shortcut(shortcut)
+                                .selected(gizmo_settings.mode == mode)
+                                .build()
+                            {
+                                gizmo_settings.mode = mode;
+                            }
+                        }
+
+                        ui.separator();
+
+                        let spaces = [
+                            ("World space", GizmoSpace::World),
+                            ("Local space", GizmoSpace::Local),
+                        ];
+
+                        for (label, space) in spaces {
+                            if ui
+                                .menu_item_config(label)
+                                .shortcut("L")
+                                .selected(gizmo_settings.space == space)
+                                .build()
+                            {
+                                gizmo_settings.space = space;
+                            }
+                        }
+
+                        ui.separator();
+
+                        ui.menu_item_config("Snapping")
+                            .build_with_ref(&mut gizmo_settings.snapping);
+
+                        ui.disabled(!gizmo_settings.snapping, || {
+                            ui.input_float(
+                                "Translation step",
+                                &mut gizmo_settings.translation_step,
+                            )
+                            .build();
+                            ui.input_float("Rotation step", &mut gizmo_settings.rotation_step)
+                                .build();
+                            ui.input_float("Scale step", &mut gizmo_settings.scale_step)
+                                .build();
+                        });
                     }
                 }
             });
@@ -344,10 +735,21 @@ impl Gui {
             .build(|| {
                 ui.text("(F5)  Toggle Camera Visualisation");
                 ui.text("(F11) Toggle Fullscreen");
+                ui.text("(T)   Translate Gizmo");
+                ui.text("(R)   Rotate Gizmo");
+                ui.text("(S)   Scale Gizmo");
+                ui.text("(L)   Toggle Gizmo Local/World Space");
+                ui.text("(Click) Select Object");
             });
     }
 
-    fn build_models_list_window(ui: &mut ig::Ui, windows_visibility: &mut GuiWindowsVisibility) {
+    fn build_models_list_window(
+        ui: &mut ig::Ui,
+        windows_visibility: &mut GuiWindowsVisibility,
+        objects_list: &mut [ObjectInfo],
+        selection: &mut Selection,
+        user_action: &mut Option<GuiAction>,
+    ) {
         ui.window("Models List")
             .bg_alpha(0.4)
             .movable(true)
@@ -355,26 +757,559 @@ impl Gui {
             .position([4., 25.0], ig::Condition::FirstUseEver)
             .opened(&mut windows_visibility.models)
             .build(|| {
-                ui.text("Models list here..");
+                if objects_list.is_empty() {
+                    ui.text("No models loaded.");
+                }
+
+                // Pais possíveis: a raiz da cena e os nós dos objetos.
+                let parents: Vec<(NodeId, String)> =
+                    std::iter::once((SceneGraph::ROOT, String::from("(scene)")))
+                        .chain(
+                            objects_list
+                                .iter()
+                                .map(|obj_info| (obj_info.node, obj_info.name.clone())),
+                        )
+                        .collect();
+
+                for obj_info in objects_list.iter_mut() {
+                    let _obj_id = ui.push_id_usize(obj_info.id as usize);
+
+                    let selected = selection.object == Some(obj_info.id);
+                    let reveal = selected && selection.reveal;
+
+                    // Clicar no nome seleciona o objeto e na seta o expande.
+                    // O objeto escolhido com o mouse na janela é expandido.
+                    let obj_node = ui
+                        .tree_node_config(&obj_info.name)
+                        .selected(selected)
+                        .open_on_arrow(true)
+                        .opened(
+                            true,
+                            if reveal {
+                                ig::Condition::Always
+                            } else {
+                                ig::Condition::Never
+                            },
+                        )
+                        .push();
+
+                    if reveal {
+                        ui.set_scroll_here_y();
+                    }
+
+                    if ui.is_item_clicked() && !ui.is_item_toggled_open() && !selected {
+                        *selection = Selection {
+                            object: Some(obj_info.id),
+                            ..Default::default()
+                        };
+                    }
+
+                    let Some(_obj_node) = obj_node else {
+                        continue;
+                    };
+
+                    if let Some(_transform_node) = ui.tree_node("Transform") {
+                        if Self::edit_transform(ui, &mut obj_info.transform) {
+                            *user_action = Some(GuiAction::SetTransform {
+                                node: obj_info.node,
+                                transform: obj_info.transform,
+                            });
+                        }
+
+                        let candidates: Vec<&(NodeId, String)> = parents
+                            .iter()
+                            .filter(|(node, _)| *node != obj_info.node)
+                            .collect();
+                        let names: Vec<&str> =
+                            candidates.iter().map(|(_, name)| name.as_str()).collect();
+                        let mut current = candidates
+                            .iter()
+                            .position(|(node, _)| *node == obj_info.parent)
+                            .unwrap_or(0);
+
+                        if ui.combo_simple_string("Parent", &mut current, &names) {
+                            *user_action = Some(GuiAction::SetParent {
+                                node: obj_info.node,
+                                parent: candidates[current].0,
+                            });
+                        }
+                    }
+
+                    for (mesh_index, mesh_info) in obj_info.mesh_info_list.iter_mut().enumerate() {
+                        let _mesh_id = ui.push_id_usize(mesh_index);
+
+                        let label = format!(
+                            "{} ({} triangles)",
+                            mesh_info.name, mesh_info.triangle_count
+                        );
+                        let picked = selection.picked.as_ref().filter(|hit| {
+                            hit.object_id == obj_info.id && hit.mesh_index == mesh_index
+                        });
+
+                        if picked.is_some() {
+                            ui.text_colored([1.0, 0.85, 0.2, 1.0], label);
+                        } else {
+                            ui.text(label);
+                        }
+
+                        if mesh_info.has_normal_map {
+                            ui.same_line();
+                            if ui.checkbox("Normal map", &mut mesh_info.normal_mapping) {
+                                *user_action = Some(GuiAction::SetNormalMapping {
+                                    object_id: obj_info.id,
+                                    mesh_index,
+                                    enabled: mesh_info.normal_mapping,
+                                });
+                            }
+                        }
+
+                        if mesh_info.texture_name.is_some() {
+                            for (label, model) in
+                                [("Phong", MaterialModel::Phong), ("PBR", MaterialModel::Pbr)]
+                            {
+                                ui.same_line();
+                                if ui.radio_button(label, &mut mesh_info.material_model, model) {
+                                    *user_action = Some(GuiAction::SetMaterialModel {
+                                        object_id: obj_info.id,
+                                        mesh_index,
+                                        model,
+                                    });
+                                }
+                            }
+
+                            const FILTERS: [(&str, TextureFilter); 4] = [
+                                ("Nearest", TextureFilter::Nearest),
+                                ("Bilinear", TextureFilter::Bilinear),
+                                ("Trilinear", TextureFilter::Trilinear),
+                                ("Anisotropic", TextureFilter::Anisotropic),
+                            ];
+
+                            let names = FILTERS.map(|(name, _)| name);
+                            let mut current = FILTERS
+                                .iter()
+                                .position(|(_, filter)| *filter == mesh_info.texture_filter)
+                                .unwrap_or(0);
+
+                            ui.set_next_item_width(110.0);
+                            if ui.combo_simple_string("Filter", &mut current, &names) {
+                                *user_action = Some(GuiAction::SetTextureFilter {
+                                    object_id: obj_info.id,
+                                    mesh_index,
+                                    filter: FILTERS[current].1,
+                                });
+                            }
+                        }
+
+                        if let Some(hit) = picked {
+                            ui.text_disabled(format!(
+                                "  Triangle {} at ({:.2}, {:.2}, {:.2})",
+                                hit.triangle,
+                                hit.point.x(),
+                                hit.point.y(),
+                                hit.point.z()
+                            ));
+                        }
+                    }
+
+                    // O modelo é do material, compartilhado pelas outras
+                    // meshes com a mesma textura.
+                    if let Some(GuiAction::SetMaterialModel {
+                        object_id,
+                        mesh_index,
+                        model,
+                    }) = *user_action
+                    {
+                        if object_id == obj_info.id {
+                            let texture_name =
+                                obj_info.mesh_info_list[mesh_index].texture_name.clone();
+
+                            obj_info
+                                .mesh_info_list
+                                .iter_mut()
+                                .filter(|mesh_info| mesh_info.texture_name == texture_name)
+                                .for_each(|mesh_info| mesh_info.material_model = model);
+                        }
+                    }
+
+                    // O filtro também vale para todos os mapas do material.
+                    if let Some(GuiAction::SetTextureFilter {
+                        object_id,
+                        mesh_index,
+                        filter,
+                    }) = *user_action
+                    {
+                        if object_id == obj_info.id {
+                            let texture_name =
+                                obj_info.mesh_info_list[mesh_index].texture_name.clone();
+
+                            obj_info
+                                .mesh_info_list
+                                .iter_mut()
+                                .filter(|mesh_info| mesh_info.texture_name == texture_name)
+                                .for_each(|mesh_info| mesh_info.texture_filter = filter);
+                        }
+                    }
+                }
+            });
+    }
+
+    fn edit_vec3(ui: &ig::Ui, label: &str, value: &mut Vec3) {
+        let mut data = [value.x(), value.y(), value.z()];
+        if ui.input_float3(label, &mut data).build() {
+            *value = Vec3::new(data);
+        }
+    }
+
+    // Edits the translation, the rotation, as Euler angles in degrees, and
+    // the scale. Returns whether the transform changed.
+    fn edit_transform(ui: &ig::Ui, transform: &mut Transform) -> bool {
+        let mut changed = false;
+
+        let mut translation = [
+            transform.translation.x(),
+            transform.translation.y(),
+            transform.translation.z(),
+        ];
+        if ui.input_float3("Translation", &mut translation).build() {
+            transform.translation = Vec3::new(translation);
+            changed = true;
+        }
+
+        let angles = transform.rotation.to_euler();
+        let mut rotation = [
+            angles.x().to_degrees(),
+            angles.y().to_degrees(),
+            angles.z().to_degrees(),
+        ];
+        if ui.input_float3("Rotation", &mut rotation).build() {
+            let angles = Vec3::new(rotation.map(f32::to_radians));
+            transform.rotation = Quat::from_euler(angles);
+            changed = true;
+        }
+
+        let mut scale = [
+            transform.scale.x(),
+            transform.scale.y(),
+            transform.scale.z(),
+        ];
+        if ui.input_float3("Scale", &mut scale).build() {
+            transform.scale = Vec3::new(scale);
+            changed = true;
+        }
+
+        changed
+    }
+
+    fn edit_color(ui: &ig::Ui, label: &str, value: &mut Vec3) -> bool {
+        let mut data = [value.x(), value.y(), value.z()];
+        let changed = ui.color_edit3(label, &mut data);
+        if changed {
+            *value = Vec3::new(data);
+        }
+
+        changed
+    }
+
+    fn build_light_editor(ui: &ig::Ui, light: &mut Light) {
+        match &mut light.kind {
+            LightKind::Directional { direction } => {
+                Self::edit_vec3(ui, "Direction", direction);
+            }
+            LightKind::Point { position, range } => {
+                Self::edit_vec3(ui, "Position", position);
+                ui.slider("Range", 1.0, 5000.0, range);
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                range,
+                inner_angle,
+                outer_angle,
+            } => {
+                Self::edit_vec3(ui, "Position", position);
+                Self::edit_vec3(ui, "Direction", direction);
+                ui.slider("Range", 1.0, 5000.0, range);
+                ui.slider("Inner angle", 0.0, 90.0, inner_angle);
+                ui.slider("Outer angle", 0.0, 90.0, outer_angle);
+
+                *inner_angle = inner_angle.min(*outer_angle);
+            }
+        }
+
+        Self::edit_color(ui, "Color", &mut light.color);
+        ui.slider("Intensity", 0.0, 10.0, &mut light.intensity);
+        ui.checkbox("Cast shadows", &mut light.cast_shadows);
+    }
+
+    fn build_lights_window(
+        ui: &mut ig::Ui,
+        windows_visibility: &mut GuiWindowsVisibility,
+        lights: &mut [Light],
+        ambient_light: &mut Vec3,
+        user_action: &mut Option<GuiAction>,
+    ) {
+        ui.window("Lights")
+            .bg_alpha(0.4)
+            .movable(true)
+            .resizable(true)
+            .position([4., 250.0], ig::Condition::FirstUseEver)
+            .size([320., 0.0], ig::Condition::FirstUseEver)
+            .opened(&mut windows_visibility.lights)
+            .build(|| {
+                Self::edit_color(ui, "Ambient", ambient_light);
+
+                ui.separator();
+
+                let white = Vec3::new([1.0, 1.0, 1.0]);
+
+                if ui.button("Add directional") {
+                    let light = Light::directional(Vec3::new([-1.0, -1.0, -1.0]), white, 1.0);
+                    *user_action = Some(GuiAction::AddLight(light));
+                }
+                ui.same_line();
+                if ui.button("Add point") {
+                    let light = Light::point(Vec3::new([0.0, 100.0, 100.0]), 1000.0, white, 1.0);
+                    *user_action = Some(GuiAction::AddLight(light));
+                }
+                ui.same_line();
+                if ui.button("Add spot") {
+                    let light = Light::spot(
+                        Vec3::new([0.0, 200.0, 200.0]),
+                        Vec3::new([0.0, -1.0, -1.0]),
+                        2000.0,
+                        15.0,
+                        25.0,
+                        white,
+                        1.0,
+                    );
+                    *user_action = Some(GuiAction::AddLight(light));
+                }
+
+                for (index, light) in lights.iter_mut().enumerate() {
+                    let _id = ui.push_id_usize(index);
+
+                    let kind = match light.kind {
+                        LightKind::Directional { .. } => "Directional",
+                        LightKind::Point { .. } => "Point",
+                        LightKind::Spot { .. } => "Spot",
+                    };
+
+                    if ui.collapsing_header(
+                        format!("{kind} light {index}"),
+                        ig::TreeNodeFlags::DEFAULT_OPEN,
+                    ) {
+                        Self::build_light_editor(ui, light);
+
+                        if ui.button("Remove") {
+                            *user_action = Some(GuiAction::RemoveLight(index));
+                        }
+                    }
+                }
+            });
+    }
+
+    fn build_background_window(
+        ui: &mut ig::Ui,
+        windows_visibility: &mut GuiWindowsVisibility,
+        background: &mut BackgroundSettings,
+        user_action: &mut Option<GuiAction>,
+    ) {
+        ui.window("Background")
+            .bg_alpha(0.4)
+            .movable(true)
+            .resizable(true)
+            .position([4., 450.0], ig::Condition::FirstUseEver)
+            .size([320., 0.0], ig::Condition::FirstUseEver)
+            .opened(&mut windows_visibility.background)
+            .build(|| {
+                let mut changed =
+                    ui.radio_button("Color", &mut background.kind, BackgroundKind::Color);
+                if background.kind == BackgroundKind::Color {
+                    changed |= Self::edit_color(ui, "##color", &mut background.color);
+                }
+
+                changed |=
+                    ui.radio_button("Gradient", &mut background.kind, BackgroundKind::Gradient);
+                if background.kind == BackgroundKind::Gradient {
+                    changed |= Self::edit_color(ui, "Top", &mut background.top);
+                    changed |= Self::edit_color(ui, "Bottom", &mut background.bottom);
+                }
+
+                if changed {
+                    let scene_background = match background.kind {
+                        BackgroundKind::Gradient => Background::Gradient {
+                            top: background.top,
+                            bottom: background.bottom,
+                        },
+                        _ => Background::Color(background.color),
+                    };
+
+                    *user_action = Some(GuiAction::SetBackground(scene_background));
+                }
+
+                ui.separator();
+
+                if ui.radio_button_bool("Panorama", background.kind == BackgroundKind::Panorama) {
+                    *user_action = Some(GuiAction::LoadPanorama);
+                }
+                if ui.radio_button_bool("Cube map", background.kind == BackgroundKind::CubeMap) {
+                    *user_action = Some(GuiAction::LoadCubeMap);
+                }
+
+                ui.text_disabled(
+                    "Equirectangular image or folder with px, nx, py,\nny, pz and nz images.",
+                );
+            });
+    }
+
+    fn build_stats_window(
+        ui: &mut ig::Ui,
+        windows_visibility: &mut GuiWindowsVisibility,
+        width: usize,
+        stats: &RenderStats,
+    ) {
+        ui.window("Stats")
+            .bg_alpha(0.4)
+            .movable(true)
+            .resizable(false)
+            .position([width as f32 - 5., 160.0], ig::Condition::FirstUseEver)
+            .position_pivot([1.0, 0.0])
+            .opened(&mut windows_visibility.stats)
+            .build(|| {
+                ui.text(format!(
+                    "Objects: {} drawn, {} culled",
+                    stats.objects_drawn, stats.objects_culled
+                ));
+                ui.text(format!(
+                    "Meshes:  {} drawn, {} culled",
+                    stats.meshes_drawn, stats.meshes_culled
+                ));
+            });
+    }
+
+    fn build_inspector_window(
+        ui: &mut ig::Ui,
+        windows_visibility: &mut GuiWindowsVisibility,
+        width: usize,
+        pixel: Option<&PixelInfo>,
+    ) {
+        ui.window("Inspector")
+            .bg_alpha(0.4)
+            .movable(true)
+            .resizable(false)
+            .position([width as f32 - 5., 230.0], ig::Condition::FirstUseEver)
+            .position_pivot([1.0, 0.0])
+            .opened(&mut windows_visibility.inspector)
+            .build(|| {
+                let Some(pixel) = pixel else {
+                    ui.text("Move the mouse over the scene.");
+                    return;
+                };
+
+                let [r, g, b] = pixel.color;
+                ui.text(format!("Pixel:    ({}, {})", pixel.x, pixel.y));
+                ui.text(format!("Color:    {r} {g} {b}"));
+                ui.same_line();
+                ui.color_button(
+                    "##pixel_color",
+                    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0],
+                );
+
+                let Some(depth) = pixel.depth else {
+                    ui.text("Background");
+                    return;
+                };
+                ui.text(format!("Depth:    {depth:.4}"));
+
+                if let Some(surface) = pixel.surface.as_ref() {
+                    let (uv, n) = (surface.uv, surface.normal);
+
+                    ui.separator();
+                    ui.text(format!("Object:   {}", surface.object_name));
+                    ui.text(format!("Mesh:     {}", surface.mesh_name));
+                    ui.text(format!("Material: {}", surface.material_name));
+                    ui.text(format!("UV:       ({:.3}, {:.3})", uv.x, uv.y));
+                    ui.text(format!(
+                        "Normal:   ({:.3}, {:.3}, {:.3})",
+                        n.x(),
+                        n.y(),
+                        n.z()
+                    ));
+                }
             });
     }
 
-    fn build_windows(ui: &mut ig::Ui, width: usize, windows_visibility: &mut GuiWindowsVisibility) {
+    #[allow(clippy::too_many_arguments)]
+    fn build_windows(
+        ui: &mut ig::Ui,
+        width: usize,
+        windows_visibility: &mut GuiWindowsVisibility,
+        objects_list: &mut [ObjectInfo],
+        selection: &mut Selection,
+        lights: &mut [Light],
+        ambient_light: &mut Vec3,
+        background: &mut BackgroundSettings,
+        render_stats: &RenderStats,
+        inspected_pixel: Option<&PixelInfo>,
+        user_action: &mut Option<GuiAction>,
+    ) {
         if windows_visibility.shortcuts {
             Self::build_shortcuts_list_window(ui, windows_visibility, width);
         }
 
         if windows_visibility.models {
-            Self::build_models_list_window(ui, windows_visibility);
+            Self::build_models_list_window(
+                ui,
+                windows_visibility,
+                objects_list,
+                selection,
+                user_action,
+            );
+        }
+
+        if windows_visibility.lights {
+            Self::build_lights_window(ui, windows_visibility, lights, ambient_light, user_action);
+        }
+
+        if windows_visibility.background {
+            Self::build_background_window(ui, windows_visibility, background, user_action);
+        }
+
+        if windows_visibility.stats {
+            Self::build_stats_window(ui, windows_visibility, width, render_stats);
+        }
+
+        if windows_visibility.inspector {
+            Self::build_inspector_window(ui, windows_visibility, width, inspected_pixel);
         }
     }
 
     pub fn build_ui(&mut self, user_action: &mut Option<GuiAction>) {
         let ui = self.imgui.new_frame();
 
-        Self::build_top_menu(ui, self.width, &mut self.windows_visibility, user_action);
+        Self::build_top_menu(
+            ui,
+            self.width,
+            &mut self.windows_visibility,
+            &mut self.render_settings,
+            &mut self.gizmo_settings,
+            user_action,
+        );
 
-        Self::build_windows(ui, self.width, &mut self.windows_visibility);
+        Self::build_windows(
+            ui,
+            self.width,
+            &mut self.windows_visibility,
+            &mut self.objects_list,
+            &mut self.selection,
+            &mut self.lights,
+            &mut self.ambient_light,
+            &mut self.background,
+            &self.render_stats,
+            self.inspected_pixel.as_ref(),
+            user_action,
+        );
+        self.selection.reveal = false;
 
         //ui.show_metrics_window(&mut true);
     }
@@ -0,0 +1,76 @@
+use crate::renderer::canvas::{Canvas, Color};
+use crate::renderer::linalg::Vec3;
+use crate::renderer::scene::{PickHit, Scene};
+
+const BOX_COLOR: Color = Color::Custom([255, 150, 30]);
+const TRIANGLE_COLOR: Color = Color::Custom([250, 220, 40]);
+
+// Arestas da caixa, pelos índices dos cantos: o bit i do índice escolhe o
+// mínimo ou o máximo no eixo i.
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+// Draws over the frame the bounding box of the selected object and, when it
+// was picked in the viewport, the outline of the picked triangle.
+pub fn draw_selection(
+    scene: &Scene,
+    canvas: &mut Canvas,
+    object_id: u32,
+    picked: Option<&PickHit>,
+) {
+    if let Some(bounds) = scene.object_bounds(object_id) {
+        let corners: [Vec3; 8] = std::array::from_fn(|i| {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    bounds.min[axis]
+                } else {
+                    bounds.max[axis]
+                }
+            };
+
+            Vec3::new([pick(0), pick(1), pick(2)])
+        });
+
+        for (a, b) in BOX_EDGES {
+            draw_segment(scene, canvas, corners[a], corners[b], BOX_COLOR);
+        }
+    }
+
+    let triangle = picked
+        .filter(|hit| hit.object_id == object_id)
+        .and_then(|hit| scene.triangle_vertices(hit.object_id, hit.mesh_index, hit.triangle));
+
+    if let Some(vertices) = triangle {
+        for i in 0..3 {
+            draw_segment(
+                scene,
+                canvas,
+                vertices[i],
+                vertices[(i + 1) % 3],
+                TRIANGLE_COLOR,
+            );
+        }
+    }
+}
+
+// Segmento entre dois pontos do mundo, omitido quando uma das pontas está
+// atrás do plano near.
+fn draw_segment(scene: &Scene, canvas: &mut Canvas, a: Vec3, b: Vec3, color: Color) {
+    let offset = canvas.offset();
+
+    if let (Some(a), Some(b)) = (scene.project_point(a), scene.project_point(b)) {
+        canvas.draw_line(a - offset, b - offset, color);
+    }
+}
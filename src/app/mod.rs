@@ -1,15 +1,24 @@
+mod gizmo;
 mod gui;
+mod highlight;
 mod window;
 
 use rfd::FileDialog;
 use std::ffi::{CStr, CString};
 
-use crate::renderer::canvas::Canvas;
-use crate::renderer::scene::{Object, ObjectInfo, Scene};
+use crate::renderer::canvas::{Canvas, Msaa, SimdLevel, ToneMapping};
+use crate::renderer::environment::Background;
+use crate::renderer::light::Light;
+use crate::renderer::linalg::{Vec2, Vec3};
+use crate::renderer::scene::{
+    MaterialModel, NodeId, Object, ObjectInfo, Scene, SceneGraph, ShadingModel, TextureFilter,
+    Transform,
+};
 
+use gizmo::{Gizmo, GizmoMode, GizmoSpace};
 use gui::*;
 
-use window::{Event, Key, Window};
+use window::{Button, Event, Key, Window};
 
 //struct AppState();
 
@@ -27,6 +36,75 @@ enum GuiAction {
     Open,
     ExportAs(ImgFileFormat),
     ListModelsInfo,
+    AddLight(Light),
+    RemoveLight(usize),
+    SetNormalMapping {
+        object_id: u32,
+        mesh_index: usize,
+        enabled: bool,
+    },
+    SetMaterialModel {
+        object_id: u32,
+        mesh_index: usize,
+        model: MaterialModel,
+    },
+    SetTextureFilter {
+        object_id: u32,
+        mesh_index: usize,
+        filter: TextureFilter,
+    },
+    SetTransform {
+        node: NodeId,
+        transform: Transform,
+    },
+    SetParent {
+        node: NodeId,
+        parent: NodeId,
+    },
+    SetBackground(Background),
+    LoadPanorama,
+    LoadCubeMap,
+}
+
+// Renderer options that can be changed from the GUI.
+pub struct RenderSettings {
+    perspective_correction: bool,
+    depth_prepass: bool,
+    guard_band: bool,
+    frustum_culling: bool,
+    simd: SimdLevel,
+    msaa: Msaa,
+    shading: ShadingModel,
+    shadows: bool,
+    shadow_map_size: usize,
+    oit: bool,
+    hdr: bool,
+    tone_mapping: ToneMapping,
+    exposure: f32,
+    environment_lighting: bool,
+    environment_intensity: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            perspective_correction: true,
+            depth_prepass: false,
+            guard_band: true,
+            frustum_culling: true,
+            simd: SimdLevel::detect(),
+            msaa: Msaa::Off,
+            shading: ShadingModel::Phong,
+            shadows: true,
+            shadow_map_size: Scene::DEFAULT_SHADOW_MAP_SIZE,
+            oit: false,
+            hdr: false,
+            tone_mapping: ToneMapping::AcesFilmic,
+            exposure: 1.0,
+            environment_lighting: false,
+            environment_intensity: 1.0,
+        }
+    }
 }
 
 const PIXEL_BYTES: usize = 4;
@@ -52,6 +130,10 @@ pub struct Application {
     camera_mode: CameraNavigation,
 
     camera_moving_direction: u8,
+
+    gizmo: Gizmo,
+    // Última posição do mouse na janela.
+    mouse_position: (i32, i32),
 }
 
 impl Application {
@@ -62,9 +144,13 @@ impl Application {
         let win = Window::new(width, height);
         let (screen_width, screen_height) = win.get_screen_dim();
 
+        let scene = Scene::new(screen_width, screen_height);
+        let mut gui = Gui::new(width, height);
+        gui.set_lights(scene.lights(), scene.ambient_light());
+
         Self {
-            gui: Gui::new(width, height),
-            scene: Scene::new(screen_width, screen_height),
+            gui,
+            scene,
             win,
             canvas: Canvas::new(width, height),
             current_frame: vec![0; width * height * PIXEL_BYTES],
@@ -72,6 +158,8 @@ impl Application {
             height,
             camera_mode: CameraNavigation::Locked,
             camera_moving_direction: 0,
+            gizmo: Gizmo::new(),
+            mouse_position: (0, 0),
         }
     }
 
@@ -127,8 +215,15 @@ impl Application {
 
                     Event::MouseMotion(mouse_info) => {
                         self.move_camera_direction(mouse_info.dx, mouse_info.dy);
+
+                        self.mouse_position = (mouse_info.x, mouse_info.y);
+                        self.move_gizmo();
                     }
 
+                    Event::ButtonPress(Button::MouseLeft) => self.click(),
+
+                    Event::ButtonRelease(Button::MouseLeft) => self.gizmo.end_drag(),
+
                     Event::KeyPress(key) => {
                         print!("KeyPress ");
                         match key {
@@ -152,7 +247,9 @@ impl Application {
                             },
 
                             CameraNavigation::Locked => {
-                                // TODO.
+                                if !self.gui.wants_keyboard() {
+                                    self.change_gizmo_settings(key);
+                                }
                             }
                         }
                     }
@@ -192,8 +289,17 @@ impl Application {
                 //println!("FPS {}", 1000.0 / ms_elapsed);
                 //scene.camera_right();
                 self.move_camera_position();
+                self.apply_render_settings();
+                self.apply_light_edits();
+                self.gizmo.settings = *self.gui.gizmo_settings();
 
                 self.scene.render(&mut self.canvas);
+                self.gui.set_render_stats(self.scene.render_stats());
+
+                if self.gui.inspector_open() {
+                    let pixel = self.inspect_pixel();
+                    self.gui.set_inspected_pixel(pixel);
+                }
 
                 // Stores a copy of the current frame before
                 // the GUI is rendered.
@@ -201,6 +307,19 @@ impl Application {
                     .as_mut_slice()
                     .copy_from_slice(self.canvas.as_bytes_slice());
 
+                if let Some(obj_info) = self.gui.selected_object() {
+                    highlight::draw_selection(
+                        &self.scene,
+                        &mut self.canvas,
+                        obj_info.id,
+                        self.gui.picked(),
+                    );
+                }
+
+                if let Some(node) = self.selected_node() {
+                    self.gizmo.draw(&self.scene, node, &mut self.canvas);
+                }
+
                 self.gui.new_frame(&mut self.win, &frame_events, elapsed);
                 self.gui.build_ui(&mut user_action);
                 self.gui.render(&mut self.canvas);
@@ -219,6 +338,66 @@ impl Application {
                             self.gui.add_obj(obj_info);
                         }
                     }
+                    GuiAction::AddLight(light) => {
+                        self.scene.add_light(light);
+                        self.gui
+                            .set_lights(self.scene.lights(), self.scene.ambient_light());
+                    }
+                    GuiAction::SetNormalMapping {
+                        object_id,
+                        mesh_index,
+                        enabled,
+                    } => {
+                        self.scene
+                            .set_normal_mapping(object_id, mesh_index, enabled);
+                    }
+                    GuiAction::SetMaterialModel {
+                        object_id,
+                        mesh_index,
+                        model,
+                    } => {
+                        self.scene.set_material_model(object_id, mesh_index, model);
+                    }
+                    GuiAction::SetTextureFilter {
+                        object_id,
+                        mesh_index,
+                        filter,
+                    } => {
+                        self.scene.set_texture_filter(object_id, mesh_index, filter);
+                    }
+                    GuiAction::SetTransform { node, transform } => {
+                        self.scene.set_transform(node, transform);
+                    }
+                    GuiAction::SetParent { node, parent } => {
+                        // O pai que criaria um ciclo é recusado, então a GUI
+                        // mostra o pai mantido pela cena.
+                        self.scene.set_parent(node, parent);
+                        if let Some(parent) =
+                            self.scene.graph().node(node).and_then(|node| node.parent())
+                        {
+                            self.gui.set_parent(node, parent);
+                        }
+                    }
+                    GuiAction::SetBackground(background) => {
+                        self.scene.set_background(background);
+                    }
+                    GuiAction::LoadPanorama => {
+                        if let Some(background) = self.open_panorama_file() {
+                            self.scene.set_background(background);
+                            self.gui.set_background_kind(BackgroundKind::Panorama);
+                        }
+                    }
+                    GuiAction::LoadCubeMap => {
+                        if let Some(background) = self.open_cube_map_dir() {
+                            self.scene.set_background(background);
+                            self.gui.set_background_kind(BackgroundKind::CubeMap);
+                        }
+                    }
+                    GuiAction::RemoveLight(index) => {
+                        self.scene.remove_light(index);
+                        self.gui
+                            .set_lights(self.scene.lights(), self.scene.ambient_light());
+                    }
                     _ => {}
                 }
             }
@@ -235,6 +414,42 @@ impl Application {
         }
     }
 
+    fn apply_render_settings(&mut self) {
+        let settings = self.gui.render_settings();
+
+        self.canvas
+            .set_perspective_correction(settings.perspective_correction);
+        self.canvas.set_simd_level(settings.simd);
+        self.canvas.set_msaa(settings.msaa);
+        self.canvas.set_oit(settings.oit);
+        self.canvas.set_hdr(settings.hdr);
+        self.canvas.set_tone_mapping(settings.tone_mapping);
+        self.canvas.set_exposure(settings.exposure);
+        // O ID buffer só é mantido com o Inspector aberto.
+        self.canvas.set_id_buffer(self.gui.inspector_open());
+        self.scene.set_depth_prepass(settings.depth_prepass);
+        self.scene.set_guard_band(settings.guard_band);
+        self.scene.set_frustum_culling(settings.frustum_culling);
+        self.scene.set_shading_model(settings.shading);
+        self.scene.set_shadows(settings.shadows);
+        self.scene.set_shadow_map_size(settings.shadow_map_size);
+        self.scene
+            .set_environment_lighting(settings.environment_lighting);
+        self.scene
+            .set_environment_intensity(settings.environment_intensity);
+    }
+
+    // Copies the lights edited in the GUI to the scene.
+    fn apply_light_edits(&mut self) {
+        for (index, light) in self.gui.lights().iter().enumerate() {
+            if let Some(scene_light) = self.scene.light_mut(index) {
+                *scene_light = *light;
+            }
+        }
+
+        self.scene.set_ambient_light(self.gui.ambient_light());
+    }
+
     fn toggle_camera_mode(&mut self) {
         self.camera_mode = match self.camera_mode {
             CameraNavigation::Free => {
@@ -299,6 +514,112 @@ impl Application {
         }
     }
 
+    // Position of the mouse in the screen coordinates of the projected
+    // triangles, whose origin is the bottom left corner: the center of the
+    // pixel under it, at integer coordinates like the raster samples.
+    fn mouse_screen_position(&self) -> Vec2 {
+        let (x, y) = self.mouse_position;
+        let offset = self.canvas.offset();
+
+        Vec2::new(
+            x as f32 + offset.x,
+            (self.height as i32 - 1 - y) as f32 + offset.y,
+        )
+    }
+
+    // Pixel of the canvas under the mouse, read from the frame just
+    // rendered, before the selection and the GUI are drawn over it.
+    fn inspect_pixel(&self) -> Option<PixelInfo> {
+        let (x, y) = self.mouse_position;
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+
+        // A origem do canvas é no canto inferior esquerdo.
+        let (x, y) = (x as usize, self.height - 1 - y as usize);
+
+        let id = self.canvas.get_pixel_id(x, y);
+        let mouse = self.mouse_screen_position();
+        let surface = id.and_then(|id| self.scene.inspect(id, mouse.x, mouse.y));
+
+        // A profundidade vem do depth buffer, mesmo nos pixeis de objetos
+        // sem ID.
+        let depth = self.canvas.frame_depth(x, y);
+
+        Some(PixelInfo {
+            x,
+            y,
+            color: self.canvas.pixel_color(x, y),
+            depth: (depth < self.canvas.depth_max()).then_some(depth),
+            surface,
+        })
+    }
+
+    fn selected_node(&self) -> Option<NodeId> {
+        self.gui.selected_object().map(|obj_info| obj_info.node)
+    }
+
+    // A click on a handle of the gizmo starts dragging it, anywhere else it
+    // selects the object under the mouse.
+    fn click(&mut self) {
+        if !matches!(self.camera_mode, CameraNavigation::Locked) || self.gui.wants_mouse() {
+            return;
+        }
+
+        let mouse = self.mouse_screen_position();
+
+        if let Some(node) = self.selected_node() {
+            if self.gizmo.begin_drag(&self.scene, node, mouse) {
+                return;
+            }
+        }
+
+        let hit = self.scene.pick(mouse.x, mouse.y);
+        self.gui.set_picked(hit);
+    }
+
+    // Applies the drag of the gizmo to the selected object or highlights the
+    // handle under the mouse.
+    fn move_gizmo(&mut self) {
+        if !matches!(self.camera_mode, CameraNavigation::Locked) {
+            return;
+        }
+
+        let Some(node) = self.selected_node() else {
+            return;
+        };
+        let mouse = self.mouse_screen_position();
+
+        if self.gizmo.is_dragging() {
+            if let Some(transform) = self.gizmo.drag(&self.scene, mouse) {
+                self.scene.set_transform(node, transform);
+                self.gui.set_transform(node, transform);
+            }
+        } else {
+            self.gizmo.hover(&self.scene, node, mouse);
+        }
+    }
+
+    fn change_gizmo_settings(&mut self, key: &Key) {
+        let settings = self.gui.gizmo_settings_mut();
+
+        match key {
+            Key::T => settings.mode = GizmoMode::Translate,
+            Key::R => settings.mode = GizmoMode::Rotate,
+            Key::S => settings.mode = GizmoMode::Scale,
+            Key::L => {
+                settings.space = match settings.space {
+                    GizmoSpace::Local => GizmoSpace::World,
+                    GizmoSpace::World => GizmoSpace::Local,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Distance from the origin of the farthest vertex of the opened models.
+    const MODEL_FIT_DISTANCE: f32 = 100.0;
+
     fn open_obj_file(&mut self) -> Option<ObjectInfo> {
         let file = FileDialog::new()
             .add_filter("text", &["obj"])
@@ -307,12 +628,33 @@ impl Application {
         if let Some(file_path) = file {
             let obj = Object::load_from_file(file_path.to_str().unwrap());
 
-            Some(self.scene.add_obj(obj))
+            // Escala o modelo para que caiba na vista inicial da câmera.
+            let scale = obj.fit_scale(Self::MODEL_FIT_DISTANCE);
+            let transform = Transform {
+                scale: Vec3::new([scale, scale, scale]),
+                ..Transform::identity()
+            };
+
+            Some(self.scene.add_obj_to(obj, SceneGraph::ROOT, transform))
         } else {
             None
         }
     }
 
+    fn open_panorama_file(&self) -> Option<Background> {
+        let file = FileDialog::new()
+            .add_filter("image", &["png", "jpg", "jpeg", "bmp", "tga", "hdr"])
+            .pick_file()?;
+
+        Some(Background::load_panorama(&file))
+    }
+
+    fn open_cube_map_dir(&self) -> Option<Background> {
+        let dir = FileDialog::new().pick_folder()?;
+
+        Background::load_cube_map(&dir)
+    }
+
     fn export_frame_as(&self, img_fmt: ImgFileFormat) {
         let file_extensions = match img_fmt {
             ImgFileFormat::Jpeg => ["jpeg", "jpg"].as_slice(),
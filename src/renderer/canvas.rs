@@ -1,7 +1,13 @@
 use itertools::Either;
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Mul};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
+#[cfg(target_arch = "x86_64")]
+use super::linalg::packed::{F32x4, F32x8, PackedF32, PackedI64};
 use super::linalg::{Vec2, Vec3, Vec4};
+use super::shader::{FragmentInput, FragmentOutput, FragmentShader, Uniforms, Varyings};
+use super::srgb;
 
 // TODO: resolver dependencia cruzada :(
 use super::scene::Texture;
@@ -88,7 +94,28 @@ impl Pixel {
 
     pub fn from_normalized_vec3(src: Vec3) -> Self {
         let scaled = src * 255.0;
-        Self::new(scaled.x() as u8, scaled.y() as u8, scaled.z() as u8)
+        Self::new(
+            scaled.x().clamp(0.0, 255.0) as u8,
+            scaled.y().clamp(0.0, 255.0) as u8,
+            scaled.z().clamp(0.0, 255.0) as u8,
+        )
+    }
+
+    // Converts a linear color to sRGB, as expected by the window.
+    pub fn from_linear_vec3(src: Vec3) -> Self {
+        Self::new(
+            srgb::linear_to_srgb(src.x()),
+            srgb::linear_to_srgb(src.y()),
+            srgb::linear_to_srgb(src.z()),
+        )
+    }
+
+    pub fn as_linear_vec3(&self) -> Vec3 {
+        Vec3::new([
+            srgb::srgb_to_linear(self.r),
+            srgb::srgb_to_linear(self.g),
+            srgb::srgb_to_linear(self.b),
+        ])
     }
 
     pub fn as_vec4(&self) -> Vec4 {
@@ -137,9 +164,9 @@ impl Add for Pixel {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self {
-        let r_sum = self.r + rhs.r;
-        let g_sum = self.g + rhs.g;
-        let b_sum = self.b + rhs.b;
+        let r_sum = self.r.saturating_add(rhs.r);
+        let g_sum = self.g.saturating_add(rhs.g);
+        let b_sum = self.b.saturating_add(rhs.b);
 
         Pixel {
             r: r_sum,
@@ -190,104 +217,273 @@ pub struct VertexSimpleAttributes {
     pub alpha: f32,
 }
 
+// Vértice de um triângulo projetado em coordenadas de janela.
 #[derive(Debug, Copy, Clone)]
-pub struct VertexAttributes {
-    //color:  Color,
-    normal: Vec3,
-    light: Vec3,
-    //eye:            Vec3,
-    halfway: Vec3,
-    texture_coord: Vec3,
+pub struct ProjectedVertex<V> {
     pub screen_coord: Vec2,
-    depth: f32,
+    pub depth: f32,
+    // 1/w of the vertex after the projection, used by the
+    // perspective-correct interpolation.
+    pub inv_w: f32,
+    pub varyings: V,
 }
 
-impl VertexAttributes {
-    pub fn new(
-        screen_coord: Vec2,
-        //color:  Color,
-        depth: f32,
-        normal: Vec3,
-        light: Vec3,
-        //eye:    Vec3,
-        halfway: Vec3,
-        txt_coord: Vec3,
-    ) -> Self {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthTest {
+    Less,
+    LessEqual,
+}
+
+impl DepthTest {
+    // Whether the fragment with `depth` is drawn over `current_depth`.
+    fn passes(self, depth: f32, current_depth: f32) -> bool {
+        match self {
+            DepthTest::Less => depth < current_depth,
+            DepthTest::LessEqual => depth <= current_depth,
+        }
+    }
+}
+
+// Variação das coordenadas baricêntricas (afins) entre pixels vizinhos,
+// usada para obter as derivadas das varyings.
+#[derive(Clone, Copy)]
+struct BarycentricStep {
+    dx: [f32; 3],
+    dy: [f32; 3],
+}
+
+impl BarycentricStep {
+    fn new(tri: &FixedTriangle) -> Self {
+        let area = tri.area as f32;
+
         Self {
-            screen_coord: screen_coord,
-            //color:  color,
-            normal: normal,
-            light: light,
-            //eye:    eye,
-            halfway: halfway,
-            depth: depth,
-            texture_coord: txt_coord,
+            dx: tri.edges.map(|edge| (edge.a * SUBPIXEL_ONE) as f32 / area),
+            dy: tri.edges.map(|edge| (edge.b * SUBPIXEL_ONE) as f32 / area),
         }
     }
+}
+
+// Precisão subpixel do rasterizador: os vértices são arredondados para
+// coordenadas em ponto fixo 28.4 (1/16 de pixel).
+const SUBPIXEL_BITS: u32 = 4;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+
+// Largest coordinate, in pixels, accepted by the raster. It keeps the
+// products of the edge functions far from overflowing an i64.
+const MAX_RASTER_COORD: f32 = (1 << 24) as f32;
+
+fn to_fixed(value: f32) -> i64 {
+    let value = value.clamp(-MAX_RASTER_COORD, MAX_RASTER_COORD);
+
+    (value * SUBPIXEL_ONE as f32).round() as i64
+}
+
+// Edge function E(x, y) = a * x + b * y + c over 28.4 coordinates,
+// oriented to be positive inside the triangle.
+#[derive(Clone, Copy)]
+struct Edge {
+    a: i64,
+    b: i64,
+    c: i64,
+    // Top-left rule: a sample exactly over the edge is covered only when
+    // it's a top or a left edge, so the samples of an edge shared by two
+    // triangles are drawn by exactly one of them.
+    bias: i64,
+}
+
+impl Edge {
+    fn new(p: (i64, i64), q: (i64, i64), orientation: i64) -> Self {
+        let a = (p.1 - q.1) * orientation;
+        let b = (q.0 - p.0) * orientation;
+        let c = (p.0 * q.1 - q.0 * p.1) * orientation;
+
+        // O eixo y da janela aponta para baixo: o interior fica abaixo de
+        // uma aresta de topo (horizontal) e à direita de uma aresta esquerda.
+        let top_left = a > 0 || (a == 0 && b > 0);
 
-    pub fn zeros() -> Self {
         Self {
-            screen_coord: Vec2::new(0., 0.),
-            //color:  Color::White,
-            normal: Vec3::zeros(),
-            light: Vec3::zeros(),
-            //eye:    Vec3::zeros(),
-            halfway: Vec3::zeros(),
-            depth: 0.0,
-            texture_coord: Vec3::zeros(),
+            a,
+            b,
+            c,
+            bias: if top_left { 0 } else { -1 },
         }
     }
+
+    fn eval(&self, x: i64, y: i64) -> i64 {
+        self.a * x + self.b * y + self.c
+    }
 }
 
-impl Add for VertexAttributes {
-    type Output = Self;
-    fn add(self, rhs: Self) -> Self {
-        Self::new(
-            self.screen_coord + rhs.screen_coord,
-            // TODO: tqv isso aq dps
-            //self.color,
-            self.depth + rhs.depth,
-            self.normal + rhs.normal,
-            self.light + rhs.light,
-            //self.eye            + rhs.eye,
-            self.halfway + rhs.halfway,
-            self.texture_coord + rhs.texture_coord,
+// Triangle snapped to the subpixel grid. The edges are opposite to the
+// vertices a, b and c, so their values divided by the area are the
+// barycentric coordinates (alpha, beta, gama).
+struct FixedTriangle {
+    vertices: [(i64, i64); 3],
+    edges: [Edge; 3],
+    // Twice the area of the triangle.
+    area: i64,
+}
+
+impl FixedTriangle {
+    // Returns None for degenerate triangles, which don't cover any sample.
+    fn new(a: Vec2, b: Vec2, c: Vec2) -> Option<Self> {
+        let fixed = |v: Vec2| (to_fixed(v.x), to_fixed(v.y));
+        let (a, b, c) = (fixed(a), fixed(b), fixed(c));
+
+        let area = Edge::new(b, c, 1).eval(a.0, a.1);
+        if area == 0 {
+            return None;
+        }
+
+        let orientation = area.signum();
+
+        Some(Self {
+            vertices: [a, b, c],
+            edges: [
+                Edge::new(b, c, orientation),
+                Edge::new(c, a, orientation),
+                Edge::new(a, b, orientation),
+            ],
+            area: area.abs(),
+        })
+    }
+
+    // Range of the pixels (x_min, y_min, x_max, y_max) whose center may be
+    // covered, with the box of the triangle grown by `margin` (in 28.4).
+    fn pixel_bounds(&self, margin: i64) -> (i64, i64, i64, i64) {
+        let xs = self.vertices.map(|v| v.0);
+        let ys = self.vertices.map(|v| v.1);
+
+        let ceil = |v: i64| -(-v).div_euclid(SUBPIXEL_ONE);
+        let floor = |v: i64| v.div_euclid(SUBPIXEL_ONE);
+
+        (
+            ceil(xs.into_iter().min().unwrap() - margin),
+            ceil(ys.into_iter().min().unwrap() - margin),
+            floor(xs.into_iter().max().unwrap() + margin),
+            floor(ys.into_iter().max().unwrap() + margin),
         )
     }
-}
 
-impl Sub for VertexAttributes {
-    type Output = Self;
-    fn sub(self, rhs: Self) -> Self {
-        Self::new(
-            self.screen_coord - rhs.screen_coord,
-            // TODO: tqv isso aq dps
-            //self.color,
-            self.depth - rhs.depth,
-            self.normal - rhs.normal,
-            self.light - rhs.light,
-            //self.eye            - rhs.eye,
-            self.halfway - rhs.halfway,
-            self.texture_coord - rhs.texture_coord,
+    // Values of the edge functions at the center of the pixel (x, y).
+    fn edge_values(&self, x: usize, y: usize) -> [i64; 3] {
+        let x = (x as i64) << SUBPIXEL_BITS;
+        let y = (y as i64) << SUBPIXEL_BITS;
+
+        self.edges.map(|edge| edge.eval(x, y))
+    }
+
+    // Incremento dos valores das arestas a cada pixel em x.
+    fn step_x(&self) -> [i64; 3] {
+        self.edges.map(|edge| edge.a * SUBPIXEL_ONE)
+    }
+
+    // Incremento dos valores das arestas a cada pixel em y.
+    fn step_y(&self) -> [i64; 3] {
+        self.edges.map(|edge| edge.b * SUBPIXEL_ONE)
+    }
+
+    // Values of the edge functions moved by (dx, dy), given in 28.4.
+    fn offset(&self, values: [i64; 3], (dx, dy): (i64, i64)) -> [i64; 3] {
+        std::array::from_fn(|i| values[i] + self.edges[i].a * dx + self.edges[i].b * dy)
+    }
+
+    fn covers(&self, values: [i64; 3]) -> bool {
+        values
+            .iter()
+            .zip(self.edges.iter())
+            .all(|(value, edge)| value + edge.bias >= 0)
+    }
+
+    fn barycentric(&self, values: [i64; 3]) -> (f32, f32, f32) {
+        let area = self.area as f32;
+
+        (
+            values[0] as f32 / area,
+            values[1] as f32 / area,
+            values[2] as f32 / area,
         )
     }
 }
 
-impl Mul<f32> for VertexAttributes {
-    type Output = Self;
-    fn mul(self, rhs: f32) -> Self {
-        Self::new(
-            self.screen_coord * rhs,
-            // TODO: tqv isso aq dps
-            //self.color,
-            self.depth * rhs,
-            self.normal * rhs,
-            self.light * rhs,
-            //self.eye            * rhs,
-            self.halfway * rhs,
-            self.texture_coord * rhs,
-        )
+fn add_step(values: [i64; 3], step: [i64; 3]) -> [i64; 3] {
+    std::array::from_fn(|i| values[i] + step[i])
+}
+
+// Pixels de uma linha do triângulo ainda não rasterizados: de `x` até
+// `x_max`, com os valores das arestas no pixel `x`.
+struct RowSpan {
+    y: usize,
+    x: usize,
+    x_max: usize,
+    values: [i64; 3],
+}
+
+// Largura dos blocos de pixels do rasterizador vetorizado. Só a cobertura,
+// as coordenadas baricêntricas e o teste de profundidade são vetorizados: as
+// varyings são de um tipo genérico do shader, então a interpolação delas e o
+// shading continuam pixel a pixel, apenas nos pixels visíveis do bloco.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    Scalar,
+    // Blocos de 4 pixels.
+    Sse2,
+    // Blocos de 8 pixels.
+    Avx2,
+}
+
+impl SimdLevel {
+    // Pixels processed at once.
+    pub fn lanes(self) -> usize {
+        match self {
+            SimdLevel::Scalar => 1,
+            SimdLevel::Sse2 => 4,
+            SimdLevel::Avx2 => 8,
+        }
+    }
+
+    // Widest level supported by the CPU.
+    #[cfg(target_arch = "x86_64")]
+    pub fn detect() -> Self {
+        if is_x86_feature_detected!("avx2") {
+            SimdLevel::Avx2
+        } else {
+            SimdLevel::Sse2
+        }
     }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn detect() -> Self {
+        SimdLevel::Scalar
+    }
+}
+
+// Identifies the triangle of the scene a pixel was drawn from: the object,
+// the mesh (in the order of `Object::mesh_info_list`) and the triangle
+// within the mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrimitiveId {
+    pub object: u32,
+    pub mesh: usize,
+    pub triangle: usize,
+}
+
+// Triangle already projected to window coordinates, ready to be
+// binned into the rasterizer tiles.
+#[derive(Clone, Copy)]
+pub struct ProjectedTriangle<'a, V> {
+    pub vertices: [ProjectedVertex<V>; 3],
+    pub texture: &'a Texture,
+    // Written in the ID buffer, when enabled, wherever the triangle writes
+    // the depth.
+    pub id: Option<PrimitiveId>,
+    pub depth_update: bool,
+    pub depth_test: DepthTest,
+    // When false only the depth is written (depth prepass).
+    pub color_write: bool,
+    // Runs the fragment shader even without color write, so the fragments
+    // it discards (alpha-tested cutouts) don't write the depth either.
+    pub alpha_test: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -350,6 +546,174 @@ impl Rectangle {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Msaa {
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl Msaa {
+    pub fn sample_count(&self) -> usize {
+        self.sample_offsets().len()
+    }
+
+    // Sample positions relative to the pixel center (standard D3D patterns).
+    fn sample_offsets(&self) -> &'static [(f32, f32)] {
+        match self {
+            Msaa::Off => &[(0.0, 0.0)],
+            Msaa::X2 => &[(0.25, 0.25), (-0.25, -0.25)],
+            Msaa::X4 => &[
+                (-0.125, -0.375),
+                (0.375, -0.125),
+                (-0.375, 0.125),
+                (0.125, 0.375),
+            ],
+            Msaa::X8 => &[
+                (0.0625, -0.1875),
+                (-0.0625, 0.1875),
+                (0.3125, 0.0625),
+                (-0.1875, -0.3125),
+                (-0.3125, 0.3125),
+                (-0.4375, -0.0625),
+                (0.1875, 0.4375),
+                (0.4375, -0.4375),
+            ],
+        }
+    }
+}
+
+// Fragmento de um triângulo transparente armazenado no A-buffer.
+#[derive(Clone, Copy, Debug)]
+struct OitFragment {
+    depth: f32,
+    color: Vec3,
+    opacity: f32,
+}
+
+// Operador usado para converter as cores do buffer HDR para o intervalo [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    AcesFilmic,
+}
+
+impl ToneMapping {
+    fn apply(&self, color: Vec3) -> Vec3 {
+        let map = |c: f32| -> f32 {
+            let c = c.max(0.0);
+
+            match self {
+                ToneMapping::Clamp => c.min(1.0),
+                ToneMapping::Reinhard => c / (1.0 + c),
+                // Aproximação da curva ACES de Krzysztof Narkowicz.
+                ToneMapping::AcesFilmic => {
+                    ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).min(1.0)
+                }
+            }
+        };
+
+        Vec3::new([map(color.x()), map(color.y()), map(color.z())])
+    }
+}
+
+// Buffers written by the rasterizer. With the tiled raster they are shared
+// between the threads, each one writing only the pixels (and their samples)
+// of the tile it is processing. The elements are accessed through pointers
+// taken once from the canvas, so no reference to a whole buffer, or to the
+// canvas, is ever mutable in more than one thread.
+//
+// The pointers are valid while the canvas isn't resized nor its settings
+// changed, so a target must not outlive the draw call that created it.
+//
+// SAFETY (every accessor): `index` must be in bounds of the buffer, and no
+// other thread may access the same element at the same time.
+struct RasterTarget {
+    frame: *mut Pixel,
+    depth: *mut f32,
+    samples: *mut Pixel,
+    sample_depth: *mut f32,
+    hdr: *mut Vec3,
+    oit: *mut Vec<OitFragment>,
+    // O ID buffer é opcional e vazio quando desabilitado.
+    ids: Option<*mut Option<PrimitiveId>>,
+}
+
+unsafe impl Send for RasterTarget {}
+unsafe impl Sync for RasterTarget {}
+
+impl RasterTarget {
+    fn new(canvas: &mut Canvas) -> Self {
+        Self {
+            frame: canvas.frame.as_mut_ptr(),
+            depth: canvas.depth_frame.as_mut_ptr(),
+            samples: canvas.sample_frame.as_mut_ptr(),
+            sample_depth: canvas.sample_depth.as_mut_ptr(),
+            hdr: canvas.hdr_frame.as_mut_ptr(),
+            oit: canvas.oit_fragments.as_mut_ptr(),
+            ids: (!canvas.id_frame.is_empty()).then_some(canvas.id_frame.as_mut_ptr()),
+        }
+    }
+
+    unsafe fn pixel(&self, index: usize) -> Pixel {
+        *self.frame.add(index)
+    }
+
+    unsafe fn set_pixel(&self, index: usize, color: Pixel) {
+        *self.frame.add(index) = color;
+    }
+
+    unsafe fn depth(&self, index: usize) -> f32 {
+        *self.depth.add(index)
+    }
+
+    unsafe fn set_depth(&self, index: usize, depth: f32) {
+        *self.depth.add(index) = depth;
+    }
+
+    // `lanes` consecutive depths starting at `index`.
+    unsafe fn depth_span(&self, index: usize, lanes: usize) -> &[f32] {
+        std::slice::from_raw_parts(self.depth.add(index), lanes)
+    }
+
+    unsafe fn sample(&self, index: usize) -> Pixel {
+        *self.samples.add(index)
+    }
+
+    unsafe fn set_sample(&self, index: usize, color: Pixel) {
+        *self.samples.add(index) = color;
+    }
+
+    unsafe fn sample_depth(&self, index: usize) -> f32 {
+        *self.sample_depth.add(index)
+    }
+
+    unsafe fn set_sample_depth(&self, index: usize, depth: f32) {
+        *self.sample_depth.add(index) = depth;
+    }
+
+    unsafe fn hdr(&self, index: usize) -> Vec3 {
+        *self.hdr.add(index)
+    }
+
+    unsafe fn set_hdr(&self, index: usize, color: Vec3) {
+        *self.hdr.add(index) = color;
+    }
+
+    unsafe fn push_oit(&self, index: usize, fragment: OitFragment) {
+        (*self.oit.add(index)).push(fragment);
+    }
+
+    // Does nothing when the ID buffer is disabled.
+    unsafe fn set_id(&self, index: usize, id: Option<PrimitiveId>) {
+        if let Some(ids) = self.ids {
+            *ids.add(index) = id;
+        }
+    }
+}
+
 pub struct Canvas {
     frame: Vec<Pixel>,
     width: usize,
@@ -360,13 +724,52 @@ pub struct Canvas {
     depth_frame: Vec<f32>,
 
     depth_max: f32,
+
+    // With the ID buffer enabled, `id_frame` stores, like the depth buffer,
+    // the triangle closest to the camera in each pixel.
+    id_buffer_enabled: bool,
+    id_frame: Vec<Option<PrimitiveId>>,
+
+    tiled_raster_enabled: bool,
+    raster_threads: usize,
+
+    perspective_correction: bool,
+
+    // The non-multisampled raster processes blocks of pixels with SIMD
+    // instructions, falling back to the scalar path at the end of the rows.
+    simd_level: SimdLevel,
+
+    // With MSAA enabled, the 3D geometry is rasterized in the sample buffers,
+    // which are resolved into `frame` by `resolve`.
+    msaa: Msaa,
+    sample_frame: Vec<Pixel>,
+    sample_depth: Vec<f32>,
+
+    // With HDR enabled, the colors are accumulated without clamping in
+    // `hdr_frame` (one entry per sample) and tone mapped by `resolve`.
+    hdr_enabled: bool,
+    hdr_frame: Vec<Vec3>,
+    tone_mapping: ToneMapping,
+    exposure: f32,
+
+    // With OIT enabled, the fragments of the transparent triangles are
+    // stored in per-pixel lists (A-buffer) and blended in depth order by
+    // `resolve`, regardless of the order the triangles were drawn.
+    oit_enabled: bool,
+    oit_fragments: Vec<Vec<OitFragment>>,
 }
 
 impl Canvas {
+    pub const TILE_SIZE: usize = 64;
+
     pub fn new(width: usize, height: usize) -> Self {
         let len: usize = width * height;
         let frame = vec![Pixel::black(); len];
 
+        let raster_threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
         Self {
             frame,
             width,
@@ -376,6 +779,217 @@ impl Canvas {
             depth_frame: vec![],
             depth_max: 0.0,
             depth_update_enabled: false,
+
+            id_buffer_enabled: false,
+            id_frame: vec![],
+
+            tiled_raster_enabled: true,
+            raster_threads,
+
+            perspective_correction: true,
+
+            simd_level: SimdLevel::detect(),
+
+            msaa: Msaa::Off,
+            sample_frame: vec![],
+            sample_depth: vec![],
+
+            hdr_enabled: false,
+            hdr_frame: vec![],
+            tone_mapping: ToneMapping::AcesFilmic,
+            exposure: 1.0,
+
+            oit_enabled: false,
+            oit_fragments: vec![],
+        }
+    }
+
+    pub fn set_tiled_raster(&mut self, enabled: bool) {
+        self.tiled_raster_enabled = enabled;
+    }
+
+    pub fn tiled_raster_enabled(&self) -> bool {
+        self.tiled_raster_enabled
+    }
+
+    pub fn set_raster_threads(&mut self, threads: usize) {
+        self.raster_threads = threads.max(1);
+    }
+
+    // When disabled, the vertex attributes are interpolated with the
+    // screen-space (affine) barycentric coordinates.
+    pub fn set_perspective_correction(&mut self, enabled: bool) {
+        self.perspective_correction = enabled;
+    }
+
+    pub fn perspective_correction(&self) -> bool {
+        self.perspective_correction
+    }
+
+    // Levels not supported by the CPU are replaced by the widest supported one.
+    pub fn set_simd_level(&mut self, level: SimdLevel) {
+        self.simd_level = level.min(SimdLevel::detect());
+    }
+
+    pub fn simd_level(&self) -> SimdLevel {
+        self.simd_level
+    }
+
+    pub fn set_msaa(&mut self, msaa: Msaa) {
+        if self.msaa != msaa {
+            self.msaa = msaa;
+            self.init_samples();
+        }
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        self.msaa
+    }
+
+    pub fn set_hdr(&mut self, enabled: bool) {
+        if self.hdr_enabled != enabled {
+            self.hdr_enabled = enabled;
+            self.init_samples();
+        }
+    }
+
+    pub fn hdr_enabled(&self) -> bool {
+        self.hdr_enabled
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure.max(0.0);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    pub fn set_oit(&mut self, enabled: bool) {
+        if self.oit_enabled != enabled {
+            self.oit_enabled = enabled;
+            self.init_samples();
+        }
+    }
+
+    pub fn oit_enabled(&self) -> bool {
+        self.oit_enabled
+    }
+
+    pub fn set_id_buffer(&mut self, enabled: bool) {
+        if self.id_buffer_enabled != enabled {
+            self.id_buffer_enabled = enabled;
+            self.init_samples();
+        }
+    }
+
+    pub fn id_buffer_enabled(&self) -> bool {
+        self.id_buffer_enabled
+    }
+
+    fn init_samples(&mut self) {
+        let len = self.frame.len() * self.msaa.sample_count();
+
+        if self.msaa == Msaa::Off {
+            self.sample_frame = vec![];
+            self.sample_depth = vec![];
+        } else {
+            self.sample_frame = vec![Pixel::azul_bb(); len];
+            self.sample_depth = vec![self.depth_max; len];
+        }
+
+        if self.hdr_enabled {
+            self.hdr_frame = vec![Pixel::azul_bb().as_linear_vec3(); len];
+        } else {
+            self.hdr_frame = vec![];
+        }
+
+        if self.oit_enabled {
+            self.oit_fragments = vec![vec![]; self.frame.len()];
+        } else {
+            self.oit_fragments = vec![];
+        }
+
+        if self.id_buffer_enabled {
+            self.id_frame = vec![None; self.frame.len()];
+        } else {
+            self.id_frame = vec![];
+        }
+    }
+
+    // Writes the samples (MSAA), the HDR colors and the transparent fragments
+    // (OIT) into `frame`. Must be called after the scene is rasterized and
+    // before the frame is read.
+    pub fn resolve(&mut self) {
+        let samples = self.msaa.sample_count();
+
+        if self.msaa != Msaa::Off && !self.hdr_enabled {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let first = (self.width * y + x) * samples;
+
+                    // As amostras são codificadas em sRGB; a média é feita
+                    // no espaço linear.
+                    let sum = self.sample_frame[first..first + samples]
+                        .iter()
+                        .fold(Vec3::zeros(), |sum, sample| sum + sample.as_linear_vec3());
+
+                    let color = Pixel::from_linear_vec3(sum / samples as f32);
+
+                    self.draw_pixel_coord(x, y, color);
+                }
+            }
+        }
+
+        if !self.hdr_enabled && !self.oit_enabled {
+            return;
+        }
+
+        for idx in 0..self.frame.len() {
+            // `frame` começa pela linha de cima; os demais buffers, pela de baixo.
+            let frame_idx = self.frame_index(idx % self.width, idx / self.width);
+            let fragments = self.oit_fragments.get_mut(idx);
+            let has_fragments = fragments.as_ref().is_some_and(|f| !f.is_empty());
+
+            if !self.hdr_enabled && !has_fragments {
+                continue;
+            }
+
+            let mut color = if self.hdr_enabled {
+                let pixel_samples = &self.hdr_frame[idx * samples..(idx + 1) * samples];
+
+                let sum = pixel_samples
+                    .iter()
+                    .fold(Vec3::zeros(), |sum, sample| sum + *sample);
+
+                sum / samples as f32
+            } else {
+                self.frame[frame_idx].as_linear_vec3()
+            };
+
+            // Os fragmentos transparentes são ordenados do mais distante
+            // para o mais próximo e compostos sobre a cor opaca.
+            if let Some(fragments) = fragments {
+                fragments.sort_unstable_by(|a, b| b.depth.total_cmp(&a.depth));
+
+                for fragment in fragments.iter() {
+                    color = color * (1.0 - fragment.opacity) + fragment.color * fragment.opacity;
+                }
+            }
+
+            self.frame[frame_idx] = if self.hdr_enabled {
+                Pixel::from_linear_vec3(self.tone_mapping.apply(color * self.exposure))
+            } else {
+                Pixel::from_linear_vec3(color)
+            };
         }
     }
 
@@ -384,12 +998,18 @@ impl Canvas {
         self.offset.y = y as f32;
     }
 
+    // Position of the canvas in the screen of the projected triangles.
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+
     pub fn resize(&mut self, width: usize, height: usize) {
         self.width = width;
         self.height = height;
         self.frame.resize(width * height, Pixel::black());
 
         self.init_depth(self.depth_max);
+        self.init_samples();
     }
 
     pub fn disable_depth_update(&mut self) {
@@ -410,339 +1030,1002 @@ impl Canvas {
         }
     }
 
+    // Depth of the cleared buffer, where no fragment was drawn.
+    pub fn depth_max(&self) -> f32 {
+        self.depth_max
+    }
+
     pub fn get_pixel_depth(&self, x: usize, y: usize) -> f32 {
         debug_assert!(self.in_bounds(x, y));
         unsafe { *self.depth_frame.get_unchecked(self.width * y + x) }
     }
 
-    pub fn set_pixel_depth(&mut self, x: usize, y: usize, depth: f32) {
-        debug_assert!(self.in_bounds(x, y));
-        unsafe {
-            *self.depth_frame.get_unchecked_mut(self.width * y + x) = depth;
-        }
+    // Depth buffer, row by row from y = 0.
+    pub fn depth_frame(&self) -> &[f32] {
+        &self.depth_frame
+    }
+
+    pub fn set_pixel_depth(&mut self, x: usize, y: usize, depth: f32) {
+        debug_assert!(self.in_bounds(x, y));
+        unsafe {
+            *self.depth_frame.get_unchecked_mut(self.width * y + x) = depth;
+        }
+    }
+
+    // Depth of the pixel as shown in the frame. With MSAA the depth buffer
+    // isn't used, so it's the depth of the first sample of the pixel.
+    pub fn frame_depth(&self, x: usize, y: usize) -> f32 {
+        debug_assert!(self.in_bounds(x, y));
+        let samples = self.msaa.sample_count();
+
+        if self.msaa == Msaa::Off {
+            self.get_pixel_depth(x, y)
+        } else {
+            self.sample_depth[(self.width * y + x) * samples]
+        }
+    }
+
+    // Triangle drawn in the pixel (x, y). None when nothing was drawn there
+    // or the ID buffer is disabled.
+    pub fn get_pixel_id(&self, x: usize, y: usize) -> Option<PrimitiveId> {
+        debug_assert!(self.in_bounds(x, y));
+        self.id_frame.get(self.width * y + x).copied().flatten()
+    }
+
+    // ID buffer, row by row from y = 0. Empty when disabled.
+    pub fn id_frame(&self) -> &[Option<PrimitiveId>] {
+        &self.id_frame
+    }
+
+    // Color of the pixel (x, y) of the frame.
+    pub fn pixel_color(&self, x: usize, y: usize) -> [u8; 3] {
+        let pixel = self.get_pixel_coord(x, y);
+        [pixel.r, pixel.g, pixel.b]
+    }
+
+    // Linear color used by `clear`.
+    pub fn clear_color() -> Vec3 {
+        Pixel::azul_bb().as_linear_vec3()
+    }
+
+    pub fn clear(&mut self) {
+        self.clear_to(Pixel::azul_bb(), Pixel::azul_bb().as_linear_vec3());
+    }
+
+    // Clears the frame like `clear`, filling it with the linear color `color`.
+    pub fn clear_with_color(&mut self, color: Vec3) {
+        self.clear_to(Pixel::from_linear_vec3(color), color);
+    }
+
+    fn clear_to(&mut self, pixel: Pixel, hdr_color: Vec3) {
+        self.frame.iter_mut().for_each(|p| *p = pixel);
+
+        if self.depth_frame.len() > 0 {
+            self.init_depth(self.depth_max);
+        }
+
+        let depth_max = self.depth_max;
+        self.sample_frame
+            .iter_mut()
+            .for_each(|sample| *sample = pixel);
+        self.hdr_frame
+            .iter_mut()
+            .for_each(|color| *color = hdr_color);
+        self.oit_fragments
+            .iter_mut()
+            .for_each(|fragments| fragments.clear());
+        self.sample_depth
+            .iter_mut()
+            .for_each(|depth| *depth = depth_max);
+        self.id_frame.iter_mut().for_each(|id| *id = None);
+    }
+
+    // Clears the frame like `clear`, filling each pixel with the linear
+    // color `background(x, y)` instead of the fixed clear color, where
+    // (x, y) is the center of the pixel in the coordinates of the projected
+    // triangles (before the offset).
+    pub fn clear_with_background<F>(&mut self, background: F)
+    where
+        F: Fn(f32, f32) -> Vec3 + Sync,
+    {
+        self.clear();
+
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let width = self.width;
+        let offset = self.offset;
+        let mut colors = vec![Vec3::zeros(); self.frame.len()];
+
+        let fill_band = |band_idx: usize, band: &mut [Vec3]| {
+            for (i, color) in band.iter_mut().enumerate() {
+                let i = band_idx * Self::TILE_SIZE * width + i;
+                let x = (i % width) as f32 + offset.x;
+                let y = (i / width) as f32 + offset.y;
+
+                *color = background(x, y);
+            }
+        };
+
+        // As faixas têm a altura dos tiles do raster e são divididas entre
+        // o mesmo número de threads; um canvas de uma faixa só é preenchido
+        // nesta thread.
+        let band_len = Self::TILE_SIZE * width;
+        let workers = self
+            .raster_threads
+            .min(self.height.div_ceil(Self::TILE_SIZE));
+
+        if workers <= 1 {
+            for (band_idx, band) in colors.chunks_mut(band_len).enumerate() {
+                fill_band(band_idx, band);
+            }
+        } else {
+            let bands = Mutex::new(colors.chunks_mut(band_len).enumerate());
+
+            std::thread::scope(|scope| {
+                for _ in 0..workers {
+                    scope.spawn(|| loop {
+                        let Some((band_idx, band)) = bands.lock().unwrap().next() else {
+                            break;
+                        };
+
+                        fill_band(band_idx, band);
+                    });
+                }
+            });
+        }
+
+        let samples = self.msaa.sample_count();
+
+        // `colors` vai de y = 0, como as amostras, e o frame do topo.
+        for (i, color) in colors.into_iter().enumerate() {
+            let pixel = Pixel::from_linear_vec3(color);
+            let frame_idx = self.frame_index(i % width, i / width);
+            self.frame[frame_idx] = pixel;
+
+            if let Some(pixel_samples) = self.sample_frame.get_mut(i * samples..(i + 1) * samples) {
+                pixel_samples.fill(pixel);
+            }
+            if let Some(hdr_samples) = self.hdr_frame.get_mut(i * samples..(i + 1) * samples) {
+                hdr_samples.fill(color);
+            }
+        }
+    }
+
+    pub fn draw_triangle(
+        &mut self,
+        a_vertex: VertexSimpleAttributes,
+        b_vertex: VertexSimpleAttributes,
+        c_vertex: VertexSimpleAttributes,
+        texture: Option<&Texture>,
+        clipping_rect: Option<Rectangle>,
+    ) {
+        let default = &Texture::default();
+        let texture = texture.unwrap_or_else(move || {
+            assert!(false);
+            default
+        });
+        let diffuse_map = &texture.map_kd;
+
+        let Some(fixed) = FixedTriangle::new(
+            a_vertex.screen_coord,
+            b_vertex.screen_coord,
+            c_vertex.screen_coord,
+        ) else {
+            return;
+        };
+
+        let a_uv = a_vertex.texture_coord;
+        let b_uv = b_vertex.texture_coord;
+        let c_uv = c_vertex.texture_coord;
+
+        let color_a = a_vertex.color.as_pixel();
+        let color_b = b_vertex.color.as_pixel();
+        let color_c = c_vertex.color.as_pixel();
+
+        let Some(drawable_rect) = self.fixed_bounding_rect(&fixed, 0) else {
+            return;
+        };
+        let screen_rect = Rectangle::from_coords(0, 0, self.width - 1, self.height - 1);
+
+        let valid_rect =
+            Rectangle::clip(clipping_rect.unwrap_or_else(|| screen_rect), drawable_rect);
+
+        let x_min = valid_rect.x_min();
+        let y_min = valid_rect.y_min();
+
+        let x_max = valid_rect.x_max();
+        let y_max = valid_rect.y_max();
+
+        let step_x = fixed.step_x();
+        let step_y = fixed.step_y();
+        let mut row_values = fixed.edge_values(x_min, y_min);
+
+        for y in y_min..=y_max {
+            let mut values = row_values;
+            row_values = add_step(row_values, step_y);
+
+            for x in x_min..=x_max {
+                let pixel_values = values;
+                values = add_step(values, step_x);
+
+                if fixed.covers(pixel_values) {
+                    let (alpha, beta, gama) = fixed.barycentric(pixel_values);
+
+                    let mut color_pixel = (alpha * color_a) + (beta * color_b) + (gama * color_c);
+
+                    let color_alpha = (alpha * a_vertex.alpha)
+                        + (beta * b_vertex.alpha)
+                        + (gama * c_vertex.alpha);
+
+                    let color_uv = (a_uv * alpha) + (b_uv * beta) + (c_uv * gama);
+
+                    let color_rgba_slice = diffuse_map.get_rgba_slice(color_uv.x, color_uv.y);
+                    let texture_alpha = (color_rgba_slice[3] as f32) / 255.0;
+                    assert!((0.0..=1.0).contains(&texture_alpha));
+
+                    let color_texture =
+                        Color::Custom(color_rgba_slice[..3].try_into().unwrap()).as_pixel();
+
+                    // color blending
+                    color_pixel =
+                        color_pixel * texture_alpha + color_texture * (1.0 - texture_alpha);
+
+                    // Alpha resultante após combinação
+                    let final_alpha = color_alpha * texture_alpha;
+
+                    self.draw_pixel_coord_with_depth(x, y, color_pixel, final_alpha, 0.0);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_triangle_with_attributes<S: FragmentShader>(
+        &mut self,
+        a_attr: &ProjectedVertex<S::Varyings>,
+        b_attr: &ProjectedVertex<S::Varyings>,
+        c_attr: &ProjectedVertex<S::Varyings>,
+        texture: &Texture,
+        shader: &S,
+        uniforms: &Uniforms,
+        clipping_rect: Option<Rectangle>,
+    ) {
+        let tri = ProjectedTriangle {
+            vertices: [*a_attr, *b_attr, *c_attr],
+            texture,
+            id: None,
+            depth_update: self.depth_update_enabled,
+            depth_test: DepthTest::Less,
+            color_write: true,
+            alpha_test: false,
+        };
+
+        let target = RasterTarget::new(self);
+        self.rasterize_triangle(&target, &tri, shader, uniforms, clipping_rect);
+    }
+
+    // Draws the triangles in the given order. When the tiled raster is enabled,
+    // the screen is split in tiles of TILE_SIZE x TILE_SIZE pixels and each
+    // triangle is put in the bins of the tiles its bounding box overlaps.
+    // The tiles are then rasterized in parallel, but the triangles of each
+    // bin keep the submission order, so the depth test and the blending of
+    // the transparent triangles give the same result as the sequential path.
+    pub fn draw_projected_triangles<S: FragmentShader>(
+        &mut self,
+        triangles: &[ProjectedTriangle<S::Varyings>],
+        shader: &S,
+        uniforms: &Uniforms,
+    ) {
+        debug_assert!(!self.depth_frame.is_empty(), "Depth not initialized");
+        let target = RasterTarget::new(self);
+
+        if !self.tiled_raster_enabled || self.raster_threads <= 1 {
+            for tri in triangles.iter() {
+                self.rasterize_triangle(&target, tri, shader, uniforms, None);
+            }
+            return;
+        }
+
+        let tiles_x = self.width.div_ceil(Self::TILE_SIZE);
+        let tiles_y = self.height.div_ceil(Self::TILE_SIZE);
+
+        let mut bins: Vec<Vec<usize>> = vec![Vec::new(); tiles_x * tiles_y];
+
+        for (tri_idx, tri) in triangles.iter().enumerate() {
+            let Some(rect) = self
+                .fixed_triangle(tri)
+                .and_then(|fixed| self.triangle_bounding_rect(&fixed))
+            else {
+                continue;
+            };
+
+            let tile_x_min = rect.x_min() / Self::TILE_SIZE;
+            let tile_y_min = rect.y_min() / Self::TILE_SIZE;
+            let tile_x_max = rect.x_max() / Self::TILE_SIZE;
+            let tile_y_max = rect.y_max() / Self::TILE_SIZE;
+
+            for tile_y in tile_y_min..=tile_y_max {
+                for tile_x in tile_x_min..=tile_x_max {
+                    bins[tile_y * tiles_x + tile_x].push(tri_idx);
+                }
+            }
+        }
+
+        let (width, height) = (self.width, self.height);
+        let tile_rect = |tile_idx: usize| -> Rectangle {
+            let x_min = (tile_idx % tiles_x) * Self::TILE_SIZE;
+            let y_min = (tile_idx / tiles_x) * Self::TILE_SIZE;
+
+            let x_max = (x_min + Self::TILE_SIZE - 1).min(width - 1);
+            let y_max = (y_min + Self::TILE_SIZE - 1).min(height - 1);
+
+            Rectangle::from_coords(x_min, y_min, x_max, y_max)
+        };
+
+        let workers = self.raster_threads.min(bins.len());
+        let next_tile = AtomicUsize::new(0);
+        // Os buffers só são escritos pelo `target`.
+        let canvas: &Canvas = self;
+        let target = &target;
+
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let tile_idx = next_tile.fetch_add(1, Ordering::Relaxed);
+                    if tile_idx >= bins.len() {
+                        break;
+                    }
+
+                    // The tiles don't overlap and every access done by
+                    // `rasterize_triangle` is restricted to the clipping rect,
+                    // so no pixel is accessed by more than one thread.
+                    for tri_idx in bins[tile_idx].iter() {
+                        canvas.rasterize_triangle(
+                            target,
+                            &triangles[*tri_idx],
+                            shader,
+                            uniforms,
+                            Some(tile_rect(tile_idx)),
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    // Triangle snapped to the subpixel grid, in window coordinates.
+    fn fixed_triangle<V>(&self, tri: &ProjectedTriangle<V>) -> Option<FixedTriangle> {
+        let [a_attr, b_attr, c_attr] = &tri.vertices;
+
+        FixedTriangle::new(
+            a_attr.screen_coord - self.offset,
+            b_attr.screen_coord - self.offset,
+            c_attr.screen_coord - self.offset,
+        )
+    }
+
+    // Bounding box of the pixels covered by the triangle, clipped to the
+    // screen. Returns None when the triangle is outside of the screen.
+    fn triangle_bounding_rect(&self, fixed: &FixedTriangle) -> Option<Rectangle> {
+        // The samples of the multisampled raster are up to half a pixel away
+        // from the pixel center.
+        let margin = if self.msaa == Msaa::Off {
+            0
+        } else {
+            SUBPIXEL_ONE / 2
+        };
+
+        self.fixed_bounding_rect(fixed, margin)
+    }
+
+    fn fixed_bounding_rect(&self, fixed: &FixedTriangle, margin: i64) -> Option<Rectangle> {
+        let (x_min, y_min, x_max, y_max) = fixed.pixel_bounds(margin);
+
+        let x_min = x_min.max(0);
+        let y_min = y_min.max(0);
+
+        let x_max = x_max.min(self.width as i64 - 1);
+        let y_max = y_max.min(self.height as i64 - 1);
+
+        if x_min > x_max || y_min > y_max {
+            return None;
+        }
+
+        Some(Rectangle::from_coords(
+            x_min as usize,
+            y_min as usize,
+            x_max as usize,
+            y_max as usize,
+        ))
+    }
+
+    // Interpolação com correção de perspectiva: os atributos variam
+    // linearmente no espaço de tela apenas quando divididos por w, então
+    // interpolamos attr/w e 1/w e dividimos um pelo outro.
+    fn perspective_barycentric<V>(
+        &self,
+        tri: &ProjectedTriangle<V>,
+        alpha: f32,
+        beta: f32,
+        gama: f32,
+    ) -> (f32, f32, f32) {
+        if self.perspective_correction {
+            let alpha_w = alpha * tri.vertices[0].inv_w;
+            let beta_w = beta * tri.vertices[1].inv_w;
+            let gama_w = gama * tri.vertices[2].inv_w;
+
+            let inv_w = alpha_w + beta_w + gama_w;
+
+            (alpha_w / inv_w, beta_w / inv_w, gama_w / inv_w)
+        } else {
+            (alpha, beta, gama)
+        }
+    }
+
+    // Varyings of the fragment with the affine barycentric
+    // coordinates (alpha, beta, gama).
+    fn interpolate_varyings<V: Varyings>(
+        &self,
+        tri: &ProjectedTriangle<V>,
+        (alpha, beta, gama): (f32, f32, f32),
+    ) -> V {
+        let (alpha, beta, gama) = self.perspective_barycentric(tri, alpha, beta, gama);
+        let [a_attr, b_attr, c_attr] = &tri.vertices;
+
+        a_attr.varyings * alpha + b_attr.varyings * beta + c_attr.varyings * gama
     }
 
-    pub fn clear(&mut self) {
-        self.frame
-            .iter_mut()
-            .for_each(|pixel| *pixel = Pixel::azul_bb());
+    // Shades the fragment with the affine barycentric coordinates `affine`.
+    fn shade_fragment<S: FragmentShader>(
+        &self,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
+        affine: (f32, f32, f32),
+        depth: f32,
+        step: &BarycentricStep,
+    ) -> Option<FragmentOutput> {
+        let (alpha, beta, gama) = affine;
+
+        let neighbor = |dx: f32, dy: f32| {
+            let neighbor_affine = (
+                alpha + step.dx[0] * dx + step.dy[0] * dy,
+                beta + step.dx[1] * dx + step.dy[1] * dy,
+                gama + step.dx[2] * dx + step.dy[2] * dy,
+            );
+
+            self.interpolate_varyings(tri, neighbor_affine)
+        };
 
-        if self.depth_frame.len() > 0 {
-            self.init_depth(self.depth_max);
-        }
+        let input = FragmentInput::new(
+            self.interpolate_varyings(tri, affine),
+            depth,
+            tri.texture,
+            uniforms,
+            &neighbor,
+        );
+
+        shader.shade(&input)
     }
 
-    pub fn draw_triangle(
-        &mut self,
-        a_vertex: VertexSimpleAttributes,
-        b_vertex: VertexSimpleAttributes,
-        c_vertex: VertexSimpleAttributes,
-        texture: Option<&Texture>,
+    // Every buffer access is restricted to the pixels of `clipping_rect`
+    // (the whole screen when None), which the caller must not access at the
+    // same time from another thread.
+    fn rasterize_triangle<S: FragmentShader>(
+        &self,
+        target: &RasterTarget,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
         clipping_rect: Option<Rectangle>,
     ) {
-        let default = &Texture::default();
-        let texture = texture.unwrap_or_else(move || {
-            assert!(false);
-            default
-        });
-        let diffuse_map = &texture.map_kd;
+        if self.msaa != Msaa::Off {
+            self.rasterize_triangle_msaa(target, tri, shader, uniforms, clipping_rect);
+            return;
+        }
 
-        let a_center = self.pos_map_center(a_vertex.screen_coord);
-        let b_center = self.pos_map_center(b_vertex.screen_coord);
-        let c_center = self.pos_map_center(c_vertex.screen_coord);
+        let Some(fixed) = self.fixed_triangle(tri) else {
+            // Triângulo degenerado
+            return;
+        };
 
-        let a_uv = a_vertex.texture_coord;
-        let b_uv = b_vertex.texture_coord;
-        let c_uv = c_vertex.texture_coord;
+        let Some(drawable_rect) = self.triangle_bounding_rect(&fixed) else {
+            return;
+        };
+        let screen_rect = Rectangle::from_coords(0, 0, self.width - 1, self.height - 1);
 
-        let color_a = a_vertex.color.as_pixel();
-        let color_b = b_vertex.color.as_pixel();
-        let color_c = c_vertex.color.as_pixel();
+        let valid_rect =
+            Rectangle::clip(clipping_rect.unwrap_or_else(|| screen_rect), drawable_rect);
 
-        let f_ab = |x: f32, y: f32| -> f32 {
-            (a_center.y - b_center.y) * x
-                + (b_center.x - a_center.x) * y
-                + (a_center.x * b_center.y)
-                - (b_center.x * a_center.y)
-        };
+        let x_min = valid_rect.x_min();
+        let y_min = valid_rect.y_min();
 
-        let f_bc = |x: f32, y: f32| -> f32 {
-            (b_center.y - c_center.y) * x
-                + (c_center.x - b_center.x) * y
-                + (b_center.x * c_center.y)
-                - (c_center.x * b_center.y)
-        };
+        let x_max = valid_rect.x_max();
+        let y_max = valid_rect.y_max();
 
-        let f_ca = |x: f32, y: f32| -> f32 {
-            (c_center.y - a_center.y) * x
-                + (a_center.x - c_center.x) * y
-                + (c_center.x * a_center.y)
-                - (a_center.x * c_center.y)
-        };
+        let [a_attr, b_attr, c_attr] = &tri.vertices;
 
-        let min = |x: f32, y: f32, z: f32| -> f32 {
-            let mut ret = f32::INFINITY;
-            [x, y, z].iter().for_each(|v| {
-                if *v < ret {
-                    ret = *v;
-                }
-            });
+        let a_depth = a_attr.depth;
+        let b_depth = b_attr.depth;
+        let c_depth = c_attr.depth;
+
+        let step = BarycentricStep::new(&fixed);
+
+        // The edge functions are evaluated once per triangle and then
+        // incremented pixel by pixel.
+        let step_x = fixed.step_x();
+        let step_y = fixed.step_y();
+        let mut row_values = fixed.edge_values(x_min, y_min);
+
+        let rasterize_pixel = |x: usize, y: usize, pixel_values: [i64; 3]| {
+            if !fixed.covers(pixel_values) {
+                return;
+            }
+
+            let affine = fixed.barycentric(pixel_values);
+            let (alpha, beta, gama) = affine;
+            let (alpha, beta, gama) = self.perspective_barycentric(tri, alpha, beta, gama);
+
+            let pixel_depth = (alpha * a_depth) + (beta * b_depth) + (gama * c_depth);
 
-            ret
+            // Early depth test: the texture fetches and the shading
+            // are done only for the fragments that will be visible.
+            // SAFETY: the pixel is inside the clipping rect.
+            let current_depth = unsafe { target.depth(self.width * y + x) };
+            if !tri.depth_test.passes(pixel_depth, current_depth) {
+                return;
+            }
+
+            self.write_fragment(
+                target,
+                tri,
+                shader,
+                uniforms,
+                (x, y),
+                affine,
+                pixel_depth,
+                &step,
+            );
         };
 
-        let max = |x: f32, y: f32, z: f32| -> f32 {
-            let mut ret = -f32::INFINITY;
-            [x, y, z].iter().for_each(|v| {
-                if *v > ret {
-                    ret = *v;
+        // Os blocos completos da linha, alinhados a múltiplos da largura do
+        // SIMD, são rasterizados pelo caminho vetorizado e os pixels restantes
+        // pelo escalar. Com o alinhamento, cada pixel passa pelo mesmo caminho
+        // qualquer que seja o retângulo rasterizado, então o raster em tiles
+        // tem exatamente o mesmo resultado que o sequencial.
+        let simd_start = x_min
+            .next_multiple_of(self.simd_level.lanes())
+            .min(x_max + 1);
+
+        for y in y_min..=y_max {
+            let mut values = row_values;
+            row_values = add_step(row_values, step_y);
+
+            for x in x_min..simd_start {
+                rasterize_pixel(x, y, values);
+                values = add_step(values, step_x);
+            }
+
+            let mut span = RowSpan {
+                y,
+                x: simd_start,
+                x_max,
+                values,
+            };
+            self.rasterize_span_simd(target, tri, shader, uniforms, &fixed, &step, &mut span);
+
+            let mut values = span.values;
+            for x in span.x..=x_max {
+                rasterize_pixel(x, y, values);
+                values = add_step(values, step_x);
+            }
+        }
+    }
+
+    // Shades a fragment that passed the depth test and writes its color and depth.
+    #[allow(clippy::too_many_arguments)]
+    fn write_fragment<S: FragmentShader>(
+        &self,
+        target: &RasterTarget,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
+        (x, y): (usize, usize),
+        affine: (f32, f32, f32),
+        depth: f32,
+        step: &BarycentricStep,
+    ) {
+        if tri.color_write {
+            let Some(FragmentOutput { color, opacity }) =
+                self.shade_fragment(tri, shader, uniforms, affine, depth, step)
+            else {
+                // Fragmento descartado pelo shader.
+                return;
+            };
+
+            // SAFETY: the callers only write the pixels of their clipping rect.
+            unsafe {
+                if self.oit_enabled && opacity < 1.0 {
+                    target.push_oit(
+                        self.width * y + x,
+                        OitFragment {
+                            depth,
+                            color,
+                            opacity,
+                        },
+                    );
+                } else if self.hdr_enabled {
+                    Self::blend_hdr(target, self.width * y + x, color, opacity);
+                } else {
+                    let index = self.frame_index(x, y);
+                    let color = Pixel::from_linear_vec3(color);
+                    let bg_color = target.pixel(index);
+
+                    target.set_pixel(index, Self::blend(bg_color, color, opacity));
                 }
-            });
+            }
+        } else if tri.alpha_test
+            && self
+                .shade_fragment(tri, shader, uniforms, affine, depth, step)
+                .is_none()
+        {
+            return;
+        }
 
-            ret
-        };
+        if tri.depth_update {
+            // SAFETY: as above.
+            unsafe {
+                target.set_depth(self.width * y + x, depth);
+                target.set_id(self.width * y + x, tri.id);
+            }
+        }
+    }
 
-        let mut x_min = min(a_center.x, b_center.x, c_center.x) as usize;
-        let mut y_min = min(a_center.y, b_center.y, c_center.y) as usize;
+    // Rasteriza os blocos completos de pixels do span com a maior largura
+    // de SIMD habilitada, avançando o span até o primeiro pixel não processado.
+    #[cfg(target_arch = "x86_64")]
+    #[allow(clippy::too_many_arguments)]
+    fn rasterize_span_simd<S: FragmentShader>(
+        &self,
+        target: &RasterTarget,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
+        fixed: &FixedTriangle,
+        step: &BarycentricStep,
+        span: &mut RowSpan,
+    ) {
+        match self.simd_level {
+            // SAFETY: the AVX2 level is only selected when the CPU supports it.
+            SimdLevel::Avx2 => unsafe {
+                self.rasterize_span_avx2(target, tri, shader, uniforms, fixed, step, span)
+            },
+            // SAFETY: SSE2 is part of the x86_64 baseline.
+            SimdLevel::Sse2 => unsafe {
+                self.rasterize_span_blocks::<F32x4, S>(
+                    target, tri, shader, uniforms, fixed, step, span,
+                )
+            },
+            SimdLevel::Scalar => {}
+        }
+    }
 
-        let mut x_max = max(a_center.x, b_center.x, c_center.x) as usize;
-        let mut y_max = max(a_center.y, b_center.y, c_center.y) as usize;
+    #[cfg(not(target_arch = "x86_64"))]
+    #[allow(clippy::too_many_arguments)]
+    fn rasterize_span_simd<S: FragmentShader>(
+        &self,
+        _target: &RasterTarget,
+        _tri: &ProjectedTriangle<S::Varyings>,
+        _shader: &S,
+        _uniforms: &Uniforms,
+        _fixed: &FixedTriangle,
+        _step: &BarycentricStep,
+        _span: &mut RowSpan,
+    ) {
+    }
 
-        let mut drawable_rect = Rectangle::from_coords(x_min, y_min, x_max, y_max);
-        let screen_rect = Rectangle::from_coords(0, 0, self.width - 1, self.height - 1);
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn rasterize_span_avx2<S: FragmentShader>(
+        &self,
+        target: &RasterTarget,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
+        fixed: &FixedTriangle,
+        step: &BarycentricStep,
+        span: &mut RowSpan,
+    ) {
+        self.rasterize_span_blocks::<F32x8, S>(target, tri, shader, uniforms, fixed, step, span)
+    }
 
-        drawable_rect = Rectangle::clip(drawable_rect, screen_rect.clone());
+    // Vectorized raster of a span: the coverage, the barycentric coordinates
+    // and the depth test are evaluated for blocks of `P::LANES` pixels. The
+    // visible pixels of each block then go one by one through
+    // `write_fragment`, which interpolates the varyings and shades them.
+    //
+    // SAFETY: the CPU must support the target features of `P`. The function
+    // is inlined so its caller's target features apply to the intrinsics.
+    #[cfg(target_arch = "x86_64")]
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn rasterize_span_blocks<P: PackedF32, S: FragmentShader>(
+        &self,
+        target: &RasterTarget,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
+        fixed: &FixedTriangle,
+        step: &BarycentricStep,
+        span: &mut RowSpan,
+    ) {
+        let lanes = P::LANES;
+        let y = span.y;
 
-        let valid_rect =
-            Rectangle::clip(clipping_rect.unwrap_or_else(|| screen_rect), drawable_rect);
+        let step_x = fixed.step_x();
+        let block_step = step_x.map(|step| step * lanes as i64);
 
-        x_min = valid_rect.x_min();
-        y_min = valid_rect.y_min();
+        // Offsets of the edge values of each lane from the first pixel of the block.
+        let lane_values = step_x.map(|step| P::I64::lanes_times(step));
+        let thresholds = fixed.edges.map(|edge| P::I64::splat(-1 - edge.bias));
+        let all_lanes = (1_u32 << lanes) - 1;
 
-        x_max = valid_rect.x_max();
-        y_max = valid_rect.y_max();
+        let lane_index = P::lanes();
+        let bary_dx = step.dx.map(|dx| P::splat(dx) * lane_index);
 
-        let f_alpha = f_bc(a_center.x, a_center.y);
-        let f_beta = f_ca(b_center.x, b_center.y);
-        let f_gama = f_ab(c_center.x, c_center.y);
+        let inv_w = tri.vertices.map(|vertex| P::splat(vertex.inv_w));
+        let vertex_depth = tri.vertices.map(|vertex| P::splat(vertex.depth));
 
-        let f_alpha_outside = f_bc(-1.0, -1.0);
-        let f_beta_outside = f_ca(-1.0, -1.0);
-        let f_gama_outside = f_ab(-1.0, -1.0);
+        let mut affine_lanes = [[0.0_f32; 8]; 3];
+        let mut depth_lanes = [0.0_f32; 8];
 
-        for y in y_min..=y_max {
-            let y_f32 = y as f32;
-            for x in x_min..=x_max {
-                let x_f32 = x as f32;
+        while span.x + lanes <= span.x_max + 1 {
+            let x = span.x;
+            let values = span.values;
+
+            span.x += lanes;
+            span.values = add_step(values, block_step);
+
+            let coverage = (0..3).fold(all_lanes, |mask, edge| {
+                let edge_values = P::I64::splat(values[edge]) + lane_values[edge];
+                mask & edge_values.gt_mask(thresholds[edge])
+            });
 
-                let alpha: f32 = f_bc(x_f32, y_f32) / f_alpha;
-                let beta: f32 = f_ca(x_f32, y_f32) / f_beta;
-                let gama: f32 = f_ab(x_f32, y_f32) / f_gama;
+            if coverage == 0 {
+                continue;
+            }
+
+            let (alpha, beta, gama) = fixed.barycentric(values);
+            let affine = [
+                P::splat(alpha) + bary_dx[0],
+                P::splat(beta) + bary_dx[1],
+                P::splat(gama) + bary_dx[2],
+            ];
 
-                if alpha >= 0.0 && beta >= 0.0 && gama >= 0.0 {
-                    if (alpha > 0.0 || f_alpha * f_alpha_outside > 0.0)
-                        && (beta > 0.0 || f_beta * f_beta_outside > 0.0)
-                        && (gama > 0.0 || f_gama * f_gama_outside > 0.0)
-                    {
-                        let mut color_pixel =
-                            (alpha * color_a) + (beta * color_b) + (gama * color_c);
+            let weights = if self.perspective_correction {
+                let weighted: [P; 3] = std::array::from_fn(|i| affine[i] * inv_w[i]);
+                let inv_w = weighted[0] + weighted[1] + weighted[2];
 
-                        let color_alpha = (alpha * a_vertex.alpha)
-                            + (beta * b_vertex.alpha)
-                            + (gama * c_vertex.alpha);
+                weighted.map(|weight| weight / inv_w)
+            } else {
+                affine
+            };
 
-                        let color_uv = (a_uv * alpha) + (b_uv * beta) + (c_uv * gama);
+            let depth = weights[0] * vertex_depth[0]
+                + weights[1] * vertex_depth[1]
+                + weights[2] * vertex_depth[2];
 
-                        let color_rgba_slice = diffuse_map.get_rgba_slice(color_uv.x, color_uv.y);
-                        let texture_alpha = (color_rgba_slice[3] as f32) / 255.0;
-                        assert!((0.0..=1.0).contains(&texture_alpha));
+            let first_pixel = self.width * y + x;
+            let current_depth = P::load(target.depth_span(first_pixel, lanes));
 
-                        let color_texture =
-                            Color::Custom(color_rgba_slice[..3].try_into().unwrap()).as_pixel();
+            let visible = coverage
+                & match tri.depth_test {
+                    DepthTest::Less => depth.lt_mask(current_depth),
+                    DepthTest::LessEqual => depth.le_mask(current_depth),
+                };
 
-                        // color blending
-                        color_pixel =
-                            color_pixel * texture_alpha + color_texture * (1.0 - texture_alpha);
+            if visible == 0 {
+                continue;
+            }
 
-                        // Alpha resultante após combinação
-                        let final_alpha = color_alpha * texture_alpha;
+            for (i, lane) in affine.iter().enumerate() {
+                lane.store(&mut affine_lanes[i]);
+            }
+            depth.store(&mut depth_lanes);
 
-                        self.draw_pixel_coord_with_depth(x, y, color_pixel, final_alpha, 0.0);
-                    }
+            for lane in 0..lanes {
+                if visible & (1 << lane) == 0 {
+                    continue;
                 }
+
+                let affine = (
+                    affine_lanes[0][lane],
+                    affine_lanes[1][lane],
+                    affine_lanes[2][lane],
+                );
+
+                self.write_fragment(
+                    target,
+                    tri,
+                    shader,
+                    uniforms,
+                    (x + lane, y),
+                    affine,
+                    depth_lanes[lane],
+                    step,
+                );
             }
         }
     }
 
-    pub fn draw_triangle_with_attributes(
-        &mut self,
-        a_attr: &VertexAttributes,
-        b_attr: &VertexAttributes,
-        c_attr: &VertexAttributes,
-        texture: &Texture,
+    // Multisampled version of `rasterize_triangle`: the coverage and the depth
+    // test are evaluated at every sample of the pixel, but the fragment is
+    // shaded only once and its color is stored in the covered samples.
+    fn rasterize_triangle_msaa<S: FragmentShader>(
+        &self,
+        target: &RasterTarget,
+        tri: &ProjectedTriangle<S::Varyings>,
+        shader: &S,
+        uniforms: &Uniforms,
         clipping_rect: Option<Rectangle>,
     ) {
-        let a_center = self.pos_map_center(a_attr.screen_coord - self.offset);
-        let b_center = self.pos_map_center(b_attr.screen_coord - self.offset);
-        let c_center = self.pos_map_center(c_attr.screen_coord - self.offset);
+        let Some(fixed) = self.fixed_triangle(tri) else {
+            // Triângulo degenerado
+            return;
+        };
 
-        //let a_pixel_color = a_attr.color.as_pixel();
-        //let b_pixel_color = b_attr.color.as_pixel();
-        //let c_pixel_color = c_attr.color.as_pixel();
+        let Some(drawable_rect) = self.triangle_bounding_rect(&fixed) else {
+            return;
+        };
+        let screen_rect = Rectangle::from_coords(0, 0, self.width - 1, self.height - 1);
 
-        let a_depth = a_attr.depth;
-        let b_depth = b_attr.depth;
-        let c_depth = c_attr.depth;
+        let valid_rect =
+            Rectangle::clip(clipping_rect.unwrap_or_else(|| screen_rect), drawable_rect);
 
-        let f_ab = |x: f32, y: f32| -> f32 {
-            (a_center.y - b_center.y) * x
-                + (b_center.x - a_center.x) * y
-                + (a_center.x * b_center.y)
-                - (b_center.x * a_center.y)
-        };
+        let [a_attr, b_attr, c_attr] = &tri.vertices;
 
-        let f_bc = |x: f32, y: f32| -> f32 {
-            (b_center.y - c_center.y) * x
-                + (c_center.x - b_center.x) * y
-                + (b_center.x * c_center.y)
-                - (c_center.x * b_center.y)
-        };
+        let step = BarycentricStep::new(&fixed);
 
-        let f_ca = |x: f32, y: f32| -> f32 {
-            (c_center.y - a_center.y) * x
-                + (a_center.x - c_center.x) * y
-                + (c_center.x * a_center.y)
-                - (a_center.x * c_center.y)
-        };
+        // Os padrões de amostragem são múltiplos de 1/16 de pixel, então as
+        // amostras ficam exatamente na grade subpixel.
+        let sample_offsets = self.msaa.sample_offsets();
+        let samples = sample_offsets.len();
+        let fixed_offsets: [(i64, i64); 8] = std::array::from_fn(|sample| {
+            sample_offsets
+                .get(sample)
+                .map_or((0, 0), |(dx, dy)| (to_fixed(*dx), to_fixed(*dy)))
+        });
 
-        let min = |x: f32, y: f32, z: f32| -> f32 {
-            let mut ret = f32::INFINITY;
-            [x, y, z].iter().for_each(|v| {
-                if *v < ret {
-                    ret = *v;
-                }
-            });
+        let (x_min, y_min) = (valid_rect.x_min(), valid_rect.y_min());
+        let (x_max, y_max) = (valid_rect.x_max(), valid_rect.y_max());
 
-            ret
-        };
+        let step_x = fixed.step_x();
+        let step_y = fixed.step_y();
+        let mut row_values = fixed.edge_values(x_min, y_min);
 
-        let max = |x: f32, y: f32, z: f32| -> f32 {
-            let mut ret = -f32::INFINITY;
-            [x, y, z].iter().for_each(|v| {
-                if *v > ret {
-                    ret = *v;
-                }
-            });
+        for y in y_min..=y_max {
+            let mut values = row_values;
+            row_values = add_step(row_values, step_y);
 
-            ret
-        };
+            for x in x_min..=x_max {
+                let pixel_values = values;
+                values = add_step(values, step_x);
 
-        let mut x_min = min(a_center.x, b_center.x, c_center.x) as usize;
-        let mut y_min = min(a_center.y, b_center.y, c_center.y) as usize;
+                let first_sample = (self.width * y + x) * samples;
 
-        let mut x_max = max(a_center.x, b_center.x, c_center.x) as usize;
-        let mut y_max = max(a_center.y, b_center.y, c_center.y) as usize;
+                let mut covered = [false; 8];
+                let mut sample_depth = [0.0_f32; 8];
+                let mut centroid: Option<(f32, f32, f32)> = None;
 
-        let mut drawable_rect = Rectangle::from_coords(x_min, y_min, x_max, y_max);
-        let screen_rect = Rectangle::from_coords(0, 0, self.width - 1, self.height - 1);
+                for (sample, offset) in fixed_offsets[..samples].iter().enumerate() {
+                    let sample_values = fixed.offset(pixel_values, *offset);
+                    if !fixed.covers(sample_values) {
+                        continue;
+                    }
 
-        drawable_rect = Rectangle::clip(drawable_rect, screen_rect.clone());
+                    let affine = fixed.barycentric(sample_values);
+                    let (alpha, beta, gama) = affine;
+                    let (alpha, beta, gama) = self.perspective_barycentric(tri, alpha, beta, gama);
+                    let depth = alpha * a_attr.depth + beta * b_attr.depth + gama * c_attr.depth;
 
-        let valid_rect =
-            Rectangle::clip(clipping_rect.unwrap_or_else(|| screen_rect), drawable_rect);
+                    // SAFETY: the samples of the pixels inside the clipping
+                    // rect are only accessed by this thread.
+                    let current_depth = unsafe { target.sample_depth(first_sample + sample) };
+                    let visible = tri.depth_test.passes(depth, current_depth);
 
-        x_min = valid_rect.x_min();
-        y_min = valid_rect.y_min();
+                    if visible {
+                        covered[sample] = true;
+                        sample_depth[sample] = depth;
+                        centroid.get_or_insert(affine);
+                    }
+                }
 
-        x_max = valid_rect.x_max();
-        y_max = valid_rect.y_max();
+                // Nenhuma amostra visível
+                let Some(centroid) = centroid else {
+                    continue;
+                };
 
-        let f_alpha = f_bc(a_center.x, a_center.y);
-        let f_beta = f_ca(b_center.x, b_center.y);
-        let f_gama = f_ab(c_center.x, c_center.y);
+                let fragment = if tri.color_write || tri.alpha_test {
+                    // The fragment is shaded at the pixel center when it's inside
+                    // the triangle, otherwise at the first visible sample.
+                    let affine = if fixed.covers(pixel_values) {
+                        fixed.barycentric(pixel_values)
+                    } else {
+                        centroid
+                    };
+
+                    let (alpha, beta, gama) = affine;
+                    let (alpha, beta, gama) = self.perspective_barycentric(tri, alpha, beta, gama);
+                    let depth = alpha * a_attr.depth + beta * b_attr.depth + gama * c_attr.depth;
+
+                    let Some(output) =
+                        self.shade_fragment(tri, shader, uniforms, affine, depth, &step)
+                    else {
+                        // Fragmento descartado pelo shader.
+                        continue;
+                    };
+
+                    Some((output, depth))
+                } else {
+                    None
+                };
 
-        let f_alpha_outside = f_bc(-1.0, -1.0);
-        let f_beta_outside = f_ca(-1.0, -1.0);
-        let f_gama_outside = f_ab(-1.0, -1.0);
+                if let Some((FragmentOutput { color, opacity }, depth)) =
+                    fragment.filter(|_| tri.color_write)
+                {
+                    if self.oit_enabled && opacity < 1.0 {
+                        // O A-buffer guarda um fragmento por pixel, com a
+                        // opacidade atenuada pela cobertura das amostras.
+                        let coverage =
+                            covered.iter().filter(|c| **c).count() as f32 / samples as f32;
+
+                        // SAFETY: as above.
+                        unsafe {
+                            target.push_oit(
+                                self.width * y + x,
+                                OitFragment {
+                                    depth,
+                                    color,
+                                    opacity: opacity * coverage,
+                                },
+                            );
+                        }
+                    } else {
+                        let pixel_color = Pixel::from_linear_vec3(color);
+
+                        for sample in (0..samples).filter(|sample| covered[*sample]) {
+                            let index = first_sample + sample;
+
+                            // SAFETY: as above.
+                            unsafe {
+                                if self.hdr_enabled {
+                                    Self::blend_hdr(target, index, color, opacity);
+                                } else {
+                                    let bg_color = target.sample(index);
+                                    target.set_sample(
+                                        index,
+                                        Self::blend(bg_color, pixel_color, opacity),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
 
-        for y in y_min..=y_max {
-            let y_f32 = y as f32;
-            for x in x_min..=x_max {
-                let x_f32 = x as f32;
-
-                let alpha: f32 = f_bc(x_f32, y_f32) / f_alpha;
-                let beta: f32 = f_ca(x_f32, y_f32) / f_beta;
-                let gama: f32 = f_ab(x_f32, y_f32) / f_gama;
-
-                if alpha >= 0.0 && beta >= 0.0 && gama >= 0.0 {
-                    if (alpha > 0.0 || f_alpha * f_alpha_outside > 0.0)
-                        && (beta > 0.0 || f_beta * f_beta_outside > 0.0)
-                        && (gama > 0.0 || f_gama * f_gama_outside > 0.0)
-                    {
-                        let pixel_depth = (alpha * a_depth) + (beta * b_depth) + (gama * c_depth);
-
-                        // Texturasss ????
-                        let pixel_texture_coord = (a_attr.texture_coord * alpha)
-                            + (b_attr.texture_coord * beta)
-                            + (c_attr.texture_coord * gama);
-
-                        let diffuse_color_slice = texture
-                            .map_kd
-                            .get_rgb_slice(pixel_texture_coord.x(), pixel_texture_coord.y());
-
-                        let ambient_color_slice = texture
-                            .map_ka
-                            .get_rgb_slice(pixel_texture_coord.x(), pixel_texture_coord.y());
-
-                        let diffuse_color: Vec3 = Pixel::new(
-                            diffuse_color_slice[0],
-                            diffuse_color_slice[1],
-                            diffuse_color_slice[2],
-                        )
-                        .normalized_as_vec3();
-
-                        let ambient_color: Vec3 = Pixel::new(
-                            ambient_color_slice[0],
-                            ambient_color_slice[1],
-                            ambient_color_slice[2],
-                        )
-                        .normalized_as_vec3();
-
-                        // phong shadding ??
-                        // TODO: Transformar isso aq em operacao matricial + utilizar SIMD
-                        let pixel_normal = (a_attr.normal * alpha)
-                            + (b_attr.normal * beta)
-                            + (c_attr.normal * gama);
-
-                        let pixel_light =
-                            (a_attr.light * alpha) + (b_attr.light * beta) + (c_attr.light * gama);
-
-                        let pixel_halfway = (a_attr.halfway * alpha)
-                            + (b_attr.halfway * beta)
-                            + (c_attr.halfway * gama);
-
-                        let power = 2;
-
-                        /*
-                        let c_l = texture.ks;         // intensity term
-                        let c_r = pixel_color * 0.5;  // diffuse reflectance
-                        let c_a = pixel_color ;       // ambient term
-                        */
-
-                        let c_l = texture.ks; // intensity term
-                        let c_r = diffuse_color.color_multiply(texture.kd); // diffuse reflectance
-                        let c_a = ambient_color.color_multiply(texture.ka); // ambient term
-
-                        let color_normalized = c_r.color_multiply(
-                            c_a + c_l * (1.0 - 0.0_f32.max(pixel_light.dot(pixel_normal))),
-                        ) + c_l
-                            * pixel_halfway.dot(pixel_normal).powi(power);
-
-                        let color = Pixel::from_normalized_vec3(color_normalized);
-
-                        let pixel_opacity = texture.alpha;
-
-                        self.draw_pixel_coord_with_depth(x, y, color, pixel_opacity, pixel_depth);
+                if tri.depth_update {
+                    // SAFETY: as above.
+                    unsafe {
+                        for sample in (0..samples).filter(|sample| covered[*sample]) {
+                            target.set_sample_depth(first_sample + sample, sample_depth[sample]);
+                        }
+
+                        // O ID do pixel acompanha a profundidade da primeira amostra.
+                        if covered[0] {
+                            target.set_id(self.width * y + x, tri.id);
+                        }
                     }
                 }
             }
@@ -766,6 +2049,10 @@ impl Canvas {
 
     // TODO: rewrite!!!
     pub fn draw_line(&mut self, a: Vec2, b: Vec2, color: Color) {
+        let Some((a, b)) = self.clip_line(a, b) else {
+            return;
+        };
+
         let a_center = self.pos_map_center(a);
         let b_center = self.pos_map_center(b);
 
@@ -783,6 +2070,40 @@ impl Canvas {
         }
     }
 
+    // Recorta o segmento ab pelos limites da tela (Liang-Barsky). Retorna
+    // None quando o segmento está inteiro fora da tela.
+    fn clip_line(&self, a: Vec2, b: Vec2) -> Option<(Vec2, Vec2)> {
+        let delta = b - a;
+        let max = Vec2::new((self.width - 1) as f32, (self.height - 1) as f32);
+
+        let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+
+        // Cada par (p, q) é uma das bordas: o segmento está dentro dela
+        // onde t * p <= q.
+        for (p, q) in [
+            (-delta.x, a.x),
+            (delta.x, max.x - a.x),
+            (-delta.y, a.y),
+            (delta.y, max.y - a.y),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t_min = t_min.max(q / p);
+            } else {
+                t_max = t_max.min(q / p);
+            }
+        }
+
+        if t_min > t_max {
+            return None;
+        }
+
+        Some((a + delta * t_min, a + delta * t_max))
+    }
+
     // TODO: rewrite!!!
     fn midpoint_draw(&mut self, _a_center: Vec2, _b_center: Vec2, idx: usize, color: Color) {
         // idx      m \in
@@ -867,7 +2188,9 @@ impl Canvas {
                 _ => (0, 0),
             };
 
-            self.draw_pixel_coord(x as usize, y as usize, color.as_pixel());
+            if self.in_bounds(x as usize, y as usize) {
+                self.draw_pixel_coord(x as usize, y as usize, color.as_pixel());
+            }
 
             let d_cond = match idx {
                 0 => d > 0.0,
@@ -903,6 +2226,45 @@ impl Canvas {
         }
     }
 
+    fn depth_test(&self, x: usize, y: usize, depth: f32, test: DepthTest) -> bool {
+        debug_assert!(self.depth_frame.len() > 0, "Depth not initialized");
+
+        test.passes(depth, self.get_pixel_depth(x, y))
+    }
+
+    fn blend_pixel_coord(&mut self, x: usize, y: usize, color: Pixel, opacity: f32) {
+        let bg_color = self.get_pixel_coord(x, y);
+        self.draw_pixel_coord(x, y, Self::blend(bg_color, color, opacity));
+    }
+
+    // The colors are sRGB-encoded and are mixed in linear space.
+    fn blend(bg_color: Pixel, color: Pixel, opacity: f32) -> Pixel {
+        if opacity < 1.0 {
+            Pixel::from_linear_vec3(
+                bg_color.as_linear_vec3() * (1.0 - opacity) + color.as_linear_vec3() * opacity,
+            )
+        } else {
+            color
+        }
+    }
+
+    // `index` is the position of the pixel (or of the sample, with MSAA)
+    // in `hdr_frame`.
+    //
+    // SAFETY: the same as the accessors of `RasterTarget`.
+    unsafe fn blend_hdr(target: &RasterTarget, index: usize, color: Vec3, opacity: f32) {
+        let hdr_color = target.hdr(index);
+
+        target.set_hdr(
+            index,
+            if opacity < 1.0 {
+                hdr_color * (1.0 - opacity) + color * opacity
+            } else {
+                color
+            },
+        );
+    }
+
     fn draw_pixel_coord_with_depth(
         &mut self,
         x: usize,
@@ -911,17 +2273,8 @@ impl Canvas {
         opacity: f32,
         depth: f32,
     ) {
-        debug_assert!(self.depth_frame.len() > 0, "Depth not initialized");
-
-        let new_color = if opacity < 1.0 {
-            let bg_color = self.get_pixel_coord(x, y);
-            bg_color * (1.0 - opacity) + color * opacity
-        } else {
-            color
-        };
-
-        if depth < self.get_pixel_depth(x, y) {
-            self.draw_pixel_coord(x, y, new_color);
+        if self.depth_test(x, y, depth, DepthTest::Less) {
+            self.blend_pixel_coord(x, y, color, opacity);
 
             if self.depth_update_enabled == true {
                 self.set_pixel_depth(x, y, depth);
@@ -929,6 +2282,11 @@ impl Canvas {
         }
     }
 
+    // Position of the pixel (x, y) in `frame`, which is stored from the top row.
+    fn frame_index(&self, x: usize, y: usize) -> usize {
+        self.width * (self.height - y - 1) + x
+    }
+
     fn get_pixel_coord(&self, x: usize, y: usize) -> Pixel {
         debug_assert!(
             self.in_bounds(x, y),
@@ -981,3 +2339,325 @@ impl Canvas {
         self.frame.as_ptr().cast::<u8>()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::test_utils::Rng;
+
+    // Pinta o fragmento com a cor das varyings e a opacidade do shader.
+    struct ColorShader {
+        opacity: f32,
+    }
+
+    impl FragmentShader for ColorShader {
+        type Varyings = Vec3;
+
+        fn shade(&self, input: &FragmentInput<Vec3>) -> Option<FragmentOutput> {
+            Some(FragmentOutput {
+                color: input.varyings,
+                opacity: self.opacity,
+            })
+        }
+    }
+
+    const WIDTH: usize = 300;
+    const HEIGHT: usize = 200;
+
+    fn triangles<'a>(
+        rng: &mut Rng,
+        texture: &'a Texture,
+        count: usize,
+        opaque: bool,
+    ) -> Vec<ProjectedTriangle<'a, Vec3>> {
+        (0..count)
+            .map(|triangle| {
+                let center = Vec2::new(rng.next() * WIDTH as f32, rng.next() * HEIGHT as f32);
+                let vertices = std::array::from_fn(|_| ProjectedVertex {
+                    screen_coord: center
+                        + Vec2::new(rng.next() * 160.0 - 80.0, rng.next() * 160.0 - 80.0),
+                    depth: 1.0 + rng.next() * 10.0,
+                    inv_w: 0.5 + rng.next(),
+                    varyings: Vec3::new([rng.next(), rng.next(), rng.next()]),
+                });
+
+                ProjectedTriangle {
+                    vertices,
+                    texture,
+                    id: Some(PrimitiveId {
+                        object: 0,
+                        mesh: 0,
+                        triangle,
+                    }),
+                    depth_update: opaque,
+                    depth_test: DepthTest::Less,
+                    color_write: true,
+                    alpha_test: false,
+                }
+            })
+            .collect()
+    }
+
+    // Draws the same opaque and then transparent triangles with the given
+    // canvas setup, returning the frame, the depth and the ID buffers.
+    fn render(setup: impl Fn(&mut Canvas)) -> (Vec<u8>, Vec<f32>, Vec<Option<PrimitiveId>>) {
+        let texture = Texture::default();
+        let uniforms = Uniforms {
+            camera_position: Vec3::zeros(),
+            ambient_light: Vec3::zeros(),
+            lights: &[],
+            shadow_maps: &[],
+            environment: None,
+            environment_intensity: 0.0,
+        };
+
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let opaque = triangles(&mut rng, &texture, 60, true);
+        let transparent = triangles(&mut rng, &texture, 60, false);
+
+        let mut canvas = Canvas::new(WIDTH, HEIGHT);
+        canvas.set_id_buffer(true);
+        setup(&mut canvas);
+        canvas.init_depth(f32::INFINITY);
+        canvas.clear();
+
+        canvas.draw_projected_triangles(&opaque, &ColorShader { opacity: 1.0 }, &uniforms);
+        canvas.draw_projected_triangles(&transparent, &ColorShader { opacity: 0.4 }, &uniforms);
+        canvas.resolve();
+
+        (
+            canvas.as_bytes_slice().to_vec(),
+            canvas.depth_frame().to_vec(),
+            canvas.id_frame().to_vec(),
+        )
+    }
+
+    fn assert_tiled_matches_sequential(setup: impl Fn(&mut Canvas)) {
+        let sequential = render(|canvas| {
+            setup(canvas);
+            canvas.set_tiled_raster(false);
+        });
+        let tiled = render(|canvas| {
+            setup(canvas);
+            canvas.set_tiled_raster(true);
+            canvas.set_raster_threads(4);
+        });
+
+        assert!(sequential.0 == tiled.0, "frames differ");
+        assert!(
+            sequential
+                .1
+                .iter()
+                .map(|d| d.to_bits())
+                .eq(tiled.1.iter().map(|d| d.to_bits())),
+            "depths differ"
+        );
+        assert!(sequential.2 == tiled.2, "IDs differ");
+        assert!(sequential.2.iter().any(|id| id.is_some()));
+    }
+
+    #[test]
+    fn tiled_raster_matches_sequential_scalar() {
+        assert_tiled_matches_sequential(|canvas| canvas.set_simd_level(SimdLevel::Scalar));
+    }
+
+    #[test]
+    fn tiled_raster_matches_sequential_simd() {
+        for level in [SimdLevel::Sse2, SimdLevel::Avx2] {
+            assert_tiled_matches_sequential(|canvas| canvas.set_simd_level(level));
+        }
+    }
+
+    #[test]
+    fn tiled_raster_matches_sequential_msaa() {
+        assert_tiled_matches_sequential(|canvas| canvas.set_msaa(Msaa::X4));
+    }
+
+    #[test]
+    fn tiled_raster_matches_sequential_hdr_oit() {
+        assert_tiled_matches_sequential(|canvas| {
+            canvas.set_hdr(true);
+            canvas.set_oit(true);
+        });
+    }
+
+    #[test]
+    fn blend_mixes_in_linear_space() {
+        let half = srgb::linear_to_srgb(0.5);
+
+        let color = Canvas::blend(Pixel::black(), Pixel::white(), 0.5);
+        assert_eq!([color.r, color.g, color.b], [half; 3]);
+
+        let color = Canvas::blend(Pixel::black(), Pixel::white(), 1.0);
+        assert_eq!([color.r, color.g, color.b], [255; 3]);
+    }
+
+    #[test]
+    fn msaa_resolve_in_linear_space() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set_msaa(Msaa::X4);
+        canvas.clear();
+
+        canvas.sample_frame[..2].fill(Pixel::white());
+        canvas.sample_frame[2..].fill(Pixel::black());
+        canvas.resolve();
+
+        let color = canvas.get_pixel_coord(0, 0);
+        assert_eq!([color.r, color.g, color.b], [srgb::linear_to_srgb(0.5); 3]);
+    }
+
+    #[test]
+    fn background_rows_with_and_without_msaa() {
+        // Branco em cima (y = 1) e preto embaixo (y = 0).
+        let background = |_: f32, y: f32| Vec3::new([y; 3]);
+
+        for msaa in [Msaa::Off, Msaa::X4] {
+            let mut canvas = Canvas::new(1, 2);
+            canvas.set_msaa(msaa);
+            canvas.clear_with_background(background);
+            canvas.resolve();
+
+            assert_eq!(canvas.pixel_color(0, 1), [255; 3], "{:?}", msaa);
+            assert_eq!(canvas.pixel_color(0, 0), [0; 3], "{:?}", msaa);
+        }
+    }
+
+    #[test]
+    fn background_with_worker_threads() {
+        let background = |x: f32, y: f32| Vec3::new([x / 3.0, y / HEIGHT as f32, 0.5]);
+
+        let frame = |threads: usize| {
+            let mut canvas = Canvas::new(3, HEIGHT);
+            canvas.set_raster_threads(threads);
+            canvas.clear_with_background(background);
+            canvas.as_bytes_slice().to_vec()
+        };
+        assert!(frame(1) == frame(4), "frames differ");
+
+        // Sem pixels, não há faixas para preencher.
+        for (width, height) in [(0, 4), (4, 0)] {
+            let mut canvas = Canvas::new(width, height);
+            canvas.clear_with_background(background);
+        }
+    }
+
+    #[test]
+    fn hdr_resolve_keeps_rows_in_place() {
+        let texture = Texture::default();
+        let uniforms = Uniforms {
+            camera_position: Vec3::zeros(),
+            ambient_light: Vec3::zeros(),
+            lights: &[],
+            shadow_maps: &[],
+            environment: None,
+            environment_intensity: 0.0,
+        };
+
+        // Triângulo retângulo no canto de baixo à esquerda: não é simétrico
+        // em relação à linha do meio.
+        let vertex = |x: f32, y: f32| ProjectedVertex {
+            screen_coord: Vec2::new(x, y),
+            depth: 1.0,
+            inv_w: 1.0,
+            varyings: Vec3::new([0.2, 0.6, 1.0]),
+        };
+        let triangle = ProjectedTriangle {
+            vertices: [vertex(2.0, 2.0), vertex(30.0, 2.0), vertex(2.0, 14.0)],
+            texture: &texture,
+            id: None,
+            depth_update: true,
+            depth_test: DepthTest::Less,
+            color_write: true,
+            alpha_test: false,
+        };
+
+        let frame = |hdr: bool| {
+            let mut canvas = Canvas::new(32, 20);
+            canvas.set_hdr(hdr);
+            canvas.set_tone_mapping(ToneMapping::Clamp);
+            canvas.set_exposure(1.0);
+            canvas.init_depth(f32::INFINITY);
+            canvas.clear_with_color(Vec3::zeros());
+            canvas.draw_projected_triangles(&[triangle], &ColorShader { opacity: 1.0 }, &uniforms);
+            canvas.resolve();
+            canvas
+        };
+
+        let sdr = frame(false);
+        let hdr = frame(true);
+        assert_ne!(sdr.pixel_color(4, 4), [0; 3]);
+        assert_eq!(sdr.pixel_color(4, 16), [0; 3]);
+        assert!(
+            sdr.as_bytes_slice() == hdr.as_bytes_slice(),
+            "HDR frame differs"
+        );
+    }
+
+    #[test]
+    fn oit_blends_over_the_opaque_pixel_below() {
+        let texture = Texture::default();
+        let uniforms = Uniforms {
+            camera_position: Vec3::zeros(),
+            ambient_light: Vec3::zeros(),
+            lights: &[],
+            shadow_maps: &[],
+            environment: None,
+            environment_intensity: 0.0,
+        };
+
+        let triangle =
+            |vertices: [Vec2; 3], depth: f32, color: Vec3, opaque: bool| ProjectedTriangle {
+                vertices: vertices.map(|screen_coord| ProjectedVertex {
+                    screen_coord,
+                    depth,
+                    inv_w: 1.0,
+                    varyings: color,
+                }),
+                texture: &texture,
+                id: None,
+                depth_update: opaque,
+                depth_test: DepthTest::Less,
+                color_write: true,
+                alpha_test: false,
+            };
+
+        let mut canvas = Canvas::new(8, 8);
+        canvas.set_oit(true);
+        canvas.init_depth(f32::INFINITY);
+        canvas.clear_with_color(Vec3::zeros());
+
+        // Vermelho opaco só nas linhas de baixo; verde transparente cobrindo
+        // todo o canvas, na frente.
+        let bottom = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(16.0, 0.0),
+            Vec2::new(0.0, 2.0),
+        ];
+        let full = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(16.0, 0.0),
+            Vec2::new(0.0, 16.0),
+        ];
+        let red = Vec3::new([1.0, 0.0, 0.0]);
+        let green = Vec3::new([0.0, 1.0, 0.0]);
+
+        canvas.draw_projected_triangles(
+            &[triangle(bottom, 2.0, red, true)],
+            &ColorShader { opacity: 1.0 },
+            &uniforms,
+        );
+        canvas.draw_projected_triangles(
+            &[triangle(full, 1.0, green, false)],
+            &ColorShader { opacity: 0.5 },
+            &uniforms,
+        );
+        canvas.resolve();
+
+        let pixel = |color: Vec3| {
+            let pixel = Pixel::from_linear_vec3(color);
+            [pixel.r, pixel.g, pixel.b]
+        };
+        assert_eq!(canvas.pixel_color(0, 0), pixel(red * 0.5 + green * 0.5));
+        assert_eq!(canvas.pixel_color(0, 7), pixel(green * 0.5));
+    }
+}
@@ -0,0 +1,318 @@
+use std::f32::consts::PI;
+
+use super::linalg::{Vec2, Vec3};
+use super::scene::{ColorSpace, TextureFilter, TextureMap, WrapMode};
+
+// Fundo da cena, visto nas direções em que nenhuma superfície foi desenhada.
+pub enum Background {
+    // Linear color.
+    Color(Vec3),
+    // Linear colors of the zenith and of the nadir, interpolated by the
+    // height of the direction.
+    Gradient { top: Vec3, bottom: Vec3 },
+    // Equirectangular panorama: u is the longitude, starting at -z, and v
+    // the latitude.
+    Panorama(TextureMap),
+    // Faces in the order +X, -X, +Y, -Y, +Z, -Z, with the orientation of
+    // the OpenGL cube maps.
+    CubeMap(Box<[TextureMap; 6]>),
+}
+
+impl Background {
+    // Nomes aceitos para o arquivo de cada face de um cube map, na ordem
+    // das faces.
+    const CUBE_FACE_NAMES: [[&'static str; 3]; 6] = [
+        ["px", "posx", "right"],
+        ["nx", "negx", "left"],
+        ["py", "posy", "top"],
+        ["ny", "negy", "bottom"],
+        ["pz", "posz", "front"],
+        ["nz", "negz", "back"],
+    ];
+
+    pub fn load_panorama(file_path: &std::path::PathBuf) -> Self {
+        Self::Panorama(TextureMap::load_from_file(
+            file_path,
+            ColorSpace::Srgb,
+            TextureFilter::Trilinear,
+        ))
+    }
+
+    // Loads the six faces of a cube map from the images of the directory
+    // `dir`, named after the faces (e.g. px.png, negx.jpg or right.png).
+    // Returns None when some face is missing.
+    pub fn load_cube_map(dir: &std::path::Path) -> Option<Self> {
+        let files: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+
+        let mut faces = Vec::with_capacity(Self::CUBE_FACE_NAMES.len());
+
+        for names in Self::CUBE_FACE_NAMES {
+            let Some(file_path) = files.iter().find(|path| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| names.contains(&stem.to_lowercase().as_str()))
+            }) else {
+                eprintln!("Cube map face {} not found in {}", names[0], dir.display());
+                return None;
+            };
+
+            let mut face =
+                TextureMap::load_from_file(file_path, ColorSpace::Srgb, TextureFilter::Trilinear);
+            face.set_wrap(WrapMode::ClampToEdge);
+            faces.push(face);
+        }
+
+        let faces: [TextureMap; 6] = faces.try_into().ok()?;
+
+        Some(Self::CubeMap(Box::new(faces)))
+    }
+
+    // Radiance arriving from the direction `dir`. `footprint` is the angle,
+    // in radians, covered by the sample, which selects the mip level of the
+    // images.
+    pub fn sample(&self, dir: Vec3, footprint: f32) -> Vec3 {
+        match self {
+            Self::Color(color) => *color,
+
+            Self::Gradient { top, bottom } => {
+                let t = (dir.normalized().y() * 0.5 + 0.5).clamp(0.0, 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+
+            Self::Panorama(map) => {
+                let dir = dir.normalized();
+                let u = 0.5 + dir.x().atan2(-dir.z()) / (2.0 * PI);
+                let v = 0.5 + dir.y().clamp(-1.0, 1.0).asin() / PI;
+
+                map.sample(
+                    u,
+                    v,
+                    Vec2::new(footprint / (2.0 * PI), 0.0),
+                    Vec2::new(0.0, footprint / PI),
+                )
+            }
+
+            Self::CubeMap(faces) => {
+                let (x, y, z) = (dir.x(), dir.y(), dir.z());
+                let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+                // Face do eixo de maior componente e coordenadas (s, t) nela,
+                // com t crescendo para baixo na imagem.
+                let (face, s, t, major) = if ax >= ay && ax >= az {
+                    if x > 0.0 {
+                        (0, -z, -y, ax)
+                    } else {
+                        (1, z, -y, ax)
+                    }
+                } else if ay >= az {
+                    if y > 0.0 {
+                        (2, x, z, ay)
+                    } else {
+                        (3, x, -z, ay)
+                    }
+                } else if z > 0.0 {
+                    (4, x, -y, az)
+                } else {
+                    (5, -x, -y, az)
+                };
+
+                if major <= 0.0 {
+                    return Vec3::zeros();
+                }
+
+                let u = (s / major + 1.0) * 0.5;
+                let v = 1.0 - (t / major + 1.0) * 0.5;
+
+                // Cada face cobre 90 graus.
+                let duv = footprint / (PI / 2.0);
+
+                faces[face].sample(u, v, Vec2::new(duv, 0.0), Vec2::new(0.0, duv))
+            }
+        }
+    }
+}
+
+// Equirectangular map of linear colors, with the same layout as
+// `Background::Panorama`.
+struct LightProbe {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+}
+
+impl LightProbe {
+    fn from_background(background: &Background, width: usize, height: usize) -> Self {
+        let footprint = 2.0 * PI / width as f32;
+
+        let texels = (0..width * height)
+            .map(|i| Self::texel_direction(i % width, i / width, width, height))
+            .map(|dir| background.sample(dir, footprint))
+            .collect();
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    // Direction of the center of the texel (x, y), with y = 0 at the zenith.
+    fn texel_direction(x: usize, y: usize, width: usize, height: usize) -> Vec3 {
+        let longitude = ((x as f32 + 0.5) / width as f32 - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * PI;
+
+        Vec3::new([
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+            -latitude.cos() * longitude.cos(),
+        ])
+    }
+
+    // Bilinear sample in the direction `dir`, repeating the longitude and
+    // clamping the latitude.
+    fn sample(&self, dir: Vec3) -> Vec3 {
+        let dir = dir.normalized();
+        let u = 0.5 + dir.x().atan2(-dir.z()) / (2.0 * PI);
+        let v = 0.5 - dir.y().clamp(-1.0, 1.0).asin() / PI;
+
+        let x = u * self.width as f32 - 0.5;
+        let y = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let texel = |x: isize, y: isize| {
+            let x = x.rem_euclid(self.width as isize) as usize;
+            let y = y.clamp(0, self.height as isize - 1) as usize;
+            self.texels[y * self.width + x]
+        };
+
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+        let bottom = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    // Weighted average of the probe around each texel of a new width x
+    // height probe, with the weight `kernel(cos)` of each texel of `self`,
+    // where cos is the cosine of the angle between the two directions.
+    fn convolve(&self, width: usize, height: usize, kernel: impl Fn(f32) -> f32 + Sync) -> Self {
+        // Direção e ângulo sólido (a menos de uma constante) das amostras.
+        let samples: Vec<(Vec3, f32, Vec3)> = (0..self.width * self.height)
+            .map(|i| {
+                let dir =
+                    Self::texel_direction(i % self.width, i / self.width, self.width, self.height);
+                let solid_angle = (1.0 - dir.y() * dir.y()).max(0.0).sqrt();
+
+                (dir, solid_angle, self.texels[i])
+            })
+            .collect();
+
+        let mut texels = vec![Vec3::zeros(); width * height];
+
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let rows_per_thread = height.div_ceil(threads);
+
+        std::thread::scope(|scope| {
+            for (chunk_idx, chunk) in texels.chunks_mut(rows_per_thread * width).enumerate() {
+                let samples = &samples;
+                let kernel = &kernel;
+
+                scope.spawn(move || {
+                    for (i, texel) in chunk.iter_mut().enumerate() {
+                        let i = chunk_idx * rows_per_thread * width + i;
+                        let normal = Self::texel_direction(i % width, i / width, width, height);
+
+                        let mut sum = Vec3::zeros();
+                        let mut total_weight = 0.0;
+
+                        for (dir, solid_angle, radiance) in samples.iter() {
+                            let cos = normal.dot(*dir);
+                            if cos <= 0.0 {
+                                continue;
+                            }
+
+                            let weight = kernel(cos) * solid_angle;
+                            sum += *radiance * weight;
+                            total_weight += weight;
+                        }
+
+                        if total_weight > 0.0 {
+                            *texel = sum / total_weight;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+// Iluminação vinda do fundo (image-based lighting), pré-filtrada para a
+// reflexão difusa e para a especular de cada rugosidade.
+pub struct EnvironmentLight {
+    // Radiance reflected by a white Lambertian surface, by its normal
+    // (the irradiance divided by π).
+    irradiance: LightProbe,
+    // Radiance reflected around the mirror direction, for the roughnesses
+    // 0, 1/4, 2/4, 3/4 and 1.
+    specular: Vec<LightProbe>,
+}
+
+impl EnvironmentLight {
+    pub fn new(background: &Background) -> Self {
+        let mirror = LightProbe::from_background(background, 128, 64);
+        let source = LightProbe::from_background(background, 64, 32);
+
+        let irradiance = source.convolve(32, 16, |cos| cos);
+
+        // Lóbulo de Phong equivalente à distribuição GGX de cada rugosidade.
+        let lobe = |roughness: f32| {
+            let alpha = roughness * roughness;
+            let exponent = 2.0 / (alpha * alpha) - 2.0;
+            move |cos: f32| cos.powf(exponent)
+        };
+
+        let mut specular = vec![
+            source.convolve(64, 32, lobe(0.25)),
+            source.convolve(32, 16, lobe(0.5)),
+            source.convolve(32, 16, lobe(0.75)),
+            source.convolve(32, 16, lobe(1.0)),
+        ];
+        specular.insert(0, mirror);
+
+        Self {
+            irradiance,
+            specular,
+        }
+    }
+
+    pub fn diffuse(&self, normal: Vec3) -> Vec3 {
+        self.irradiance.sample(normal)
+    }
+
+    // Radiance reflected in the direction `dir` by a surface with the GGX
+    // `roughness`, interpolated between the prefiltered levels.
+    pub fn specular(&self, dir: Vec3, roughness: f32) -> Vec3 {
+        let levels = self.specular.len() - 1;
+        let level = roughness.clamp(0.0, 1.0) * levels as f32;
+
+        let lower = (level.floor() as usize).min(levels - 1);
+        let t = level - lower as f32;
+
+        self.specular[lower].sample(dir) * (1.0 - t) + self.specular[lower + 1].sample(dir) * t
+    }
+}
@@ -0,0 +1,136 @@
+use super::linalg::Vec3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    // Luz no infinito: todos os raios são paralelos a `direction`.
+    Directional {
+        direction: Vec3,
+    },
+    Point {
+        position: Vec3,
+        // Distance at which the light stops contributing.
+        range: f32,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        range: f32,
+        // Half-angles of the cone, in degrees. The intensity goes from full,
+        // inside the inner cone, to zero at the outer one.
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    // Linear color.
+    pub color: Vec3,
+    pub intensity: f32,
+    pub cast_shadows: bool,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional {
+                direction: direction.normalized(),
+            },
+            color,
+            intensity,
+            cast_shadows: true,
+        }
+    }
+
+    pub fn point(position: Vec3, range: f32, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Point { position, range },
+            color,
+            intensity,
+            cast_shadows: true,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+        color: Vec3,
+        intensity: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                position,
+                direction: direction.normalized(),
+                range,
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            intensity,
+            cast_shadows: true,
+        }
+    }
+
+    // Direction from `point` to the light and the radiance that arrives
+    // there. Returns None when the light doesn't reach the point.
+    pub fn incident(&self, point: Vec3) -> Option<(Vec3, Vec3)> {
+        let (to_light, attenuation) = match self.kind {
+            LightKind::Directional { direction } => (direction.normalized() * -1.0, 1.0),
+
+            LightKind::Point { position, range } => {
+                let to_light = position - point;
+                let dist = to_light.norm();
+
+                (to_light / dist, distance_attenuation(dist, range))
+            }
+
+            LightKind::Spot {
+                position,
+                direction,
+                range,
+                inner_angle,
+                outer_angle,
+            } => {
+                let to_light = position - point;
+                let dist = to_light.norm();
+                let to_light = to_light / dist;
+
+                let cos_inner = inner_angle.to_radians().cos();
+                let cos_outer = outer_angle.to_radians().cos();
+                let cos_theta = direction.normalized().dot(to_light * -1.0);
+
+                let cone = smoothstep(cos_outer, cos_inner, cos_theta);
+
+                (to_light, distance_attenuation(dist, range) * cone)
+            }
+        };
+
+        if attenuation <= 0.0 || !to_light.x().is_finite() {
+            return None;
+        }
+
+        Some((to_light, self.color * (self.intensity * attenuation)))
+    }
+}
+
+// Decai suavemente com o quadrado da distância até zerar em `range`.
+fn distance_attenuation(dist: f32, range: f32) -> f32 {
+    let ratio = dist / range;
+    let window = (1.0 - ratio * ratio).max(0.0);
+
+    window * window
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1.0 } else { 0.0 };
+    }
+
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+
+    t * t * (3.0 - 2.0 * t)
+}
@@ -1,11 +1,23 @@
 #[cfg(target_arch = "x86")]
 use std::arch::x86::*;
 
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Neg, Sub};
 
 pub const EPS: f32 = 0.0000001;
 
-#[derive(Debug, Copy, Clone)]
+// Se o determinante é desprezível em relação ao produto das normas das
+// linhas, o maior valor que ele pode ter (desigualdade de Hadamard). Ao
+// contrário de comparar com EPS, não depende da escala da matriz.
+fn is_singular<const N: usize>(det: f32, rows: &[[f32; N]; N]) -> bool {
+    let bound: f32 = rows
+        .iter()
+        .map(|row| row.iter().map(|v| v * v).sum::<f32>().sqrt())
+        .product();
+
+    det.abs() <= EPS * bound
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -64,7 +76,43 @@ impl Div<f32> for Vec2 {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Neg for Vec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Index<usize> for Vec2 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Vec2 index out of bounds: {}", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec2 {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Vec2 index out of bounds: {}", index),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec4 {
     a: [f32; 4],
 }
@@ -119,6 +167,14 @@ impl Add for Vec4 {
     }
 }
 
+impl Sub for Vec4 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + (-rhs)
+    }
+}
+
 impl Mul<f32> for Vec4 {
     type Output = Self;
 
@@ -142,6 +198,46 @@ impl Mul<Vec4> for f32 {
     }
 }
 
+impl Div<f32> for Vec4 {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self {
+        Self {
+            a: self.a.map(|value| value / rhs),
+        }
+    }
+}
+
+impl Neg for Vec4 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            a: self.a.map(|value| -value),
+        }
+    }
+}
+
+impl AddAssign for Vec4 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Index<usize> for Vec4 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        &self.a[index]
+    }
+}
+
+impl IndexMut<usize> for Vec4 {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        &mut self.a[index]
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec3 {
     a: [f32; 3],
@@ -258,7 +354,37 @@ impl Sub for Vec3 {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Neg for Vec3 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            a: self.a.map(|value| -value),
+        }
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        &self.a[index]
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        &mut self.a[index]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Matrix4 {
     a: [[f32; 4]; 4],
 }
@@ -272,6 +398,188 @@ impl Matrix4 {
         Self { a: [[0.0; 4]; 4] }
     }
 
+    pub fn identity() -> Self {
+        Self::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1., 0., 0., offset.x()],
+            [0., 1., 0., offset.y()],
+            [0., 0., 1., offset.z()],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0., 0., 0.],
+            [0., factors.y(), 0., 0.],
+            [0., 0., factors.z(), 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    // Matriz View de uma câmera em `eye` olhando para `target`: leva os
+    // pontos do mundo para a base (u, v, w) da câmera, que olha ao longo de
+    // -w. O vetor `up` não pode ser paralelo à direção de visão.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let w = (eye - target).normalized();
+        let u = up.cross(w).normalized();
+        let v = w.cross(u);
+
+        // A transposta da base da câmera vezes a translação T(-eye).
+        Self::new([
+            [u.x(), u.y(), u.z(), -u.dot(eye)],
+            [v.x(), v.y(), v.z(), -v.dot(eye)],
+            [w.x(), w.y(), w.z(), -w.dot(eye)],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    // Leva a caixa [left, right] x [bottom, top] x [far, near] para o cubo
+    // canônico [-1, 1], com o plano near em z = 1. Como a câmera olha ao
+    // longo de -z, `near` e `far` são negativos e near > far.
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let (l, r, b, t, n, f) = (left, right, bottom, top, near, far);
+
+        Self::new([
+            [2.0 / (r - l), 0.0, 0.0, -(r + l) / (r - l)],
+            [0.0, 2.0 / (t - b), 0.0, -(t + b) / (t - b)],
+            [0.0, 0.0, 2.0 / (n - f), -(n + f) / (n - f)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Projeção perspectiva do frustum cuja janela no plano near é
+    // [left, right] x [bottom, top], até o espaço de recorte. Os pontos
+    // visíveis ficam com w > 0 e satisfazem -w <= x, y, z <= w.
+    pub fn perspective(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let (n, f) = (near, far);
+
+        // A matriz de perspectiva mapeia o frustum para o volume de visão
+        // ortográfica: mantém os pontos do plano z = n e leva o grande
+        // retângulo em z = f para o pequeno retângulo em z = f. Ela resulta
+        // em w = z, que é negativo para os pontos na frente da câmera, por
+        // isso invertemos o sinal das coordenadas homogêneas, o que não
+        // altera o ponto desomogeneizado.
+        let persp = Self::new([
+            [-n, 0.0, 0.0, 0.0],
+            [0.0, -n, 0.0, 0.0],
+            [0.0, 0.0, -(n + f), n * f],
+            [0.0, 0.0, -1.0, 0.0],
+        ]);
+
+        Self::orthographic(left, right, bottom, top, near, far) * persp
+    }
+
+    // Menores 2x2 das duas primeiras linhas (s) e das duas últimas (c),
+    // usados pela expansão de Laplace do determinante e da adjunta.
+    fn minors(&self) -> ([f32; 6], [f32; 6]) {
+        let m = &self.a;
+
+        let s = [
+            m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3],
+        ];
+
+        let c = [
+            m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3],
+        ];
+
+        (s, c)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.minors();
+
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // None when the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.a;
+        let (s, c) = self.minors();
+
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if is_singular(det, m) {
+            return None;
+        }
+
+        // Adjunta dividida pelo determinante.
+        let adjugate = [
+            [
+                m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3],
+                -m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3],
+                m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3],
+                -m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3],
+            ],
+            [
+                -m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1],
+                m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1],
+                -m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1],
+                m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1],
+            ],
+            [
+                m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0],
+                -m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0],
+                m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0],
+                -m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0],
+            ],
+            [
+                -m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0],
+                m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0],
+                -m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0],
+                m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0],
+            ],
+        ];
+
+        Some(Self::new(adjugate.map(|row| row.map(|value| value / det))))
+    }
+
+    // Matriz que transforma as normais das superfícies transformadas por
+    // uma matriz afim: a inversa transposta do bloco 3x3, a menos de um
+    // fator positivo, por isso as normais devem ser normalizadas depois.
+    // É calculada pela matriz dos cofatores, que existe mesmo quando o
+    // bloco não é inversível.
+    pub fn normal_matrix(&self) -> Mat3 {
+        let block = self.as_mat3();
+        let cofactors = block.cofactors();
+
+        // A matriz dos cofatores é a inversa transposta vezes o
+        // determinante, cujo sinal inverteria as normais dos espelhamentos.
+        if block.determinant() < 0.0 {
+            -cofactors
+        } else {
+            cofactors
+        }
+    }
+
+    // Bloco 3x3 superior esquerdo, a parte linear de uma matriz afim.
+    pub fn as_mat3(&self) -> Mat3 {
+        let m = &self.a;
+
+        Mat3::new([
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ])
+    }
+
     pub fn transposed(&self) -> Self {
         let mut cpy = self.clone();
 
@@ -358,3 +666,871 @@ impl Mul<Vec4> for Matrix4 {
         out
     }
 }
+
+impl Index<usize> for Matrix4 {
+    type Output = [f32; 4];
+
+    fn index(&self, row: usize) -> &[f32; 4] {
+        &self.a[row]
+    }
+}
+
+impl IndexMut<usize> for Matrix4 {
+    fn index_mut(&mut self, row: usize) -> &mut [f32; 4] {
+        &mut self.a[row]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat3 {
+    a: [[f32; 3]; 3],
+}
+
+impl Mat3 {
+    pub fn new(data: [[f32; 3]; 3]) -> Self {
+        Self { a: data }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]])
+    }
+
+    pub fn transposed(&self) -> Self {
+        let m = &self.a;
+
+        Self::new(std::array::from_fn(|i| std::array::from_fn(|j| m[j][i])))
+    }
+
+    // Matriz dos cofatores, a adjunta transposta.
+    pub fn cofactors(&self) -> Self {
+        let m = &self.a;
+
+        Self::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let (i0, i1) = ((i + 1) % 3, (i + 2) % 3);
+                let (j0, j1) = ((j + 1) % 3, (j + 2) % 3);
+                m[i0][j0] * m[i1][j1] - m[i0][j1] * m[i1][j0]
+            })
+        }))
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let cofactors = self.cofactors();
+
+        (0..3).map(|j| self.a[0][j] * cofactors.a[0][j]).sum()
+    }
+
+    // None when the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if is_singular(det, &self.a) {
+            return None;
+        }
+
+        let adjugate = self.cofactors().transposed();
+        Some(Self::new(
+            adjugate.a.map(|row| row.map(|value| value / det)),
+        ))
+    }
+
+    pub fn as_matrix4(&self) -> Matrix4 {
+        let m = &self.a;
+
+        Matrix4::new([
+            [m[0][0], m[0][1], m[0][2], 0.],
+            [m[1][0], m[1][1], m[1][2], 0.],
+            [m[2][0], m[2][1], m[2][2], 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..3).map(|k| self.a[i][k] * rhs.a[k][j]).sum())
+        }))
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(
+            self.a
+                .map(|row| row[0] * rhs.x() + row[1] * rhs.y() + row[2] * rhs.z()),
+        )
+    }
+}
+
+impl Neg for Mat3 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(self.a.map(|row| row.map(|value| -value)))
+    }
+}
+
+impl Index<usize> for Mat3 {
+    type Output = [f32; 3];
+
+    fn index(&self, row: usize) -> &[f32; 3] {
+        &self.a[row]
+    }
+}
+
+impl IndexMut<usize> for Mat3 {
+    fn index_mut(&mut self, row: usize) -> &mut [f32; 3] {
+        &mut self.a[row]
+    }
+}
+
+// Quaternion de rotação w + xi + yj + zk.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quat {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Rotação de `angle` radianos em torno de `axis`, pela regra da mão
+    // direita.
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+
+        Self::new(cos, axis.x() * sin, axis.y() * sin, axis.z() * sin)
+    }
+
+    // Rotação em torno de x, depois de y e por fim de z, com os ângulos em
+    // radianos de `angles`.
+    pub fn from_euler(angles: Vec3) -> Self {
+        let x = Self::from_axis_angle(Vec3::new([1., 0., 0.]), angles.x());
+        let y = Self::from_axis_angle(Vec3::new([0., 1., 0.]), angles.y());
+        let z = Self::from_axis_angle(Vec3::new([0., 0., 1.]), angles.z());
+
+        z * y * x
+    }
+
+    // Inversa de `from_euler`, com o ângulo em torno de y em [-π/2, π/2].
+    pub fn to_euler(&self) -> Vec3 {
+        let Self { w, x, y, z } = *self;
+
+        let angle_x = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let angle_y = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let angle_z = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+
+        Vec3::new([angle_x, angle_y, angle_z])
+    }
+
+    pub fn norm(&self) -> f32 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn normalized(&self) -> Self {
+        let norm = self.norm();
+        Self::new(self.w / norm, self.x / norm, self.y / norm, self.z / norm)
+    }
+
+    // Para quaternions unitários, a rotação inversa.
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(&self, rhs: Self) -> f32 {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    // Interpolação esférica entre os quaternions unitários `self` (t = 0)
+    // e `target` (t = 1), com velocidade angular constante e pelo menor
+    // arco entre as duas rotações.
+    pub fn slerp(&self, target: Self, t: f32) -> Self {
+        // q e -q representam a mesma rotação; escolhemos o mais próximo.
+        let mut cos = self.dot(target);
+        let target = if cos < 0.0 {
+            cos = -cos;
+            -target
+        } else {
+            target
+        };
+
+        // Para rotações muito próximas o seno se anula, e a interpolação
+        // linear normalizada é equivalente.
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+
+        Self::new(
+            self.w * a + target.w * b,
+            self.x * a + target.x * b,
+            self.y * a + target.y * b,
+            self.z * a + target.z * b,
+        )
+        .normalized()
+    }
+
+    // Aplica a rotação, de um quaternion unitário, ao vetor `v`.
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        let v = Self::new(0.0, v.x(), v.y(), v.z());
+        let rotated = *self * v * self.conjugate();
+
+        Vec3::new([rotated.x, rotated.y, rotated.z])
+    }
+
+    // Matriz de rotação do quaternion, que deve ser unitário.
+    pub fn as_matrix(&self) -> Matrix4 {
+        let Self { w, x, y, z } = *self;
+
+        Matrix4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+}
+
+// Produto de Hamilton: `a * b` aplica a rotação b e depois a.
+impl Mul for Quat {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+impl Neg for Quat {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+// Tipos empacotados usados pelo rasterizador vetorizado: cada valor guarda
+// um bloco de `LANES` pixels consecutivos de uma linha.
+//
+// Os tipos de 4 lanes usam apenas SSE2, que faz parte do x86_64. Os de 8
+// lanes usam AVX2 e só podem ser criados depois de verificar o suporte da
+// CPU, por isso os construtores são `unsafe`.
+//
+// Invariante: um valor desses tipos só existe se a CPU suporta as features
+// do tipo, já que todo valor vem de um construtor `unsafe`. Os métodos e os
+// operadores seguros usam os intrínsecos contando com isso.
+#[cfg(target_arch = "x86_64")]
+pub mod packed {
+    use std::arch::x86_64::*;
+    use std::ops::{Add, Div, Mul, Sub};
+
+    pub trait PackedF32:
+        Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+    {
+        const LANES: usize;
+
+        type I64: PackedI64;
+
+        /// Every lane set to `value`.
+        ///
+        /// # Safety
+        ///
+        /// The CPU must support the target features of the type.
+        unsafe fn splat(value: f32) -> Self;
+
+        /// The lane indices: 0, 1, 2, ...
+        ///
+        /// # Safety
+        ///
+        /// The CPU must support the target features of the type.
+        unsafe fn lanes() -> Self;
+
+        /// Loads `LANES` values from the beginning of the slice, which
+        /// panics when it is shorter.
+        ///
+        /// # Safety
+        ///
+        /// The CPU must support the target features of the type.
+        unsafe fn load(values: &[f32]) -> Self;
+
+        // Stores the lanes at the beginning of the slice, which panics
+        // when it is shorter.
+        fn store(self, values: &mut [f32]);
+
+        // Bit masks (lane i in the bit i) of the comparisons.
+        fn lt_mask(self, rhs: Self) -> u32;
+        fn le_mask(self, rhs: Self) -> u32;
+    }
+
+    pub trait PackedI64: Copy + Add<Output = Self> {
+        /// Every lane set to `value`.
+        ///
+        /// # Safety
+        ///
+        /// The CPU must support the target features of the type.
+        unsafe fn splat(value: i64) -> Self;
+
+        /// The lane indices times `step`: 0, step, 2 * step, ...
+        ///
+        /// # Safety
+        ///
+        /// The CPU must support the target features of the type.
+        unsafe fn lanes_times(step: i64) -> Self;
+
+        // Bit mask of the lanes greater than `rhs`. The comparison is done
+        // by the sign of `rhs - self`, so the difference must not overflow.
+        fn gt_mask(self, rhs: Self) -> u32;
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct F32x4(__m128);
+
+    #[derive(Clone, Copy)]
+    pub struct I64x4(__m128i, __m128i);
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    impl PackedF32 for F32x4 {
+        const LANES: usize = 4;
+
+        type I64 = I64x4;
+
+        #[inline(always)]
+        unsafe fn splat(value: f32) -> Self {
+            Self(_mm_set1_ps(value))
+        }
+
+        #[inline(always)]
+        unsafe fn lanes() -> Self {
+            Self(_mm_setr_ps(0.0, 1.0, 2.0, 3.0))
+        }
+
+        #[inline(always)]
+        unsafe fn load(values: &[f32]) -> Self {
+            assert!(values.len() >= Self::LANES);
+            Self(_mm_loadu_ps(values.as_ptr()))
+        }
+
+        #[inline(always)]
+        fn store(self, values: &mut [f32]) {
+            assert!(values.len() >= Self::LANES);
+            unsafe { _mm_storeu_ps(values.as_mut_ptr(), self.0) }
+        }
+
+        #[inline(always)]
+        fn lt_mask(self, rhs: Self) -> u32 {
+            unsafe { _mm_movemask_ps(_mm_cmplt_ps(self.0, rhs.0)) as u32 }
+        }
+
+        #[inline(always)]
+        fn le_mask(self, rhs: Self) -> u32 {
+            unsafe { _mm_movemask_ps(_mm_cmple_ps(self.0, rhs.0)) as u32 }
+        }
+    }
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    impl PackedI64 for I64x4 {
+        #[inline(always)]
+        unsafe fn splat(value: i64) -> Self {
+            Self(_mm_set1_epi64x(value), _mm_set1_epi64x(value))
+        }
+
+        #[inline(always)]
+        unsafe fn lanes_times(step: i64) -> Self {
+            Self(_mm_set_epi64x(step, 0), _mm_set_epi64x(3 * step, 2 * step))
+        }
+
+        #[inline(always)]
+        fn gt_mask(self, rhs: Self) -> u32 {
+            unsafe {
+                let low = _mm_movemask_pd(_mm_castsi128_pd(_mm_sub_epi64(rhs.0, self.0)));
+                let high = _mm_movemask_pd(_mm_castsi128_pd(_mm_sub_epi64(rhs.1, self.1)));
+
+                (low | (high << 2)) as u32
+            }
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct F32x8(__m256);
+
+    #[derive(Clone, Copy)]
+    pub struct I64x8(__m256i, __m256i);
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    impl PackedF32 for F32x8 {
+        const LANES: usize = 8;
+
+        type I64 = I64x8;
+
+        #[inline(always)]
+        unsafe fn splat(value: f32) -> Self {
+            Self(_mm256_set1_ps(value))
+        }
+
+        #[inline(always)]
+        unsafe fn lanes() -> Self {
+            Self(_mm256_setr_ps(0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0))
+        }
+
+        #[inline(always)]
+        unsafe fn load(values: &[f32]) -> Self {
+            assert!(values.len() >= Self::LANES);
+            Self(_mm256_loadu_ps(values.as_ptr()))
+        }
+
+        #[inline(always)]
+        fn store(self, values: &mut [f32]) {
+            assert!(values.len() >= Self::LANES);
+            unsafe { _mm256_storeu_ps(values.as_mut_ptr(), self.0) }
+        }
+
+        #[inline(always)]
+        fn lt_mask(self, rhs: Self) -> u32 {
+            unsafe { _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_LT_OQ>(self.0, rhs.0)) as u32 }
+        }
+
+        #[inline(always)]
+        fn le_mask(self, rhs: Self) -> u32 {
+            unsafe { _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_LE_OQ>(self.0, rhs.0)) as u32 }
+        }
+    }
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    impl PackedI64 for I64x8 {
+        #[inline(always)]
+        unsafe fn splat(value: i64) -> Self {
+            Self(_mm256_set1_epi64x(value), _mm256_set1_epi64x(value))
+        }
+
+        #[inline(always)]
+        unsafe fn lanes_times(step: i64) -> Self {
+            Self(
+                _mm256_setr_epi64x(0, step, 2 * step, 3 * step),
+                _mm256_setr_epi64x(4 * step, 5 * step, 6 * step, 7 * step),
+            )
+        }
+
+        #[inline(always)]
+        fn gt_mask(self, rhs: Self) -> u32 {
+            unsafe {
+                let low = _mm256_movemask_pd(_mm256_castsi256_pd(_mm256_sub_epi64(rhs.0, self.0)));
+                let high = _mm256_movemask_pd(_mm256_castsi256_pd(_mm256_sub_epi64(rhs.1, self.1)));
+
+                (low | (high << 4)) as u32
+            }
+        }
+    }
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    macro_rules! impl_packed_ops {
+        ($name:ident, $add:ident, $sub:ident, $mul:ident, $div:ident) => {
+            impl Add for $name {
+                type Output = Self;
+
+                #[inline(always)]
+                fn add(self, rhs: Self) -> Self {
+                    unsafe { Self($add(self.0, rhs.0)) }
+                }
+            }
+
+            impl Sub for $name {
+                type Output = Self;
+
+                #[inline(always)]
+                fn sub(self, rhs: Self) -> Self {
+                    unsafe { Self($sub(self.0, rhs.0)) }
+                }
+            }
+
+            impl Mul for $name {
+                type Output = Self;
+
+                #[inline(always)]
+                fn mul(self, rhs: Self) -> Self {
+                    unsafe { Self($mul(self.0, rhs.0)) }
+                }
+            }
+
+            impl Div for $name {
+                type Output = Self;
+
+                #[inline(always)]
+                fn div(self, rhs: Self) -> Self {
+                    unsafe { Self($div(self.0, rhs.0)) }
+                }
+            }
+        };
+    }
+
+    impl_packed_ops!(F32x4, _mm_add_ps, _mm_sub_ps, _mm_mul_ps, _mm_div_ps);
+    impl_packed_ops!(
+        F32x8,
+        _mm256_add_ps,
+        _mm256_sub_ps,
+        _mm256_mul_ps,
+        _mm256_div_ps
+    );
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    impl Add for I64x4 {
+        type Output = Self;
+
+        #[inline(always)]
+        fn add(self, rhs: Self) -> Self {
+            unsafe { Self(_mm_add_epi64(self.0, rhs.0), _mm_add_epi64(self.1, rhs.1)) }
+        }
+    }
+
+    // SAFETY (unsafe blocks): the value exists, so the CPU supports the
+    // target features of the type.
+    impl Add for I64x8 {
+        type Output = Self;
+
+        #[inline(always)]
+        fn add(self, rhs: Self) -> Self {
+            unsafe {
+                Self(
+                    _mm256_add_epi64(self.0, rhs.0),
+                    _mm256_add_epi64(self.1, rhs.1),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-4;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < TOLERANCE, "{} != {}", a, b);
+    }
+
+    fn assert_vec3_close(a: Vec3, b: Vec3) {
+        for i in 0..3 {
+            assert_close(a[i], b[i]);
+        }
+    }
+
+    fn assert_matrix_close(a: Matrix4, b: Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_close(a[i][j], b[i][j]);
+            }
+        }
+    }
+
+    fn sample_matrix() -> Matrix4 {
+        Matrix4::translation(Vec3::new([3., -2., 5.]))
+            * Quat::from_axis_angle(Vec3::new([1., 2., 3.]), 0.7).as_matrix()
+            * Matrix4::scale(Vec3::new([2., 0.5, -3.]))
+    }
+
+    #[test]
+    fn vector_operators() {
+        let mut a = Vec3::new([1., 2., 3.]);
+        a += Vec3::new([1., 1., 1.]);
+        assert_eq!(a, Vec3::new([2., 3., 4.]));
+        assert_eq!(-a, Vec3::new([-2., -3., -4.]));
+        a[1] = 7.;
+        assert_eq!(a[1], 7.);
+
+        let mut b = Vec2::new(1., 2.);
+        b += Vec2::new(3., 4.);
+        assert_eq!(-b, Vec2::new(-4., -6.));
+        assert_eq!(b[0], 4.);
+
+        let mut c = Vec4::new([1., 2., 3., 4.]);
+        c += Vec4::new([1., 1., 1., 1.]);
+        assert_eq!(c - Vec4::new([2., 2., 2., 2.]), Vec4::new([0., 1., 2., 3.]));
+        assert_eq!(c / 2., Vec4::new([1., 1.5, 2., 2.5]));
+        assert_eq!(-c, Vec4::new([-2., -3., -4., -5.]));
+        c[3] = 0.;
+        assert_eq!(c[3], 0.);
+    }
+
+    #[test]
+    fn determinant_and_inverse() {
+        assert_close(Matrix4::identity().determinant(), 1.);
+        assert_close(Matrix4::scale(Vec3::new([2., 3., 4.])).determinant(), 24.);
+        assert_close(
+            Matrix4::translation(Vec3::new([1., 2., 3.])).determinant(),
+            1.,
+        );
+
+        let m = sample_matrix();
+        assert_close(m.determinant(), -3.);
+        assert_matrix_close(m * m.inverse().unwrap(), Matrix4::identity());
+        assert_matrix_close(m.inverse().unwrap() * m, Matrix4::identity());
+
+        assert!(Matrix4::scale(Vec3::new([1., 0., 1.])).inverse().is_none());
+    }
+
+    #[test]
+    fn inverse_of_small_scale() {
+        // det = 0.004³, abaixo de EPS, mas a matriz é bem condicionada.
+        let m = Matrix4::scale(Vec3::new([0.004; 3])) * sample_matrix();
+        assert_matrix_close(m.inverse().unwrap() * m, Matrix4::identity());
+        assert_matrix_close(
+            Matrix4::scale(Vec3::new([0.004; 3])).inverse().unwrap(),
+            Matrix4::scale(Vec3::new([250.; 3])),
+        );
+
+        let m3 = m.as_mat3();
+        let product = m3.inverse().unwrap() * m3;
+        let identity = Mat3::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_close(product[i][j], identity[i][j]);
+            }
+        }
+
+        let flat = Matrix4::scale(Vec3::new([0.004, 0., 0.004]));
+        assert!(flat.inverse().is_none());
+        assert!(flat.as_mat3().inverse().is_none());
+    }
+
+    #[test]
+    fn inverse_of_projective_and_mirrored_matrices() {
+        // A última linha da perspectiva não é (0, 0, 0, 1).
+        let projection = Matrix4::perspective(-2., 2., -1., 1., -1., -10.);
+        assert_matrix_close(
+            projection.inverse().unwrap() * projection,
+            Matrix4::identity(),
+        );
+
+        let mirrored = Matrix4::scale(Vec3::new([1., -1., 1.])) * sample_matrix();
+        let inverse = mirrored.inverse().unwrap();
+        assert_matrix_close(inverse * mirrored, Matrix4::identity());
+        assert_close(inverse.determinant(), 1. / mirrored.determinant());
+
+        let p = Vec3::new([0.7, -1.5, 4.]);
+        assert_vec3_close((inverse * (mirrored * p.as_vec4())).as_vec3(), p);
+    }
+
+    #[test]
+    fn translation_and_scale() {
+        let p = Vec3::new([1., 2., 3.]).as_vec4();
+
+        let translated = Matrix4::translation(Vec3::new([1., -1., 2.])) * p;
+        assert_vec3_close(translated.as_vec3(), Vec3::new([2., 1., 5.]));
+
+        let scaled = Matrix4::scale(Vec3::new([2., 3., -1.])) * p;
+        assert_vec3_close(scaled.as_vec3(), Vec3::new([2., 6., -3.]));
+
+        // Direções (w = 0) não são transladadas.
+        let direction = Matrix4::translation(Vec3::new([1., 1., 1.])) * Vec4::new([0., 0., 1., 0.]);
+        assert_vec3_close(direction.as_vec3(), Vec3::new([0., 0., 1.]));
+    }
+
+    #[test]
+    fn mat3() {
+        let m = sample_matrix().as_mat3();
+        assert_close(m.determinant(), sample_matrix().determinant());
+
+        let product = m * m.inverse().unwrap();
+        let identity = Mat3::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_close(product[i][j], identity[i][j]);
+            }
+        }
+
+        assert_eq!(m.transposed().transposed(), m);
+        assert_eq!(m.as_matrix4().as_mat3(), m);
+    }
+
+    #[test]
+    fn normal_matrix() {
+        // A normal de um plano continua perpendicular a ele depois de uma
+        // escala não uniforme, e não é invertida pelo espelhamento.
+        let m = sample_matrix();
+        let tangent = Vec3::new([1., -1., 0.]);
+        let normal = Vec3::new([1., 1., 0.]);
+
+        let direction = |v: Vec3| (m * Vec4::new([v.x(), v.y(), v.z(), 0.])).as_vec3();
+        let world_normal = m.normal_matrix() * normal;
+
+        assert_close(direction(tangent).dot(world_normal), 0.);
+        assert!(direction(normal).dot(world_normal) > 0.0);
+    }
+
+    #[test]
+    fn normal_matrix_under_non_uniform_scale() {
+        // O plano x + y = 0 esticado em y: a normal (1, 1, 0) passa a ser
+        // perpendicular à tangente escalada (1, -4, 0).
+        let m = Matrix4::scale(Vec3::new([1., 4., 1.]));
+        let world_normal = (m.normal_matrix() * Vec3::new([1., 1., 0.])).normalized();
+
+        assert_vec3_close(world_normal, Vec3::new([4., 1., 0.]).normalized());
+    }
+
+    #[test]
+    fn mirrored_determinant() {
+        let mirror = Matrix4::scale(Vec3::new([-1., 1., 1.]));
+        let rotation = Quat::from_axis_angle(Vec3::new([0., 1., 1.]), 1.1).as_matrix();
+
+        assert_close(mirror.determinant(), -1.);
+        assert_close(rotation.determinant(), 1.);
+        assert_close((rotation * mirror).determinant(), -1.);
+        assert_close((mirror * mirror).determinant(), 1.);
+
+        // A normal espelhada acompanha a superfície, sem ser invertida.
+        let world_normal = mirror.normal_matrix() * Vec3::new([1., 0., 0.]);
+        assert_vec3_close(world_normal, Vec3::new([-1., 0., 0.]));
+    }
+
+    #[test]
+    fn quaternion_composition() {
+        let a = Quat::from_axis_angle(Vec3::new([1., 0., 1.]), 0.4);
+        let b = Quat::from_axis_angle(Vec3::new([0., 2., 1.]), -1.3);
+        let v = Vec3::new([0.5, 1., -2.]);
+
+        assert_vec3_close((a * b).rotate(v), a.rotate(b.rotate(v)));
+        assert_matrix_close((a * b).as_matrix(), a.as_matrix() * b.as_matrix());
+        assert_vec3_close(Quat::identity().rotate(v), v);
+        assert_close(
+            Quat::new(2., 0., 0., 0.).normalized().dot(Quat::identity()),
+            1.,
+        );
+
+        // Os ângulos de Euler são aplicados em x, depois y e depois z.
+        let angles = Vec3::new([0.3, -0.4, 1.2]);
+        assert_matrix_close(
+            Quat::from_euler(angles).as_matrix(),
+            Matrix4::rotate_z(angles.z())
+                * Matrix4::rotate_y(angles.y())
+                * Matrix4::rotate_x(angles.x()),
+        );
+    }
+
+    #[test]
+    fn look_at() {
+        let eye = Vec3::new([1., 2., 3.]);
+        let target = Vec3::new([4., 2., -1.]);
+        let view = Matrix4::look_at(eye, target, Vec3::new([0., 1., 0.]));
+
+        assert_vec3_close((view * eye.as_vec4()).as_vec3(), Vec3::zeros());
+
+        // O alvo fica no eixo -z da câmera, à sua distância do olho.
+        let target_view = (view * target.as_vec4()).as_vec3();
+        assert_vec3_close(target_view, Vec3::new([0., 0., -5.]));
+
+        // Um ponto acima do olho fica acima na câmera.
+        let above = (view * (eye + Vec3::new([0., 1., 0.])).as_vec4()).as_vec3();
+        assert_close(above.x(), 0.);
+        assert!(above.y() > 0.0);
+    }
+
+    #[test]
+    fn orthographic() {
+        let m = Matrix4::orthographic(-2., 2., -1., 1., -1., -10.);
+
+        let near_corner = m * Vec3::new([2., 1., -1.]).as_vec4();
+        assert_vec3_close(near_corner.as_vec3(), Vec3::new([1., 1., 1.]));
+
+        let far_corner = m * Vec3::new([-2., -1., -10.]).as_vec4();
+        assert_vec3_close(far_corner.as_vec3(), Vec3::new([-1., -1., -1.]));
+    }
+
+    #[test]
+    fn perspective() {
+        let (near, far) = (-1., -10.);
+        let m = Matrix4::perspective(-2., 2., -1., 1., near, far);
+
+        // Os cantos da janela no plano near vão para os cantos do cubo.
+        let near_corner = m * Vec3::new([2., 1., near]).as_vec4();
+        assert!(near_corner.get_w() > 0.0);
+        assert_vec3_close(near_corner.vec3_over_w(), Vec3::new([1., 1., 1.]));
+
+        // No plano far a janela é proporcionalmente maior.
+        let far_corner = m * Vec3::new([-20., -10., far]).as_vec4();
+        assert!(far_corner.get_w() > 0.0);
+        assert_vec3_close(far_corner.vec3_over_w(), Vec3::new([-1., -1., -1.]));
+
+        let center = m * Vec3::new([0., 0., -4.]).as_vec4();
+        let ndc = center.vec3_over_w();
+        assert!(ndc.z() > -1.0 && ndc.z() < 1.0);
+    }
+
+    #[test]
+    fn quaternion_rotation() {
+        let q = Quat::from_axis_angle(Vec3::new([0., 0., 1.]), std::f32::consts::FRAC_PI_2);
+        assert_vec3_close(q.rotate(Vec3::new([1., 0., 0.])), Vec3::new([0., 1., 0.]));
+
+        let v = Vec3::new([0.3, -1.2, 2.]);
+        let q = Quat::from_axis_angle(Vec3::new([1., 2., 3.]), 0.7);
+        assert_vec3_close((q.as_matrix() * v.as_vec4()).as_vec3(), q.rotate(v));
+        assert_vec3_close(q.conjugate().rotate(q.rotate(v)), v);
+
+        let angles = Vec3::new([0.3, -0.4, 1.2]);
+        assert_vec3_close(Quat::from_euler(angles).to_euler(), angles);
+    }
+
+    #[test]
+    fn quaternion_slerp() {
+        let axis = Vec3::new([0., 1., 0.]);
+        let a = Quat::from_axis_angle(axis, 0.2);
+        let b = Quat::from_axis_angle(axis, 1.4);
+
+        let start = a.slerp(b, 0.0);
+        let end = a.slerp(b, 1.0);
+        assert_close(start.dot(a), 1.);
+        assert_close(end.dot(b), 1.);
+
+        // Velocidade angular constante ao longo do arco.
+        let quarter = a.slerp(b, 0.25);
+        assert_close(quarter.dot(Quat::from_axis_angle(axis, 0.5)), 1.);
+
+        // -b é a mesma rotação que b: o caminho continua sendo o mais curto.
+        let middle = a.slerp(-b, 0.5);
+        assert_close(middle.dot(Quat::from_axis_angle(axis, 0.8)).abs(), 1.);
+
+        // Rotações quase iguais.
+        let close = a.slerp(Quat::from_axis_angle(axis, 0.2001), 0.5);
+        assert_close(close.norm(), 1.);
+    }
+}
@@ -1,3 +1,11 @@
 pub mod canvas;
+pub mod environment;
+pub mod light;
 pub mod linalg;
 pub mod scene;
+pub mod shader;
+pub mod shadow;
+pub mod srgb;
+
+#[cfg(test)]
+mod test_utils;
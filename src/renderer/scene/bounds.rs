@@ -0,0 +1,293 @@
+use crate::renderer::linalg::{Matrix4, Vec3, Vec4};
+
+// Caixa alinhada aos eixos.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    // Menor caixa que contém os pontos. Sem pontos, a caixa é a origem.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Self {
+                min: Vec3::zeros(),
+                max: Vec3::zeros(),
+            };
+        };
+
+        points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: Vec3::new([
+                    aabb.min.x().min(p.x()),
+                    aabb.min.y().min(p.y()),
+                    aabb.min.z().min(p.z()),
+                ]),
+                max: Vec3::new([
+                    aabb.max.x().max(p.x()),
+                    aabb.max.y().max(p.y()),
+                    aabb.max.z().max(p.z()),
+                ]),
+            },
+        )
+    }
+
+    // Menor caixa que contém as duas.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_points([self.min, self.max, other.min, other.max])
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    // Metade do tamanho da caixa em cada eixo.
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) / 2.0
+    }
+
+    // Caixa alinhada aos eixos que contém a caixa transformada pela matriz
+    // afim `matrix`: cada meia-aresta transformada contribui com o valor
+    // absoluto das suas coordenadas (Arvo, Graphics Gems, 1990).
+    pub fn transformed(&self, matrix: &Matrix4) -> Self {
+        let center = (*matrix * self.center().as_vec4()).as_vec3();
+        let half = self.half_extents();
+
+        let extents = Vec3::new(std::array::from_fn(|i| {
+            (0..3).map(|j| matrix[i][j].abs() * half[j]).sum()
+        }));
+
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // Esfera centrada na caixa dos pontos, com o raio até o ponto mais
+    // distante do centro, menor que a esfera circunscrita à caixa.
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points.iter().map(|p| p.dist(center)).fold(0.0, f32::max);
+
+        Self { center, radius }
+    }
+
+    // Esfera que contém a esfera transformada pela matriz afim `matrix`. O
+    // raio é multiplicado pela maior escala dos eixos.
+    pub fn transformed(&self, matrix: &Matrix4) -> Self {
+        let center = (*matrix * self.center.as_vec4()).as_vec3();
+        let scale = (0..3)
+            .map(|j| Vec3::new([matrix[0][j], matrix[1][j], matrix[2][j]]).norm())
+            .fold(0.0, f32::max);
+
+        Self {
+            center,
+            radius: self.radius * scale,
+        }
+    }
+}
+
+// Os seis planos do volume de visão, em coordenadas de mundo.
+pub struct Frustum {
+    // Normal, apontando para dentro do volume, e distância de cada plano:
+    // os pontos p de dentro satisfazem normal . p + distance >= 0.
+    planes: [(Vec3, f32); 6],
+}
+
+impl Frustum {
+    // Extrai os planos da matriz que leva as coordenadas de mundo para as de
+    // clip, em que o volume é -w <= x, y, z <= w: cada plano é a soma ou a
+    // diferença da linha de w com a de x, y ou z (Gribb e Hartmann, 2001).
+    pub fn from_matrix(matrix_clip: &Matrix4) -> Self {
+        let row = |i: usize| Vec4::new(matrix_clip[i]);
+        let w = row(3);
+
+        let planes = [
+            w + row(0),
+            w - row(0),
+            w + row(1),
+            w - row(1),
+            w + row(2),
+            w - row(2),
+        ]
+        .map(|plane| {
+            let normal = plane.as_vec3();
+            let norm = normal.norm();
+
+            (normal / norm, plane[3] / norm)
+        });
+
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|(normal, distance)| normal.dot(sphere.center) + distance >= -sphere.radius)
+    }
+
+    // Testa, em cada plano, o canto da caixa mais adentro do volume. A caixa
+    // pode ser aceita mesmo estando fora, perto das arestas do frustum.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let half = aabb.half_extents();
+
+        self.planes.iter().all(|(normal, distance)| {
+            let reach: f32 = (0..3).map(|i| normal[i].abs() * half[i]).sum();
+            normal.dot(center) + distance >= -reach
+        })
+    }
+
+    // Whether the volumes, given in object space, of something placed in
+    // the world by `matrix` may be inside the frustum.
+    pub fn intersects_bounds(
+        &self,
+        matrix: &Matrix4,
+        sphere: &BoundingSphere,
+        aabb: &Aabb,
+    ) -> bool {
+        self.intersects_sphere(&sphere.transformed(matrix))
+            && self.intersects_aabb(&aabb.transformed(matrix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::linalg::Quat;
+
+    const TOLERANCE: f32 = 1e-4;
+
+    fn v(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3::new([x, y, z])
+    }
+
+    fn assert_vec3_close(a: Vec3, b: Vec3) {
+        assert!((a - b).norm() < TOLERANCE, "{:?} != {:?}", a, b);
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::from_points([v(-1., -1., -1.), v(1., 1., 1.)])
+    }
+
+    // Câmera na origem olhando para -z, com o plano near em z = -1 e o far
+    // em z = -10.
+    fn frustum() -> Frustum {
+        Frustum::from_matrix(&Matrix4::perspective(-1., 1., -1., 1., -1., -10.))
+    }
+
+    #[test]
+    fn aabb_transformed() {
+        let moved = unit_box()
+            .transformed(&(Matrix4::translation(v(1., 2., 3.)) * Matrix4::scale(v(2., 1., 0.5))));
+        assert_vec3_close(moved.min, v(-1., 1., 2.5));
+        assert_vec3_close(moved.max, v(3., 3., 3.5));
+
+        // Girada 45° em torno de z, a caixa envolve os cantos girados.
+        let rotation = Quat::from_axis_angle(v(0., 0., 1.), std::f32::consts::FRAC_PI_4);
+        let rotated = unit_box().transformed(&rotation.as_matrix());
+        let diagonal = 2f32.sqrt();
+        assert_vec3_close(rotated.min, v(-diagonal, -diagonal, -1.));
+        assert_vec3_close(rotated.max, v(diagonal, diagonal, 1.));
+
+        // O espelhamento não troca o mínimo e o máximo.
+        let aabb = Aabb::from_points([v(1., 0., 0.), v(2., 1., 1.)]);
+        let mirrored = aabb.transformed(&Matrix4::scale(v(-1., 1., 1.)));
+        assert_vec3_close(mirrored.min, v(-2., 0., 0.));
+        assert_vec3_close(mirrored.max, v(-1., 1., 1.));
+    }
+
+    #[test]
+    fn bounding_sphere_transformed() {
+        let sphere = BoundingSphere {
+            center: v(1., 0., 0.),
+            radius: 2.,
+        };
+
+        let matrix = Matrix4::translation(v(0., 5., 0.)) * Matrix4::scale(v(1., 3., -2.));
+        let transformed = sphere.transformed(&matrix);
+        assert_vec3_close(transformed.center, v(1., 5., 0.));
+        assert!((transformed.radius - 6.).abs() < TOLERANCE);
+
+        let rotation = Quat::from_axis_angle(v(1., 1., 0.), 0.9).as_matrix();
+        assert!((sphere.transformed(&rotation).radius - 2.).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn frustum_culls_boxes_behind_the_camera() {
+        let frustum = frustum();
+        let sphere = |aabb: &Aabb| BoundingSphere {
+            center: aabb.center(),
+            radius: aabb.half_extents().norm(),
+        };
+        let visible = |aabb: Aabb| {
+            frustum.intersects_aabb(&aabb)
+                && frustum.intersects_sphere(&sphere(&aabb))
+                && frustum.intersects_bounds(&Matrix4::identity(), &sphere(&aabb), &aabb)
+        };
+
+        assert!(visible(Aabb::from_points([
+            v(-0.5, -0.5, -5.),
+            v(0.5, 0.5, -4.)
+        ])));
+
+        // Atrás da câmera, na frente e depois do far.
+        assert!(!visible(Aabb::from_points([
+            v(-0.5, -0.5, 2.),
+            v(0.5, 0.5, 3.)
+        ])));
+        assert!(!visible(Aabb::from_points([
+            v(-0.1, -0.1, -0.9),
+            v(0.1, 0.1, -0.5)
+        ])));
+        assert!(!visible(Aabb::from_points([
+            v(-0.5, -0.5, -12.),
+            v(0.5, 0.5, -11.)
+        ])));
+
+        // Cruzando o plano near e ao lado do volume.
+        assert!(visible(Aabb::from_points([
+            v(-0.1, -0.1, -1.5),
+            v(0.1, 0.1, 0.5)
+        ])));
+        assert!(!visible(Aabb::from_points([
+            v(6., -0.5, -5.),
+            v(7., 0.5, -4.)
+        ])));
+    }
+
+    #[test]
+    fn frustum_with_mirrored_world_matrix() {
+        let frustum = frustum();
+
+        // Caixa do lado +x do objeto. A rotação leva o +x para +z, atrás da
+        // câmera; com o espelhamento antes dela, a caixa fica em -z.
+        let aabb = Aabb::from_points([v(5., -0.5, -0.5), v(6., 0.5, 0.5)]);
+        let sphere = BoundingSphere {
+            center: aabb.center(),
+            radius: aabb.half_extents().norm(),
+        };
+
+        let rotation = Quat::from_axis_angle(v(0., 1., 0.), -std::f32::consts::FRAC_PI_2);
+        let plain = rotation.as_matrix();
+        let mirrored = plain * Matrix4::scale(v(-1., 1., 1.));
+
+        assert!(mirrored.determinant() < 0.0);
+        assert!(frustum.intersects_bounds(&mirrored, &sphere, &aabb));
+        assert!(!frustum.intersects_bounds(&plain, &sphere, &aabb));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::renderer::linalg::{Vec3, Vec4};
+use crate::renderer::shader::Varyings;
+
+// Clipagem de triângulos em coordenadas de clip (homogêneas), antes da
+// desomogeneização, pelo algoritmo de Sutherland–Hodgman.
+//
+// Um ponto (x, y, z, w) está dentro do volume de visão quando
+// -w <= x <= w, -w <= y <= w e -w <= z <= w. Como as coordenadas de clip
+// são uma transformação linear das coordenadas de mundo, os atributos
+// dos vértices criados na clipagem podem ser interpolados linearmente.
+
+// Cada plano gera no máximo um vértice novo: 3 + 6.
+const MAX_CLIPPED_VERTICES: usize = 9;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClipVertex<V> {
+    pub clip_coord: Vec4,
+    // Position in world coordinates, used to compute the depth.
+    pub position: Vec3,
+    pub varyings: V,
+}
+
+impl<V: Varyings> ClipVertex<V> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            clip_coord: self.clip_coord * (1.0 - t) + other.clip_coord * t,
+            position: self.position * (1.0 - t) + other.position * t,
+            varyings: self.varyings * (1.0 - t) + other.varyings * t,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ClipPlane {
+    Near,
+    Far,
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl ClipPlane {
+    const ALL: [ClipPlane; 6] = [
+        ClipPlane::Near,
+        ClipPlane::Far,
+        ClipPlane::Left,
+        ClipPlane::Right,
+        ClipPlane::Bottom,
+        ClipPlane::Top,
+    ];
+
+    // Signed distance of the point to the plane, positive inside. The lateral
+    // planes are moved away by the `guard_band` factor.
+    fn distance(self, p: Vec4, guard_band: f32) -> f32 {
+        let w = p.get_w();
+
+        match self {
+            ClipPlane::Near => w - p.get_z(),
+            ClipPlane::Far => w + p.get_z(),
+            ClipPlane::Left => guard_band * w + p.x(),
+            ClipPlane::Right => guard_band * w - p.x(),
+            ClipPlane::Bottom => guard_band * w + p.get_y(),
+            ClipPlane::Top => guard_band * w - p.get_y(),
+        }
+    }
+
+    // Bit mask of the planes that have the point outside.
+    fn outcode(p: Vec4, guard_band: f32) -> u8 {
+        Self::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, plane)| plane.distance(p, guard_band) < 0.0)
+            .fold(0, |code, (i, _)| code | (1 << i))
+    }
+}
+
+// Polígono convexo resultante da clipagem de um triângulo.
+pub struct ClippedPolygon<V> {
+    vertices: [ClipVertex<V>; MAX_CLIPPED_VERTICES],
+    len: usize,
+}
+
+impl<V: Varyings> ClippedPolygon<V> {
+    // Triangulação em leque do polígono.
+    pub fn triangles(&self) -> impl Iterator<Item = [ClipVertex<V>; 3]> + '_ {
+        (1..self.len.saturating_sub(1))
+            .map(|i| [self.vertices[0], self.vertices[i], self.vertices[i + 1]])
+    }
+
+    // Sutherland–Hodgman step: keeps the part of the polygon inside the plane.
+    fn clip(&self, plane: ClipPlane, guard_band: f32, out: &mut Self) {
+        out.len = 0;
+
+        for i in 0..self.len {
+            let current = &self.vertices[i];
+            let next = &self.vertices[(i + 1) % self.len];
+
+            let d_current = plane.distance(current.clip_coord, guard_band);
+            let d_next = plane.distance(next.clip_coord, guard_band);
+
+            if d_current >= 0.0 {
+                out.vertices[out.len] = *current;
+                out.len += 1;
+            }
+
+            // The edge crosses the plane.
+            if (d_current >= 0.0) != (d_next >= 0.0) {
+                let t = d_current / (d_current - d_next);
+
+                out.vertices[out.len] = current.lerp(next, t);
+                out.len += 1;
+            }
+        }
+    }
+}
+
+// Clips the triangle against the view volume. With `guard_band` greater than
+// 1, the triangles that cross only the lateral planes inside the guard band
+// are kept whole and left to the raster, which is cheaper than clipping them.
+// The triangles completely outside of the view volume are always discarded.
+pub fn clip_triangle<V: Varyings>(
+    triangle: [ClipVertex<V>; 3],
+    guard_band: f32,
+) -> ClippedPolygon<V> {
+    let mut polygon = ClippedPolygon {
+        vertices: [triangle[0]; MAX_CLIPPED_VERTICES],
+        len: 0,
+    };
+
+    let frustum_codes = triangle.map(|v| ClipPlane::outcode(v.clip_coord, 1.0));
+    if frustum_codes[0] & frustum_codes[1] & frustum_codes[2] != 0 {
+        // Todos os vértices fora de um mesmo plano.
+        return polygon;
+    }
+
+    polygon.vertices[..3].copy_from_slice(&triangle);
+    polygon.len = 3;
+
+    let codes = triangle.map(|v| ClipPlane::outcode(v.clip_coord, guard_band));
+    let crossed_planes = codes[0] | codes[1] | codes[2];
+    if crossed_planes == 0 {
+        return polygon;
+    }
+
+    let mut clipped = ClippedPolygon {
+        vertices: polygon.vertices,
+        len: 0,
+    };
+
+    for (i, plane) in ClipPlane::ALL.iter().enumerate() {
+        if crossed_planes & (1 << i) == 0 {
+            continue;
+        }
+
+        polygon.clip(*plane, guard_band, &mut clipped);
+        std::mem::swap(&mut polygon, &mut clipped);
+
+        if polygon.len < 3 {
+            polygon.len = 0;
+            break;
+        }
+    }
+
+    polygon
+}
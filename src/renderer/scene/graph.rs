@@ -0,0 +1,165 @@
+use crate::renderer::linalg::{Matrix4, Quat, Vec3};
+
+// Índice de um nó em `SceneGraph`.
+pub type NodeId = usize;
+
+// Transformação de um nó em relação ao seu pai: a escala é aplicada
+// primeiro, depois a rotação e por fim a translação.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            translation: Vec3::zeros(),
+            rotation: Quat::identity(),
+            scale: Vec3::new([1.0, 1.0, 1.0]),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::translation(self.translation)
+            * self.rotation.normalized().as_matrix()
+            * Matrix4::scale(self.scale)
+    }
+}
+
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl SceneNode {
+    // Only the root has no parent.
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+// Hierarquia de transformações da cena. A matriz de mundo de um nó é a
+// composição das transformações locais desde a raiz até ele.
+pub struct SceneGraph {
+    nodes: Vec<SceneNode>,
+}
+
+impl SceneGraph {
+    pub const ROOT: NodeId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![SceneNode {
+                name: String::from("Scene"),
+                transform: Transform::identity(),
+                parent: None,
+                children: vec![],
+            }],
+        }
+    }
+
+    // Panics if `parent` is not a node of the graph.
+    pub fn add_node(&mut self, name: String, parent: NodeId, transform: Transform) -> NodeId {
+        assert!(parent < self.nodes.len());
+
+        let id = self.nodes.len();
+        self.nodes.push(SceneNode {
+            name,
+            transform,
+            parent: Some(parent),
+            children: vec![],
+        });
+        self.nodes[parent].children.push(id);
+
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&SceneNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut SceneNode> {
+        self.nodes.get_mut(id)
+    }
+
+    // Whether `node` is `ancestor` or is in its subtree.
+    pub fn is_descendant(&self, node: NodeId, ancestor: NodeId) -> bool {
+        let mut current = Some(node);
+
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.nodes.get(id).and_then(|node| node.parent);
+        }
+
+        false
+    }
+
+    // Moves the node, with its subtree, to the children of `parent`. The
+    // local transform is kept, so the node follows the new parent. Returns
+    // false when the move would create a cycle or some node is invalid.
+    pub fn set_parent(&mut self, id: NodeId, parent: NodeId) -> bool {
+        if id == Self::ROOT
+            || id >= self.nodes.len()
+            || parent >= self.nodes.len()
+            || self.is_descendant(parent, id)
+        {
+            return false;
+        }
+
+        if let Some(old_parent) = self.nodes[id].parent {
+            self.nodes[old_parent].children.retain(|child| *child != id);
+        }
+
+        self.nodes[id].parent = Some(parent);
+        self.nodes[parent].children.push(id);
+
+        true
+    }
+
+    // Composition of the local transforms from the root to the node.
+    pub fn world_matrix(&self, id: NodeId) -> Matrix4 {
+        let mut matrix = Matrix4::identity();
+        let mut current = self.nodes.get(id);
+
+        while let Some(node) = current {
+            matrix = node.transform.matrix() * matrix;
+            current = node.parent.and_then(|parent| self.nodes.get(parent));
+        }
+
+        matrix
+    }
+
+    // Matriz de mundo de cada nó, indexada pelo seu id.
+    pub fn world_matrices(&self) -> Vec<Matrix4> {
+        let mut matrices = vec![Matrix4::identity(); self.nodes.len()];
+
+        // Os pais são visitados antes dos filhos.
+        let mut stack = vec![(Self::ROOT, Matrix4::identity())];
+
+        while let Some((id, parent_matrix)) = stack.pop() {
+            let node = &self.nodes[id];
+            let matrix = parent_matrix * node.transform.matrix();
+
+            matrices[id] = matrix;
+            stack.extend(node.children.iter().map(|child| (*child, matrix)));
+        }
+
+        matrices
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
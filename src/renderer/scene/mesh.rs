@@ -1,9 +1,16 @@
 use crate::renderer::linalg::Vec3;
+use crate::renderer::scene::bounds::{Aabb, BoundingSphere};
+use crate::renderer::scene::MaterialModel;
+use crate::renderer::scene::TextureFilter;
 
 pub struct MeshInfo {
     pub name: String,
     pub triangle_count: u32,
     pub texture_name: Option<String>,
+    pub has_normal_map: bool,
+    pub normal_mapping: bool,
+    pub material_model: MaterialModel,
+    pub texture_filter: TextureFilter,
 }
 
 pub trait IndexedTriangleNormal {
@@ -31,14 +38,43 @@ impl IndexedTriangleNormal for IndexedTriangle {
 pub struct IndexedMesh {
     pub name: String,
     pub triangles: Vec<(IndexedTriangle, IndexedTriangle, IndexedTriangle)>,
+    // Índices de `triangles` na ordem em que são desenhados. O painter's
+    // algorithm ordena esses índices, e não os triângulos, que continuam
+    // identificados pelo seu índice na BVH, no ID buffer e nos PickHit.
+    pub draw_order: Vec<usize>,
     pub texture_idx: Option<usize>,
+    // Whether the normal map of the texture is applied.
+    pub normal_mapping: bool,
+    // Volumes que envolvem os vértices da mesh, em coordenadas de objeto.
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
 }
 
 impl IndexedMesh {
-    pub fn vec3_list_from_indexed(
-        indexed_tri: IndexedTriangle,
-        vert_list: &Vec<Vec3>,
-    ) -> [Vec3; 3] {
+    // `vertices` are the vertices of the object indexed by the triangles.
+    pub fn new(
+        name: String,
+        triangles: Vec<(IndexedTriangle, IndexedTriangle, IndexedTriangle)>,
+        texture_idx: Option<usize>,
+        vertices: &[Vec3],
+    ) -> Self {
+        let mesh_vertices: Vec<Vec3> = triangles
+            .iter()
+            .flat_map(|(vertex_tri, _, _)| vertex_tri.map(|idx| vertices[idx]))
+            .collect();
+
+        Self {
+            name,
+            draw_order: (0..triangles.len()).collect(),
+            triangles,
+            texture_idx,
+            normal_mapping: true,
+            bounds: Aabb::from_points(mesh_vertices.iter().copied()),
+            bounding_sphere: BoundingSphere::from_points(&mesh_vertices),
+        }
+    }
+
+    pub fn vec3_list_from_indexed(indexed_tri: IndexedTriangle, vert_list: &[Vec3]) -> [Vec3; 3] {
         let a_idx = indexed_tri[0];
         let b_idx = indexed_tri[1];
         let c_idx = indexed_tri[2];
@@ -1,32 +1,49 @@
+mod bounds;
+mod clip;
+mod graph;
 mod mesh;
+mod mtl;
 mod object;
+mod raycast;
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use bounds::{Aabb, BoundingSphere, Frustum};
+use clip::ClipVertex;
+pub use graph::{NodeId, SceneGraph, SceneNode, Transform};
 use mesh::IndexedMesh;
 pub use object::Object;
 pub use object::ObjectInfo;
+pub use raycast::{PickHit, Ray, SurfaceInfo};
 
-use super::canvas::{Canvas, Color, VertexAttributes};
+use super::canvas::{Canvas, Color, DepthTest, PrimitiveId, ProjectedTriangle, ProjectedVertex};
+use super::shader::{
+    FlatShader, FragmentShader, NormalShader, PhongShader, Uniforms, UnlitShader, Varyings,
+    VertexInput, VertexOutput, VertexShader,
+};
 
-use super::linalg::{Matrix4, Vec2, Vec3, EPS};
+use super::environment::{Background, EnvironmentLight};
+use super::light::{Light, LightKind};
+use super::linalg::{Matrix4, Vec2, Vec3, Vec4, EPS};
+use super::shadow::{ShadowFace, ShadowMap, CUBE_FACES};
+use super::srgb;
 
+// Triângulo em coordenadas de mundo com as varyings de cada vértice.
 #[derive(Clone, Copy, Debug)]
-struct Triangle {
+struct Triangle<V> {
     vertices: [Vec3; 3],
-    vertices_attr: [VertexAttributes; 3],
+    vertices_attr: [V; 3],
 }
 
-impl Triangle {
-    pub fn new(vertices: [Vec3; 3], vertices_attr: [VertexAttributes; 3]) -> Self {
+impl<V: Varyings> Triangle<V> {
+    pub fn new(vertices: [Vec3; 3], vertices_attr: [V; 3]) -> Self {
         Self {
             vertices,
             vertices_attr,
         }
     }
 
-    fn zeroed() -> Self {
-        unsafe { std::mem::MaybeUninit::<Triangle>::zeroed().assume_init() }
-    }
-
     pub fn calc_normal(tri: &Self) -> Vec3 {
         let a = tri.vertices[0];
         let b = tri.vertices[1];
@@ -40,65 +57,50 @@ impl Triangle {
         normal
     }
 
-    pub fn clip_against_planes(
-        &self,
-        view_planes: &([ViewPlane; 2], [ViewPlane; 4]),
-        tri_pool_ret: &mut [Triangle],
-    ) -> usize {
-        let depth_planes = &view_planes.0;
-        let lateral_planes = &view_planes.1;
-
-        let mut tri_pool_size: usize = 0;
-
-        // Nessa etapa do pipeline, a verificação de clipagem nos
-        // planos laterais (right, left, top, bottom) vai impedir
-        // apenas aqueles triângulos que estiverem *completamente*
-        // fora do volume de visualização. A clipagem dos triângulos
-        // que estão parcialmente fora não é feita aqui pois é resolvido
-        // durante a rasterização.
-        if lateral_planes[0].at_least_partially_visible(&self)
-            && lateral_planes[1].at_least_partially_visible(&self)
-            && lateral_planes[2].at_least_partially_visible(&self)
-            && lateral_planes[3].at_least_partially_visible(&self)
-        {
-            tri_pool_ret[0] = self.clone();
-            tri_pool_size = 1;
-        }
-
-        // TODO: substituir por heapless::Vec
-        let mut new_tri_pool: [Triangle; 12] =
-            unsafe { std::mem::MaybeUninit::<[Triangle; 12]>::zeroed().assume_init() };
-
-        let mut tri_pool_ref: &mut [Triangle] = tri_pool_ret;
-        let mut new_pool_ref: &mut [Triangle] = new_tri_pool.as_mut();
-
-        // the 'far' and 'near' planes will apply the complete clipping method in the triangles.
-        for plane in depth_planes.iter() {
-            let mut new_tri_pool_size: usize = 0;
-
-            for tri in tri_pool_ref[0..tri_pool_size].iter() {
-                let clipped_count = plane.clip(tri, new_pool_ref[new_tri_pool_size..].as_mut());
-
-                new_tri_pool_size += clipped_count;
-            }
-
-            std::mem::swap(&mut tri_pool_ref, &mut new_pool_ref);
-            std::mem::swap(&mut tri_pool_size, &mut new_tri_pool_size);
-        }
-
-        return tri_pool_size;
-    }
-
     pub fn get_center(&self) -> Vec3 {
         let mut sum = Vec3::zeros();
         for vertex in self.vertices.iter() {
-            sum = sum + *vertex;
+            sum += *vertex;
         }
 
         sum / 3.0
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+    Trilinear,
+    Anisotropic,
+}
+
+// Espaço de cor dos texels. As texturas de cor (map_Ka, map_Kd) são
+// codificadas em sRGB e convertidas para o espaço linear na amostragem.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+// Tratamento das coordenadas de textura fora do intervalo [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+// Número máximo de amostras trilineares ao longo do eixo maior
+// da pegada do pixel na filtragem anisotrópica.
+const MAX_ANISOTROPY: usize = 8;
+
+struct MipLevel {
+    img: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
 pub struct TextureMap {
     img: Vec<u8>,
     width: usize,
@@ -107,6 +109,12 @@ pub struct TextureMap {
 
     f_width: f32,
     f_height: f32,
+
+    // Levels 1.. of the mip chain, each one half the size of the previous.
+    mips: Vec<MipLevel>,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    color_space: ColorSpace,
 }
 
 impl TextureMap {
@@ -122,6 +130,11 @@ impl TextureMap {
 
             f_width: width as f32,
             f_height: height as f32,
+
+            mips: vec![],
+            filter: TextureFilter::Nearest,
+            wrap: WrapMode::Repeat,
+            color_space: ColorSpace::Linear,
         }
     }
 
@@ -134,44 +147,422 @@ impl TextureMap {
         )
     }
 
-    pub fn get_rgba_slice(&self, u: f32, v: f32) -> [u8; 4] {
-        debug_assert!((0.0..=1.0).contains(&u));
-        debug_assert!((0.0..=1.0).contains(&v));
-        debug_assert!(self.components == 4);
+    // Empty maps are read as black.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn filter(&self) -> TextureFilter {
+        self.filter
+    }
+
+    // The mip chain is generated on demand for the filters that need it.
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.filter = filter;
+
+        let mipmapped = matches!(
+            filter,
+            TextureFilter::Trilinear | TextureFilter::Anisotropic
+        );
+        if mipmapped && self.mips.is_empty() {
+            self.generate_mips();
+        }
+    }
+
+    pub fn wrap(&self) -> WrapMode {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: WrapMode) {
+        self.wrap = wrap;
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        if self.color_space != color_space {
+            self.color_space = color_space;
+
+            // The mip levels are averaged in linear space.
+            if !self.mips.is_empty() {
+                self.generate_mips();
+            }
+        }
+    }
+
+    // Linear value in [0, 1] of a texel component.
+    fn decode(&self, c: u8) -> f32 {
+        match self.color_space {
+            ColorSpace::Srgb => srgb::srgb_to_linear(c),
+            ColorSpace::Linear => c as f32 / 255.0,
+        }
+    }
+
+    fn encode(&self, c: f32) -> u8 {
+        match self.color_space {
+            ColorSpace::Srgb => srgb::linear_to_srgb(c),
+            ColorSpace::Linear => (c * 255.0).round().clamp(0.0, 255.0) as u8,
+        }
+    }
+
+    // Index of the alpha component in the texel: the second one of the
+    // grey + alpha maps and the fourth one of the RGBA maps.
+    fn alpha_index(&self) -> Option<usize> {
+        match self.components {
+            2 => Some(1),
+            4 => Some(3),
+            _ => None,
+        }
+    }
+
+    // Red, green and blue bytes of the texel. The grey maps (with or
+    // without alpha) repeat their only color component.
+    fn texel_bytes(&self, texel: &[u8]) -> [u8; 3] {
+        if self.components < 3 {
+            [texel[0]; 3]
+        } else {
+            [texel[0], texel[1], texel[2]]
+        }
+    }
+
+    // Maps a texel index of any value to a valid index in [0, size). The
+    // empty maps have no valid index and give 0.
+    fn wrap_index(&self, i: isize, size: usize) -> usize {
+        if size == 0 {
+            return 0;
+        }
+
+        let size = size as isize;
+
+        let i = match self.wrap {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::MirroredRepeat => {
+                let i = i.rem_euclid(2 * size);
+
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            WrapMode::ClampToEdge => i.clamp(0, size - 1),
+        };
+
+        i as usize
+    }
+
+    fn generate_mips(&mut self) {
+        self.mips.clear();
+
+        let mut level = (self.img.as_slice(), self.width, self.height);
+
+        while level.1 > 1 || level.2 > 1 {
+            let (src, src_width, src_height) = level;
+
+            let width = (src_width / 2).max(1);
+            let height = (src_height / 2).max(1);
+
+            let mut img = Vec::with_capacity(width * height * self.components);
+
+            // Box filter 2x2. Nas dimensões ímpares a última
+            // linha/coluna é repetida.
+            for y in 0..height {
+                let y0 = (2 * y).min(src_height - 1);
+                let y1 = (2 * y + 1).min(src_height - 1);
+
+                for x in 0..width {
+                    let x0 = (2 * x).min(src_width - 1);
+                    let x1 = (2 * x + 1).min(src_width - 1);
+
+                    for c in 0..self.components {
+                        // O canal alfa é sempre linear.
+                        let is_alpha = self.alpha_index() == Some(c);
+
+                        let texel = |x: usize, y: usize| {
+                            let texel = src[(y * src_width + x) * self.components + c];
+
+                            if is_alpha {
+                                texel as f32 / 255.0
+                            } else {
+                                self.decode(texel)
+                            }
+                        };
+
+                        let sum = texel(x0, y0) + texel(x1, y0) + texel(x0, y1) + texel(x1, y1);
+
+                        if is_alpha {
+                            img.push((sum / 4.0 * 255.0).round() as u8);
+                        } else {
+                            img.push(self.encode(sum / 4.0));
+                        }
+                    }
+                }
+            }
+
+            self.mips.push(MipLevel { img, width, height });
+
+            let last = self.mips.last().unwrap();
+            level = (last.img.as_slice(), last.width, last.height);
+        }
+    }
+
+    fn mip_level(&self, level: usize) -> (&[u8], usize, usize) {
+        if level == 0 {
+            (self.img.as_slice(), self.width, self.height)
+        } else {
+            let mip = &self.mips[level - 1];
+            (mip.img.as_slice(), mip.width, mip.height)
+        }
+    }
+
+    // Offset of the texel (x, y) in the image of the mip level, after the
+    // wrap mode is applied.
+    fn texel_offset(&self, level: usize, x: isize, y: isize) -> usize {
+        let (_, width, height) = self.mip_level(level);
+
+        let x = self.wrap_index(x, width);
+        let y = self.wrap_index(y, height);
+
+        (y * width + x) * self.components
+    }
+
+    fn texel_rgb(&self, level: usize, x: isize, y: isize) -> [f32; 3] {
+        let (img, _, _) = self.mip_level(level);
+        let offset = self.texel_offset(level, x, y);
+
+        self.texel_bytes(&img[offset..offset + self.components])
+            .map(|c| self.decode(c))
+    }
+
+    // As linhas da imagem são armazenadas de cima para baixo,
+    // enquanto v cresce de baixo para cima.
+    fn nearest_rgb(&self, level: usize, u: f32, v: f32) -> [f32; 3] {
+        let (_, width, height) = self.mip_level(level);
+
+        let x = (u * width as f32).floor() as isize;
+        let y = ((1.0 - v) * height as f32).floor() as isize;
+
+        self.texel_rgb(level, x, y)
+    }
+
+    fn bilinear_rgb(&self, level: usize, u: f32, v: f32) -> [f32; 3] {
+        let (_, width, height) = self.mip_level(level);
+
+        let x = u * width as f32 - 0.5;
+        let y = (1.0 - v) * height as f32 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let x0 = x0 as isize;
+        let y0 = y0 as isize;
+
+        let t00 = self.texel_rgb(level, x0, y0);
+        let t10 = self.texel_rgb(level, x0 + 1, y0);
+        let t01 = self.texel_rgb(level, x0, y0 + 1);
+        let t11 = self.texel_rgb(level, x0 + 1, y0 + 1);
+
+        std::array::from_fn(|c| {
+            let top = t00[c] + (t10[c] - t00[c]) * tx;
+            let bottom = t01[c] + (t11[c] - t01[c]) * tx;
+
+            top + (bottom - top) * ty
+        })
+    }
+
+    fn trilinear_rgb(&self, lod: f32, u: f32, v: f32) -> [f32; 3] {
+        let lod = lod.clamp(0.0, self.mips.len() as f32);
+
+        let level = lod.floor() as usize;
+        let t = lod - level as f32;
+
+        let near = self.bilinear_rgb(level, u, v);
+
+        if t == 0.0 {
+            return near;
+        }
+
+        let far = self.bilinear_rgb(level + 1, u, v);
+
+        std::array::from_fn(|c| near[c] + (far[c] - near[c]) * t)
+    }
+
+    // Samples the texture with its filter and wrap mode, returning the
+    // linear color. `duv_dx` and `duv_dy` are the screen-space derivatives
+    // of the texture coordinates, used to pick the mip level.
+    pub fn sample(&self, u: f32, v: f32, duv_dx: Vec2, duv_dy: Vec2) -> Vec3 {
+        if self.is_empty() {
+            return Vec3::zeros();
+        }
+
+        let color = match self.filter {
+            TextureFilter::Nearest => self.nearest_rgb(0, u, v),
+            TextureFilter::Bilinear => self.bilinear_rgb(0, u, v),
+
+            TextureFilter::Trilinear | TextureFilter::Anisotropic => {
+                // Pegada do pixel em texels.
+                let dx = Vec2::new(duv_dx.x * self.f_width, duv_dx.y * self.f_height);
+                let dy = Vec2::new(duv_dy.x * self.f_width, duv_dy.y * self.f_height);
+
+                let len_x = (dx.x * dx.x + dx.y * dx.y).sqrt();
+                let len_y = (dy.x * dy.x + dy.y * dy.y).sqrt();
+
+                let (major, minor, major_axis) = if len_x > len_y {
+                    (len_x, len_y, duv_dx)
+                } else {
+                    (len_y, len_x, duv_dy)
+                };
+
+                if self.filter == TextureFilter::Trilinear || minor <= EPS {
+                    self.trilinear_rgb(major.max(EPS).log2(), u, v)
+                } else {
+                    // Várias amostras ao longo do eixo maior, cada uma com
+                    // o nível de mip do eixo menor.
+                    let samples = ((major / minor).ceil() as usize).clamp(1, MAX_ANISOTROPY);
+                    let lod = (major / samples as f32).max(EPS).log2();
+
+                    let mut sum = [0.0; 3];
+                    for i in 0..samples {
+                        let t = (i as f32 + 0.5) / samples as f32 - 0.5;
+                        let uv = Vec2::new(u, v) + major_axis * t;
+
+                        let color = self.trilinear_rgb(lod, uv.x, uv.y);
+                        sum.iter_mut().zip(color).for_each(|(s, c)| *s += c);
+                    }
+
+                    sum.map(|c| c / samples as f32)
+                }
+            }
+        };
+
+        Vec3::new(color)
+    }
+
+    fn nearest_offset(&self, u: f32, v: f32) -> usize {
+        let x = (u * self.f_width).floor() as isize;
+        let y = ((1.0 - v) * self.f_height).floor() as isize;
+
+        self.texel_offset(0, x, y)
+    }
 
-        // TODO: Verify correctness
-        let u_idx = (u * self.f_width).floor() as usize;
-        let v_idx = self.height - 1 - (v * self.f_height).floor() as usize;
+    // Maps without alpha are opaque.
+    pub fn get_rgba_slice(&self, u: f32, v: f32) -> [u8; 4] {
+        if self.is_empty() {
+            return [0; 4];
+        }
 
-        let offset = (v_idx * self.width + u_idx) * self.components;
+        let offset = self.nearest_offset(u, v);
+        let texel = &self.img[offset..offset + self.components];
 
-        debug_assert!(offset < self.img.len());
-        debug_assert!(offset + 4 <= self.img.len());
+        let [r, g, b] = self.texel_bytes(texel);
+        let a = self.alpha_index().map_or(255, |index| texel[index]);
 
-        self.img[(offset)..(offset) + 4].try_into().unwrap()
+        [r, g, b, a]
     }
 
     pub fn get_rgb_slice(&self, u: f32, v: f32) -> [u8; 3] {
-        debug_assert!((0.0..=1.0).contains(&u));
-        debug_assert!((0.0..=1.0).contains(&v));
+        if self.is_empty() {
+            return [0; 3];
+        }
+
+        let offset = self.nearest_offset(u, v);
+
+        self.texel_bytes(&self.img[offset..offset + self.components])
+    }
+
+    // True for the grey maps and for the color maps whose texels all have
+    // equal red, green and blue components.
+    pub fn is_grayscale(&self) -> bool {
+        self.components < 3
+            || self
+                .img
+                .chunks_exact(self.components)
+                .all(|texel| texel[0] == texel[1] && texel[1] == texel[2])
+    }
+
+    // Converts a height map (the red component) into a tangent-space
+    // normal map, using central differences between the neighbor texels.
+    pub fn height_to_normal_map(&self) -> TextureMap {
+        // Altura, em texels, correspondente ao valor máximo do height map.
+        const HEIGHT_SCALE: f32 = 4.0;
+
+        let height = |x: isize, y: isize| self.img[self.texel_offset(0, x, y)] as f32 / 255.0;
+
+        let mut img = Vec::with_capacity(self.width * self.height * 3);
+
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let dh_dx = (height(x + 1, y) - height(x - 1, y)) / 2.0 * HEIGHT_SCALE;
+                // As linhas da imagem crescem no sentido oposto a v.
+                let dh_dv = (height(x, y - 1) - height(x, y + 1)) / 2.0 * HEIGHT_SCALE;
+
+                let normal = Vec3::new([-dh_dx, -dh_dv, 1.0]).normalized();
+
+                img.extend(
+                    [normal.x(), normal.y(), normal.z()]
+                        .map(|c| ((c * 0.5 + 0.5) * 255.0).round() as u8),
+                );
+            }
+        }
+
+        let mut normal_map = TextureMap::new(img, self.width, self.height, 3);
+        normal_map.set_wrap(self.wrap);
+        normal_map.set_filter(self.filter);
+
+        normal_map
+    }
+
+    // Extracts one channel of the map into the three components, for the
+    // scalar maps (map_Ns, map_d). `channel` follows the `-imfchan` option:
+    // 'r', 'g', 'b', 'm' (matte, the alpha) or 'l' (luminance). Maps without
+    // alpha use the luminance as the matte channel, and the color channels
+    // of the grey maps are all the grey one.
+    pub fn channel_map(&self, channel: char) -> TextureMap {
+        let channel = match channel {
+            'm' => self.alpha_index(),
+            'r' if self.components >= 3 => Some(0),
+            'g' if self.components >= 3 => Some(1),
+            'b' if self.components >= 3 => Some(2),
+            _ => None,
+        };
 
-        // TODO: Verify correctness
-        let u_idx = (u * (self.f_width - 1.)).floor() as usize;
-        let v_idx = self.height - 1 - (v * (self.f_height - 1.)).floor() as usize;
+        let img = self
+            .img
+            .chunks_exact(self.components)
+            .flat_map(|texel| {
+                let value = match channel {
+                    Some(index) => texel[index],
+                    None => {
+                        let [r, g, b] = self.texel_bytes(texel).map(|c| c as f32);
+                        (0.2126 * r + 0.7152 * g + 0.0722 * b).round() as u8
+                    }
+                };
 
-        let offset = (v_idx * self.width + u_idx) * self.components;
+                [value; 3]
+            })
+            .collect();
 
-        debug_assert!(offset < self.img.len());
-        debug_assert!(offset + 3 <= self.img.len());
+        let mut channel_map = TextureMap::new(img, self.width, self.height, 3);
+        channel_map.set_wrap(self.wrap);
+        channel_map.set_filter(self.filter);
 
-        self.img[(offset)..(offset) + 3].try_into().unwrap()
+        channel_map
     }
 
     pub fn new_empty() -> Self {
-        Self::new(Vec::new(), 0, 0, 0)
+        Self::new(Vec::new(), 0, 0, 3)
     }
 
-    pub fn load_from_file(file_path: &std::path::PathBuf) -> Self {
+    pub fn load_from_file(
+        file_path: &std::path::PathBuf,
+        color_space: ColorSpace,
+        filter: TextureFilter,
+    ) -> Self {
         use stb::image::stbi_info_from_reader;
         use stb::image::stbi_load_from_reader;
         use stb::image::Channels;
@@ -183,39 +574,119 @@ impl TextureMap {
 
         _ = file.rewind();
 
-        let channels = match dbg!(pre_info.components) {
-            3 => Channels::Rgb,
-            4 => Channels::RgbAlpha,
+        // As imagens em tons de cinza (ex.: height maps) mantêm um só
+        // componente de cor.
+        let (channels, components) = match pre_info.components {
+            1 => (Channels::Grey, 1),
+            2 => (Channels::GreyAlpha, 2),
+            3 => (Channels::Rgb, 3),
+            4 => (Channels::RgbAlpha, 4),
             _ => unreachable!(),
         };
 
-        dbg!(channels);
         let (info, img) =
             stbi_load_from_reader(&mut file, channels).expect("Deu errado ler a textura");
 
-        Self::new(
+        let mut texture_map = Self::new(
             Vec::from(img.as_slice()),
             info.width as usize,
             info.height as usize,
-            info.components as usize,
-        )
+            components,
+        );
+
+        texture_map.set_color_space(color_space);
+        texture_map.set_filter(filter);
+
+        texture_map
     }
 }
 
+// Modelo de iluminação do material (illum). Os modelos de 3 a 9 (com
+// reflexão, refração ou vidro) refletem o ambiente e os acima de 9 são
+// tratados como 2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IlluminationModel {
+    // 0: só a cor difusa, sem iluminação.
+    Color,
+    // 1: ambiente e difusa.
+    Diffuse,
+    // 2: ambiente, difusa e especular.
+    Specular,
+    // 3: especular e reflexão do ambiente.
+    Reflection,
+}
+
+impl IlluminationModel {
+    pub fn from_illum(illum: i32) -> Self {
+        match illum {
+            0 => Self::Color,
+            1 => Self::Diffuse,
+            3..=9 => Self::Reflection,
+            _ => Self::Specular,
+        }
+    }
+}
+
+// Reflectance model used to shade a material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaterialModel {
+    // Blinn-Phong, with ka, kd, ks and ns.
+    Phong,
+    // Metallic-roughness, with the Cook-Torrance GGX specular. The base
+    // color is kd * map_kd, as in the PBR extension of the MTL format.
+    Pbr,
+}
+
 // https://paulbourke.net/dataformats/mtl/
 pub struct Texture {
     pub name: String,
     pub ka: Vec3,
     pub kd: Vec3,
     pub ks: Vec3,
+    // Emissive color, added regardless of the lights.
+    pub ke: Vec3,
+    // Specular exponent.
+    pub ns: f32,
+    pub illum: IlluminationModel,
+
+    pub material_model: MaterialModel,
+    // Pr and Pm, multiplied by the maps map_Pr and map_Pm (scalar maps).
+    pub roughness: f32,
+    pub metallic: f32,
 
     pub alpha: f32,
 
     pub map_ka: TextureMap,
     pub map_kd: TextureMap,
+    pub map_ks: Option<TextureMap>,
+    pub map_ke: Option<TextureMap>,
+    // Scalar maps, in all the three components: map_Ns multiplies ns and
+    // map_d is the cutout mask, discarding the fragments below
+    // ALPHA_CUTOFF.
+    pub map_ns: Option<TextureMap>,
+    pub map_d: Option<TextureMap>,
+    pub map_roughness: Option<TextureMap>,
+    pub map_metallic: Option<TextureMap>,
+
+    // Tangent-space normal map (norm, map_Bump or bump) and the `-bm`
+    // multiplier of its statement, applied to the tangent components.
+    pub map_bump: Option<TextureMap>,
+    pub bump_multiplier: f32,
 }
 
 impl Texture {
+    pub const ALPHA_CUTOFF: f32 = 0.5;
+
+    // Whether the point `uv` falls in a hole of the cutout mask (map_d),
+    // sampled at the full resolution.
+    pub fn is_cut_out(&self, uv: Vec2) -> bool {
+        let zero = Vec2::new(0.0, 0.0);
+
+        self.map_d
+            .as_ref()
+            .is_some_and(|map_d| map_d.sample(uv.x, uv.y, zero, zero).x() < Self::ALPHA_CUTOFF)
+    }
+
     pub fn with_diffuse_map(diff_map: TextureMap) -> Self {
         let map_ka = TextureMap::default();
         let map_kd = diff_map;
@@ -225,14 +696,48 @@ impl Texture {
             ka: Vec3::new([1.0, 1.0, 1.0]),
             kd: Vec3::new([1.0, 1.0, 1.0]),
             ks: Vec3::new([1.0, 1.0, 1.0]),
+            ke: Vec3::new([0.0, 0.0, 0.0]),
+            ns: 2.0,
+            illum: IlluminationModel::Specular,
+
+            material_model: MaterialModel::Phong,
+            roughness: 0.5,
+            metallic: 0.0,
 
             alpha: 1.0,
 
             map_ka,
             map_kd,
+            map_ks: None,
+            map_ke: None,
+            map_ns: None,
+            map_d: None,
+            map_roughness: None,
+            map_metallic: None,
+
+            map_bump: None,
+            bump_multiplier: 1.0,
         }
     }
 
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.map_ka.set_filter(filter);
+        self.map_kd.set_filter(filter);
+
+        [
+            &mut self.map_ks,
+            &mut self.map_ke,
+            &mut self.map_ns,
+            &mut self.map_d,
+            &mut self.map_roughness,
+            &mut self.map_metallic,
+            &mut self.map_bump,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|map| map.set_filter(filter));
+    }
+
     // TODO: impl std::default::Default trait
     pub fn default() -> Self {
         let map_ka = TextureMap::default();
@@ -243,30 +748,27 @@ impl Texture {
             ka: Vec3::new([0.9, 0.9, 0.9]),
             kd: Vec3::new([0.4, 0.4, 0.4]),
             ks: Vec3::new([0.5, 0.5, 0.5]),
+            ke: Vec3::new([0.0, 0.0, 0.0]),
+            ns: 2.0,
+            illum: IlluminationModel::Specular,
+
+            material_model: MaterialModel::Phong,
+            roughness: 0.5,
+            metallic: 0.0,
 
             alpha: 1.0,
 
             map_ka,
             map_kd,
-        }
-    }
-}
-
-#[derive(Clone, Copy, Debug)]
-struct VertexVisual {
-    light: Vec3,
-    eye: Vec3,
-    halfway: Vec3,
-    depth: f32,
-}
-
-impl VertexVisual {
-    fn zeroed() -> Self {
-        Self {
-            light: Vec3::zeros(),
-            eye: Vec3::zeros(),
-            halfway: Vec3::zeros(),
-            depth: 0.0,
+            map_ks: None,
+            map_ke: None,
+            map_ns: None,
+            map_d: None,
+            map_roughness: None,
+            map_metallic: None,
+
+            map_bump: None,
+            bump_multiplier: 1.0,
         }
     }
 }
@@ -308,6 +810,22 @@ impl Camera {
         let near: f32 = -10.0; // Distância da origem até o near plane
         let far = near - 500.; // Distância da origem até o far plane
         let fov_x: f32 = 135.0;
+
+        let camera = Self::with_frustum(pos, dir, ratio, fov_x, near, far);
+        let window = camera.get_window();
+
+        println!(
+            "CameraWindow dimension ({} x {})",
+            window.right - window.left,
+            window.top - window.bottom
+        );
+
+        camera
+    }
+
+    // Camera with a horizontal field of view of `fov_x` degrees, whose view
+    // volume goes from `near` to `far` (both negative, along -w).
+    pub fn with_frustum(pos: Vec3, dir: Vec3, ratio: f32, fov_x: f32, near: f32, far: f32) -> Self {
         let fov_x_rad = fov_x.to_radians();
 
         // A origem das coordenadas é no centro:
@@ -319,7 +837,7 @@ impl Camera {
         // |      bottom       |
         //  ‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾‾
         //           W
-        let right = dbg!(near.abs() * (fov_x_rad / 2.0).tan()); // right = W/2
+        let right = near.abs() * (fov_x_rad / 2.0).tan(); // right = W/2
         let left = -right; // left = -W/2
         let top = ratio.recip() * right; // top = H/2
         let bottom = -top; // bottom = -H/2
@@ -330,12 +848,6 @@ impl Camera {
         assert!(right > left);
         assert!(top > bottom);
 
-        println!(
-            "CameraWindow dimension ({} x {})",
-            right - left,
-            top - bottom
-        );
-
         Self {
             position: pos, // Lookfrom
             direction: dir.normalized(),
@@ -378,30 +890,35 @@ impl Camera {
         self.position = pos;
     }
 
+    // The up direction must not be parallel to the view direction.
+    pub fn set_up_direction(&mut self, up: Vec3) {
+        self.up_direction = up.normalized();
+    }
+
     pub fn move_up(&mut self, dist: f32) {
-        self.position = self.position + self.up_direction * dist;
+        self.position += self.up_direction * dist;
     }
 
     pub fn move_down(&mut self, dist: f32) {
-        self.position = self.position + self.up_direction * (-dist);
+        self.position += self.up_direction * (-dist);
     }
 
     pub fn move_left(&mut self, dist: f32) {
-        self.position = self.position + self.u * (-dist);
+        self.position += self.u * (-dist);
     }
 
     pub fn move_right(&mut self, dist: f32) {
-        self.position = self.position + self.u * dist;
+        self.position += self.u * dist;
     }
 
     pub fn move_foward(&mut self, dist: f32) {
         let foward = self.up_direction.cross(self.u).normalized();
-        self.position = self.position + foward * dist;
+        self.position += foward * dist;
     }
 
     pub fn move_backward(&mut self, dist: f32) {
         let backward = self.u.cross(self.up_direction).normalized();
-        self.position = self.position + backward * dist;
+        self.position += backward * dist;
     }
 
     pub fn rotate_origin(&mut self, theta: f32) {
@@ -427,6 +944,40 @@ impl Camera {
         ])
     }
 
+    // Direction of the ray from the camera through the point (x, y) of the
+    // near plane, in normalized coordinates ([-1, 1], y pointing up).
+    pub fn view_ray(&self, x: f32, y: f32) -> Vec3 {
+        let window = &self.window_view;
+
+        (self.u * (x * window.right) + self.v * (y * window.top)
+            - self.w * self.min_view_dist.abs())
+        .normalized()
+    }
+
+    // Normalized coordinates of the projection of `point` on the near plane,
+    // the inverse of `view_ray`. None when the point is not beyond the near
+    // plane.
+    pub fn project(&self, point: Vec3) -> Option<Vec2> {
+        let window = &self.window_view;
+        let near = self.min_view_dist.abs();
+
+        let relative = point - self.position;
+        let depth = -relative.dot(self.w);
+        if depth < near {
+            return None;
+        }
+
+        Some(Vec2::new(
+            relative.dot(self.u) * near / (depth * window.right),
+            relative.dot(self.v) * near / (depth * window.top),
+        ))
+    }
+
+    // Vetores u, v e w da base da câmera.
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        (self.u, self.v, self.w)
+    }
+
     pub fn offset_screen_direction(&mut self, dx: f32, dy: f32) {
         self.direction = (self.direction + self.u * dx + self.v * dy).normalized();
     }
@@ -451,355 +1002,554 @@ impl Camera {
     }
 
     pub fn gen_matrix(&mut self) -> Matrix4 {
-        // A matriz View, que transforma pontos do espaço do mundo
-        // para o espaço da câmera, é obtida da seguinte forma:
-        // Primeiro, encontramos a matriz de transformação que translada
-        // a câmera para a origem do mundo utilizando a posição da câmera.
-        let pos = self.position;
-        let matrix_pos = Matrix4::new([
-            [1.0, 0.0, 0.0, -pos.x()],
-            [0.0, 1.0, 0.0, -pos.y()],
-            [0.0, 0.0, 1.0, -pos.z()],
-            [0.0, 0.0, 0.0, 1.0],
-        ]);
-
-        // Com as origens alinhadas, encontramos (u,v,w), os três vetores
-        // unitários que formam a base da câmera e que não estão alinhados
-        // com as coordenadas de mundo (são o x,y,z da câmera).
+        // (u,v,w), os três vetores unitários que formam a base da câmera
+        // (são o x,y,z da câmera), são usados fora da matriz View, por
+        // isso são atualizados junto com ela.
         self.update_basis();
 
-        // A matriz de rotação que alinha os eixos da câmera com o mundo
-        // é obtida pela transposta da matriz da base da câmera, que aplica
-        // uma mudança de base do mundo para a câmera.
-        let matrix_basis_transp = self.get_basis_matrix().transposed();
-
-        // Multiplicando a translação com a rotação, obtemos a matriz View.
-        let matrix_cam = matrix_basis_transp * matrix_pos;
-        matrix_cam
+        // A matriz View, que transforma pontos do espaço do mundo para o
+        // espaço da câmera: translada a câmera para a origem do mundo e
+        // aplica a mudança de base do mundo para a câmera.
+        Matrix4::look_at(
+            self.position,
+            self.position + self.direction,
+            self.up_direction,
+        )
     }
+}
 
-    fn gen_view_planes(&mut self) -> ([ViewPlane; 2], [ViewPlane; 4]) {
-        self.update_basis();
-
-        // Matriz para aplicar a rotação que leva coordenadas da câmera para o mundo.
-        let matrix_basis = self.get_basis_matrix();
-
-        let camera_pos = self.get_pos();
-        let camera_window = self.get_window();
+// Transformações e valores usados na projeção dos triângulos de um frame.
+struct Projection<'a> {
+    matrix_clip: &'a Matrix4,
+    matrix_viewport: &'a Matrix4,
+    guard_band: f32,
+    uniforms: &'a Uniforms<'a>,
+}
 
-        let n = self.get_min_view_dist();
-        let f = self.get_max_view_dist();
-        let r = camera_window.right;
-        let l = camera_window.left;
-        let t = camera_window.top;
-        let b = camera_window.bottom;
+pub struct Scene {
+    width: usize,  // Comprimento da Janela
+    height: usize, // Altura da Janela
+    pub camera: Camera,
+    objects: Vec<Object>,
+    next_object_id: u32,
+    graph: SceneGraph,
+
+    lights: Vec<Light>,
+    ambient_light: Vec3,
+
+    shadows_enabled: bool,
+    shadow_map_size: usize,
+    // Canvas onde são renderizados os depth buffers dos shadow maps.
+    shadow_canvas: Canvas,
+    shadow_cache: Option<ShadowCache>,
+
+    background: Arc<Background>,
+    // Iluminação pré-filtrada do fundo, calculada por uma thread quando a
+    // iluminação pelo ambiente é ativada. Até a thread terminar, a cena usa
+    // a iluminação do fundo anterior.
+    environment_light: Option<(Arc<Background>, Arc<EnvironmentLight>)>,
+    environment_light_job: Option<EnvironmentLightJob>,
+    environment_lighting: bool,
+    environment_intensity: f32,
+
+    depth_prepass: bool,
+    shading_model: ShadingModel,
+    guard_band_enabled: bool,
+    frustum_culling: bool,
+
+    render_stats: RenderStats,
+}
 
-        // Os 4 vértices do near plane em coordenadas de câmera
-        let upper_right_near_cam = Vec3::new([r, t, n]).as_vec4();
-        let upper_left_near_cam = Vec3::new([l, t, n]).as_vec4();
-        let lower_right_near_cam = Vec3::new([r, b, n]).as_vec4();
-        let lower_left_near_cam = Vec3::new([l, b, n]).as_vec4();
-
-        // Os 4 vértices do near plane em coordenadas de mundo com offset da posicao da camera
-        let upper_right_near_world =
-            (matrix_basis * upper_right_near_cam).vec3_over_w() + camera_pos;
-        let upper_left_near_world = (matrix_basis * upper_left_near_cam).vec3_over_w() + camera_pos;
-        let lower_right_near_world =
-            (matrix_basis * lower_right_near_cam).vec3_over_w() + camera_pos;
-        let lower_left_near_world = (matrix_basis * lower_left_near_cam).vec3_over_w() + camera_pos;
-
-        let x_center = (l + r) / 2.; // = 0
-        let y_center = (b + t) / 2.; // = 0
-
-        // Os 4 vértices do far plane em coordenadas de câmera
-        let upper_center_far_cam = Vec3::new([x_center, (f * t) / n, f]).as_vec4();
-        let lower_center_far_cam = Vec3::new([x_center, (f * b) / n, f]).as_vec4();
-        let right_center_far_cam = Vec3::new([(f * r) / n, y_center, f]).as_vec4();
-        let left_center_far_cam = Vec3::new([(f * l) / n, y_center, f]).as_vec4();
-
-        // Os 4 vértices do far plane em coordenadas de mundo com offset da posicao da camera
-        let upper_center_far_world =
-            (matrix_basis * upper_center_far_cam).vec3_over_w() + camera_pos;
-        let lower_center_far_world =
-            (matrix_basis * lower_center_far_cam).vec3_over_w() + camera_pos;
-        let right_center_far_world =
-            (matrix_basis * right_center_far_cam).vec3_over_w() + camera_pos;
-        let left_center_far_world = (matrix_basis * left_center_far_cam).vec3_over_w() + camera_pos;
-
-        // Um ponto arbitrário dentro do frustrum para testes.
-        let visible_point = (upper_right_near_world + lower_center_far_world) / 2.0;
-
-        let depth_planes = [
-            ViewPlane::new(
-                [
-                    upper_right_near_world,
-                    lower_right_near_world,
-                    lower_left_near_world,
-                ],
-                visible_point,
-                "near",
-            ),
-            ViewPlane::new(
-                [
-                    left_center_far_world,
-                    right_center_far_world,
-                    upper_center_far_world,
-                ],
-                visible_point,
-                "far",
-            ),
-        ];
+// Shadow maps de um frame e as entradas usadas para gerá-los. Os mapas só
+// são refeitos quando a geometria das luzes que projetam sombras, as
+// matrizes de mundo dos objetos ou o tamanho dos mapas mudam; a cor e a
+// intensidade das luzes não entram nos mapas.
+struct ShadowCache {
+    // Tipo, posição, direção, alcance e cone de cada luz de `Scene::lights`,
+    // ou `None` se ela não projeta sombras.
+    casters: Vec<Option<LightKind>>,
+    world_matrices: Vec<Matrix4>,
+    size: usize,
+    maps: Vec<Option<ShadowMap>>,
+}
 
-        let lateral_planes = [
-            ViewPlane::new(
-                [
-                    right_center_far_world,
-                    upper_right_near_world,
-                    lower_right_near_world,
-                ],
-                visible_point,
-                "right",
-            ),
-            ViewPlane::new(
-                [
-                    left_center_far_world,
-                    lower_left_near_world,
-                    upper_left_near_world,
-                ],
-                visible_point,
-                "left",
-            ),
-            ViewPlane::new(
-                [
-                    upper_center_far_world,
-                    upper_left_near_world,
-                    upper_right_near_world,
-                ],
-                visible_point,
-                "top",
-            ),
-            ViewPlane::new(
-                [
-                    lower_center_far_world,
-                    lower_left_near_world,
-                    lower_right_near_world,
-                ],
-                visible_point,
-                "bottom",
-            ),
-        ];
+// Thread que pré-filtra a iluminação de um fundo.
+struct EnvironmentLightJob {
+    background: Arc<Background>,
+    handle: std::thread::JoinHandle<EnvironmentLight>,
+}
 
-        (depth_planes, lateral_planes)
-    }
+// Quantidade de objetos e de meshes desenhados e descartados pelo frustum
+// culling no último frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub objects_drawn: usize,
+    pub objects_culled: usize,
+    pub meshes_drawn: usize,
+    pub meshes_culled: usize,
 }
 
-struct ViewPlane {
-    normal: Vec3,
-    k: f32,
-    label: String,
+// Built-in shaders used by `Scene::render`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadingModel {
+    Phong,
+    Flat,
+    Unlit,
+    Normal,
 }
 
-impl ViewPlane {
-    pub fn new(points: [Vec3; 3], visible_point: Vec3, label: &str) -> Self {
-        let a_point = points[0];
-        let b_point = points[1];
-        let c_point = points[2];
+impl Scene {
+    pub fn new(width: usize, height: usize) -> Self {
+        //let camera_pos = Vec3::new([0., 2., 4.]);
+        //let camera_pos = Vec3::new([0., 300., 630.0]); // Aviao ae
+        let camera_pos = Vec3::new([0., 0., 150.0]);
+        let camera_dir = camera_pos * -1.0;
 
-        let p_vec = b_point - a_point;
-        let q_vec = c_point - b_point;
+        let light_source = Light::point(
+            Vec3::new([0., 300., 300.]),
+            2000.0,
+            Vec3::new([1.0, 1.0, 1.0]),
+            1.0,
+        );
 
-        let mut normal = p_vec.cross(q_vec);
-        let mut k = -normal.dot(a_point);
+        let ratio = (width as f32) / (height as f32);
+        let camera = Camera::new(camera_pos, camera_dir, ratio);
 
-        let mut test_value = normal.dot(visible_point) + k;
+        // alguns modelos classicos
+        // https://casual-effects.com/data/
 
-        // a condicao de validez eh que a origem gere um valor positivo,
-        // ou seja, ela esta dentro do volume de visao
+        //let obj = Object::load_from_file("models/lemur/lemur.obj");
+        //let obj_vec = Object::load_from_directory("models/dungeon_set/");
 
-        if test_value < 0.0 {
-            normal = q_vec.cross(p_vec);
-            k = -normal.dot(a_point);
+        Self {
+            width,
+            height,
+            camera,
+            objects: vec![],
+            next_object_id: 0,
+            graph: SceneGraph::new(),
+
+            lights: vec![light_source],
+            // O termo ambiente era c_r * c_a, como uma luz ambiente branca.
+            // Somado às luzes da cena, 0.2 evita que as superfícies saturem.
+            ambient_light: Vec3::new([0.2, 0.2, 0.2]),
+
+            shadows_enabled: true,
+            shadow_map_size: Self::DEFAULT_SHADOW_MAP_SIZE,
+            shadow_canvas: Canvas::new(
+                Self::DEFAULT_SHADOW_MAP_SIZE,
+                Self::DEFAULT_SHADOW_MAP_SIZE,
+            ),
+            shadow_cache: None,
 
-            test_value = normal.dot(visible_point) + k;
-            assert!(test_value > 0.0, "N'ao deu pra criar o plano");
-        }
+            background: Arc::new(Background::Color(Canvas::clear_color())),
+            environment_light: None,
+            environment_light_job: None,
+            environment_lighting: false,
+            environment_intensity: 1.0,
 
-        Self {
-            normal,
-            k,
-            label: label.to_string(),
+            depth_prepass: false,
+            shading_model: ShadingModel::Phong,
+            guard_band_enabled: true,
+            frustum_culling: true,
+
+            render_stats: RenderStats::default(),
         }
     }
 
-    pub fn func(&self, point: Vec3) -> f32 {
-        self.normal.dot(point) + self.k
+    // Size of the guard band, relative to the view volume.
+    pub const GUARD_BAND: f32 = 4.0;
+
+    // With the guard band the triangles that cross the lateral planes of the
+    // view volume, but stay inside the guard band, are not clipped.
+    pub fn set_guard_band(&mut self, enabled: bool) {
+        self.guard_band_enabled = enabled;
     }
 
-    pub fn normal(&self) -> Vec3 {
-        self.normal
+    pub fn guard_band_enabled(&self) -> bool {
+        self.guard_band_enabled
     }
-    pub fn at_least_partially_visible(&self, tri: &Triangle) -> bool {
-        let a_vertex = tri.vertices[0];
-        let b_vertex = tri.vertices[1];
-        let c_vertex = tri.vertices[2];
 
-        let f_a = self.func(a_vertex);
-        let f_b = self.func(b_vertex);
-        let f_c = self.func(c_vertex);
+    // With frustum culling the objects and meshes whose bounding volumes are
+    // outside the view volume are skipped before running the vertex shader.
+    pub fn set_frustum_culling(&mut self, enabled: bool) {
+        self.frustum_culling = enabled;
+    }
 
-        if f_a > 0.0 && f_b > 0.0 && f_c > 0.0 {
-            // completly visible
-            return true;
-        } else if f_a <= 0.0 && f_b <= 0.0 && f_c <= 0.0 {
-            // not visible
-            return false;
-        } else {
-            // partially visible
-            return true;
-        }
+    pub fn frustum_culling(&self) -> bool {
+        self.frustum_culling
     }
 
-    pub fn clip(&self, tri: &Triangle, tri_pool_ret: &mut [Triangle]) -> usize {
-        use std::mem::swap;
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
 
-        let mut a_vertex = tri.vertices[0];
-        let mut b_vertex = tri.vertices[1];
-        let mut c_vertex = tri.vertices[2];
+    // Returns the index of the new light.
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
 
-        let mut a_attr = tri.vertices_attr[0];
-        let mut b_attr = tri.vertices_attr[1];
-        let mut c_attr = tri.vertices_attr[2];
+    // Removes the light at `index`; the lights after it are shifted.
+    pub fn remove_light(&mut self, index: usize) -> Option<Light> {
+        if index < self.lights.len() {
+            Some(self.lights.remove(index))
+        } else {
+            None
+        }
+    }
 
-        let mut f_a = self.func(a_vertex);
-        let mut f_b = self.func(b_vertex);
-        let mut f_c = self.func(c_vertex);
+    pub fn light_mut(&mut self, index: usize) -> Option<&mut Light> {
+        self.lights.get_mut(index)
+    }
 
-        if f_a > 0.0 && f_b > 0.0 && f_c > 0.0 {
-            // Todos os vértices dentro do volume,
-            // não é necessário clipar.
-            tri_pool_ret[0] = tri.clone();
-            return 1;
-        } else if f_a <= 0.0 && f_b <= 0.0 && f_c <= 0.0 {
-            // Todos os vértices fora do volume,
-            // não será exibido.
-            return 0;
-        }
-        // Se prosseguiu, verificar a existência de
-        // um vértice para fora do plano enquanto
-        // os outros dois vértices estáo dentro.
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
 
-        if f_a * f_c >= 0.0 {
-            swap(&mut f_b, &mut f_c);
-            swap(&mut b_vertex, &mut c_vertex);
+    pub fn set_ambient_light(&mut self, color: Vec3) {
+        self.ambient_light = color;
+    }
 
-            swap(&mut b_attr, &mut c_attr);
+    pub fn ambient_light(&self) -> Vec3 {
+        self.ambient_light
+    }
 
-            swap(&mut f_a, &mut f_b);
-            swap(&mut a_vertex, &mut b_vertex);
+    pub const DEFAULT_SHADOW_MAP_SIZE: usize = 512;
 
-            swap(&mut a_attr, &mut b_attr);
-        } else if f_b * f_c >= 0.0 {
-            swap(&mut f_a, &mut f_c);
-            swap(&mut a_vertex, &mut c_vertex);
+    pub fn set_shadows(&mut self, enabled: bool) {
+        self.shadows_enabled = enabled;
+    }
 
-            swap(&mut a_attr, &mut c_attr);
+    pub fn shadows_enabled(&self) -> bool {
+        self.shadows_enabled
+    }
 
-            swap(&mut f_a, &mut f_b);
-            swap(&mut a_vertex, &mut b_vertex);
+    // Width and height, in texels, of each face of the shadow maps.
+    pub fn set_shadow_map_size(&mut self, size: usize) {
+        assert!(size > 0);
 
-            swap(&mut a_attr, &mut b_attr);
+        if size != self.shadow_map_size {
+            self.shadow_map_size = size;
+            self.shadow_canvas.resize(size, size);
         }
+    }
 
-        // Calcular os pontos de interseção entre o plano e o triângulo
-        // para a criação dos novos triângulos da clipagem
-        let t_a = self.func(a_vertex) / self.normal().dot(a_vertex - c_vertex) - EPS;
-        let new_vertex_a = a_vertex + (c_vertex - a_vertex) * t_a;
-        let new_a_attr = a_attr + (c_attr - a_attr) * t_a;
-        let t_b = self.func(b_vertex) / self.normal().dot(b_vertex - c_vertex) - EPS;
-        let new_vertex_b = b_vertex + (c_vertex - b_vertex) * t_b;
-        let new_b_attr = b_attr + (c_attr - b_attr) * t_b;
-
-        // Apenas um vértice do triângulo para fora do plano
-        if f_c <= 0.0 {
-            let new_triangle_a = Triangle::new(
-                [a_vertex, new_vertex_a, new_vertex_b],
-                [a_attr, new_a_attr, new_b_attr],
-            );
+    pub fn shadow_map_size(&self) -> usize {
+        self.shadow_map_size
+    }
 
-            let new_triangle_b = Triangle::new(
-                [a_vertex, b_vertex, new_vertex_b],
-                [a_attr, b_attr, new_b_attr],
-            );
+    pub fn set_background(&mut self, background: Background) {
+        self.background = Arc::new(background);
+        self.update_environment_light();
+    }
 
-            tri_pool_ret[0] = new_triangle_a;
-            tri_pool_ret[1] = new_triangle_b;
-            return 2;
-        } else {
-            let new_triangle_c = Triangle::new(
-                [c_vertex, new_vertex_a, new_vertex_b],
-                [c_attr, new_a_attr, new_b_attr],
-            );
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
 
-            tri_pool_ret[0] = new_triangle_c;
-            return 1;
-        }
+    // When enabled, the background replaces the ambient light and is
+    // reflected by the surfaces (image-based lighting).
+    pub fn set_environment_lighting(&mut self, enabled: bool) {
+        self.environment_lighting = enabled;
+        self.update_environment_light();
     }
-}
 
-pub struct Scene {
-    width: usize,  // Comprimento da Janela
-    height: usize, // Altura da Janela
-    pub camera: Camera,
-    objects: Vec<Object>,
-    next_object_id: u32,
+    pub fn environment_lighting_enabled(&self) -> bool {
+        self.environment_lighting
+    }
 
-    light_source: Vec3,
-}
+    pub fn set_environment_intensity(&mut self, intensity: f32) {
+        self.environment_intensity = intensity;
+    }
 
-impl Scene {
-    pub fn new(width: usize, height: usize) -> Self {
-        //let camera_pos = Vec3::new([0., 2., 4.]);
-        //let camera_pos = Vec3::new([0., 300., 630.0]); // Aviao ae
-        let camera_pos = Vec3::new([0., 0., 150.0]);
-        let camera_dir = camera_pos * -1.0;
+    pub fn environment_intensity(&self) -> f32 {
+        self.environment_intensity
+    }
 
-        let light_source = Vec3::new([0., 300., 300.]);
+    // Collects the environment light built by the worker thread and starts
+    // a new one when the background changed. Only one thread runs at a time,
+    // so dragging a color in the GUI doesn't pile up threads.
+    fn update_environment_light(&mut self) {
+        if self
+            .environment_light_job
+            .as_ref()
+            .is_some_and(|job| job.handle.is_finished())
+        {
+            let job = self.environment_light_job.take().unwrap();
+            let light = job
+                .handle
+                .join()
+                .expect("environment light thread panicked");
+            self.environment_light = Some((job.background, Arc::new(light)));
+        }
 
-        let ratio = (width as f32) / (height as f32);
-        let camera = Camera::new(camera_pos, camera_dir, ratio);
+        let up_to_date = self
+            .environment_light
+            .as_ref()
+            .is_some_and(|(background, _)| Arc::ptr_eq(background, &self.background));
 
-        // alguns modelos classicos
-        // https://casual-effects.com/data/
+        if self.environment_lighting && !up_to_date && self.environment_light_job.is_none() {
+            let background = self.background.clone();
+            let source = background.clone();
 
-        //let obj = Object::load_from_file("models/lemur/lemur.obj");
-        //let obj_vec = Object::load_from_directory("models/dungeon_set/");
+            self.environment_light_job = Some(EnvironmentLightJob {
+                background,
+                handle: std::thread::spawn(move || EnvironmentLight::new(&source)),
+            });
+        }
+    }
 
-        Self {
-            width,
-            height,
-            camera,
-            objects: vec![],
-            next_object_id: 0,
+    pub fn set_shading_model(&mut self, shading_model: ShadingModel) {
+        self.shading_model = shading_model;
+    }
 
-            light_source,
-        }
+    pub fn shading_model(&self) -> ShadingModel {
+        self.shading_model
+    }
+
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
     }
 
     pub fn add_obj(&mut self, obj: Object) -> ObjectInfo {
+        self.add_obj_to(obj, SceneGraph::ROOT, Transform::identity())
+    }
+
+    // Adds the object in a new node of the scene graph, child of `parent`.
+    pub fn add_obj_to(&mut self, obj: Object, parent: NodeId, transform: Transform) -> ObjectInfo {
+        let node = self.graph.add_node(obj.name.clone(), parent, transform);
+
         let info = ObjectInfo {
             id: self.next_object_id,
             name: obj.name.clone(),
+            node,
+            parent,
+            transform,
             mesh_info_list: obj.mesh_info_list(),
         };
 
         self.objects.push(obj);
-        self.objects.last_mut().unwrap().id = Some(self.next_object_id);
+
+        let obj = self.objects.last_mut().unwrap();
+        obj.id = Some(self.next_object_id);
+        obj.node = Some(node);
 
         self.next_object_id += 1;
 
         info
     }
 
+    // Adds a node without object, to group other nodes.
+    pub fn add_node(&mut self, name: String, parent: NodeId, transform: Transform) -> NodeId {
+        self.graph.add_node(name, parent, transform)
+    }
+
+    pub fn graph(&self) -> &SceneGraph {
+        &self.graph
+    }
+
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) {
+        if let Some(node) = self.graph.node_mut(node) {
+            node.transform = transform;
+        }
+    }
+
+    // See `SceneGraph::set_parent`.
+    pub fn set_parent(&mut self, node: NodeId, parent: NodeId) -> bool {
+        self.graph.set_parent(node, parent)
+    }
+
+    pub fn world_matrix(&self, node: NodeId) -> Matrix4 {
+        self.graph.world_matrix(node)
+    }
+
+    // Screen coordinates, as those of the projected triangles, of the
+    // projection of `point`. None when the point is behind the near plane.
+    // Must be called after the scene is rendered, which updates the camera.
+    // Like the viewport matrix, the centers of the pixels are at the integer
+    // coordinates, where the rasterizer samples them.
+    pub fn project_point(&self, point: Vec3) -> Option<Vec2> {
+        let ndc = self.camera.project(point)?;
+
+        Some(Vec2::new(
+            (ndc.x + 1.0) / 2.0 * self.width as f32 - 0.5,
+            (ndc.y + 1.0) / 2.0 * self.height as f32 - 0.5,
+        ))
+    }
+
+    // Direction of the ray from the camera through the point (x, y) of the
+    // screen, the inverse of `project_point`.
+    pub fn screen_ray(&self, x: f32, y: f32) -> Vec3 {
+        let ndc_x = (x + 0.5) / self.width as f32 * 2.0 - 1.0;
+        let ndc_y = (y + 0.5) / self.height as f32 * 2.0 - 1.0;
+
+        self.camera.view_ray(ndc_x, ndc_y)
+    }
+
+    // Object, mesh and triangle seen at the point (x, y) of the screen, as
+    // drawn in the last frame.
+    pub fn pick(&self, x: f32, y: f32) -> Option<PickHit> {
+        let ray = Ray::new(self.camera.get_pos(), self.screen_ray(x, y));
+
+        let (obj, hit) = self
+            .objects
+            .iter()
+            .filter_map(|obj| {
+                let matrix = self.object_world_matrix(obj);
+                ray.intersect_aabb(&obj.bounds.transformed(&matrix))?;
+
+                // O raio no espaço do objeto tem os mesmos parâmetros t, que
+                // podem ser comparados entre os objetos.
+                let inverse = matrix.inverse()?;
+                let hit = obj.raycast(&ray.transformed(&inverse))?;
+
+                Some((obj, hit))
+            })
+            .min_by(|(_, a), (_, b)| a.t.total_cmp(&b.t))?;
+
+        Some(PickHit {
+            object_id: obj.id?,
+            mesh_index: hit.mesh_index,
+            mesh_name: obj.meshes().nth(hit.mesh_index)?.name.clone(),
+            triangle: hit.triangle,
+            point: ray.at(hit.t),
+        })
+    }
+
+    // Surface of the triangle `id`, read from the ID buffer of the last
+    // frame, seen at the point (x, y) of the screen. The attributes are
+    // interpolated where the ray through the point meets the triangle.
+    pub fn inspect(&self, id: PrimitiveId, x: f32, y: f32) -> Option<SurfaceInfo> {
+        let obj = self.objects.iter().find(|obj| obj.id == Some(id.object))?;
+        let mesh = obj.meshes().nth(id.mesh)?;
+        let (vertex_tri, texture_tri, normal_tri) = *mesh.triangles.get(id.triangle)?;
+
+        let ray = Ray::new(self.camera.get_pos(), self.screen_ray(x, y));
+        let vertices = IndexedMesh::vec3_list_from_indexed(vertex_tri, &obj.world.vertices);
+        let (_, u, v) = ray.intersect_triangle_plane(vertices)?;
+
+        // O centro dos pixeis da borda pode cair um pouco fora do triângulo.
+        let u = u.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0 - u);
+        let weights = [1.0 - u - v, u, v];
+        let interpolate =
+            |values: [Vec3; 3]| (0..3).fold(Vec3::zeros(), |sum, i| sum + values[i] * weights[i]);
+
+        let uv = obj.texture_vertices.as_ref().map_or(Vec3::zeros(), |uvs| {
+            interpolate(IndexedMesh::vec3_list_from_indexed(texture_tri, uvs))
+        });
+        let normal = interpolate(IndexedMesh::vec3_list_from_indexed(
+            normal_tri,
+            &obj.world.normals,
+        ));
+
+        let material_name = mesh
+            .texture_idx
+            .and_then(|idx| obj.textures.get(idx))
+            .map_or_else(|| String::from("default"), |texture| texture.name.clone());
+
+        Some(SurfaceInfo {
+            object_name: obj.name.clone(),
+            mesh_name: mesh.name.clone(),
+            material_name,
+            uv: Vec2::new(uv.x(), uv.y()),
+            normal: normal.normalized(),
+        })
+    }
+
+    // Caixa que envolve o objeto `object_id` no espaço de mundo.
+    pub fn object_bounds(&self, object_id: u32) -> Option<Aabb> {
+        let obj = self.objects.iter().find(|obj| obj.id == Some(object_id))?;
+
+        Some(obj.bounds.transformed(&self.object_world_matrix(obj)))
+    }
+
+    // Vértices, no espaço de mundo, de um triângulo de uma mesh do objeto
+    // `object_id`, indexados como em `PickHit`.
+    pub fn triangle_vertices(
+        &self,
+        object_id: u32,
+        mesh_index: usize,
+        triangle: usize,
+    ) -> Option<[Vec3; 3]> {
+        let obj = self.objects.iter().find(|obj| obj.id == Some(object_id))?;
+        let (vertex_tri, _, _) = obj.meshes().nth(mesh_index)?.triangles.get(triangle)?;
+        let matrix = self.object_world_matrix(obj);
+
+        Some(
+            IndexedMesh::vec3_list_from_indexed(*vertex_tri, &obj.vertices)
+                .map(|v| (matrix * v.as_vec4()).as_vec3()),
+        )
+    }
+
+    // Matriz de mundo de cada objeto, composta pelas transformações do grafo
+    // de cena.
+    fn object_world_matrices(&self) -> Vec<Matrix4> {
+        let matrices = self.graph.world_matrices();
+
+        self.objects
+            .iter()
+            .map(|obj| obj.node.map_or(Matrix4::identity(), |node| matrices[node]))
+            .collect()
+    }
+
+    // Leva para o espaço de mundo só a geometria dos objetos visíveis; os
+    // outros mantêm a do último frame em que foram transformados.
+    fn update_visible_world_geometry(&mut self, matrices: &[Matrix4], visible_objects: &[bool]) {
+        for ((obj, matrix), _) in self
+            .objects
+            .iter_mut()
+            .zip(matrices)
+            .zip(visible_objects)
+            .filter(|(_, visible)| **visible)
+        {
+            obj.update_world_geometry(*matrix);
+        }
+    }
+
+    // Matriz de mundo atual do objeto, mesmo que ele não tenha sido
+    // transformado no último frame por estar fora do frustum.
+    fn object_world_matrix(&self, obj: &Object) -> Matrix4 {
+        obj.node
+            .map_or(Matrix4::identity(), |node| self.graph.world_matrix(node))
+    }
+
+    // Enables or disables the normal map of a mesh of the object `object_id`.
+    pub fn set_normal_mapping(&mut self, object_id: u32, mesh_index: usize, enabled: bool) {
+        if let Some(obj) = self
+            .objects
+            .iter_mut()
+            .find(|obj| obj.id == Some(object_id))
+        {
+            obj.set_normal_mapping(mesh_index, enabled);
+        }
+    }
+
+    // Sets the texture filter of the material of a mesh of the object
+    // `object_id`, which changes every mesh with this material.
+    pub fn set_texture_filter(&mut self, object_id: u32, mesh_index: usize, filter: TextureFilter) {
+        if let Some(obj) = self
+            .objects
+            .iter_mut()
+            .find(|obj| obj.id == Some(object_id))
+        {
+            obj.set_texture_filter(mesh_index, filter);
+        }
+    }
+
+    // Sets the reflectance model of the material of a mesh of the object
+    // `object_id`, which changes every mesh with this material.
+    pub fn set_material_model(&mut self, object_id: u32, mesh_index: usize, model: MaterialModel) {
+        if let Some(obj) = self
+            .objects
+            .iter_mut()
+            .find(|obj| obj.id == Some(object_id))
+        {
+            obj.set_material_model(mesh_index, model);
+        }
+    }
+
     pub fn move_camera_direction(&mut self, dx: i32, dy: i32) {
         assert!(dx < self.width as _);
         assert!(dy < self.height as _);
@@ -814,14 +1564,40 @@ impl Scene {
 
     pub fn rotate_camera_by_offset(&mut self, dx: i32, dy: i32) {}
 
-    fn gen_transformation_matrix(&mut self) -> Matrix4 {
-        let n_x: f32 = self.width as _; // Qnt. de pixeis do comprimento da janela
-        let n_y: f32 = self.height as _; // Qnt. de pixeis da altura da janela
+    // Fills the canvas with the background seen by the camera. Must be
+    // called after the camera basis is updated.
+    fn draw_background(&self, canvas: &mut Canvas) {
+        let width = self.width as f32;
 
-        let n = self.camera.get_min_view_dist();
-        let f = self.camera.get_max_view_dist();
+        // Ângulo coberto por cada pixel, que escolhe o nível de mip do fundo.
+        let window = self.camera.get_window();
+        let footprint = 2.0 * (window.right / self.camera.get_min_view_dist().abs()).atan() / width;
 
-        let camera_window = self.camera.get_window();
+        if let Background::Color(color) = *self.background {
+            canvas.clear_with_color(color);
+            return;
+        }
+
+        // A origem da tela é no canto inferior esquerdo.
+        canvas
+            .clear_with_background(|x, y| self.background.sample(self.screen_ray(x, y), footprint));
+    }
+
+    // Retorna a matriz que leva as coordenadas de mundo para as coordenadas
+    // de clip da câmera e a matriz ViewPort, aplicada após a clipagem,
+    // para uma janela de `width` x `height` pixeis.
+    fn gen_transformation_matrices(
+        camera: &mut Camera,
+        width: usize,
+        height: usize,
+    ) -> (Matrix4, Matrix4) {
+        let n_x: f32 = width as _; // Qnt. de pixeis do comprimento da janela
+        let n_y: f32 = height as _; // Qnt. de pixeis da altura da janela
+
+        let n = camera.get_min_view_dist();
+        let f = camera.get_max_view_dist();
+
+        let camera_window = camera.get_window();
         let r = camera_window.right;
         let l = camera_window.left;
 
@@ -829,46 +1605,17 @@ impl Scene {
         let b = camera_window.bottom;
 
         // A matriz View, que transforma pontos do espaço do mundo
-        // para o espaço da câmera, é obtida da seguinte forma:
-        // Primeiro, encontramos a matriz de transformação que translada
-        // a câmera para a origem do mundo utilizando a posição da câmera.
-        // Com as origens alinhadas, encontramos (u,v,w), os três vetores unitários
-        // que formam a base da câmera e que não estão alinhados com as coordenadas
-        // de mundo (são o x,y,z da câmera).
-        // A matriz de rotação que alinha os eixos da câmera com o mundo é obtida
-        // pela transposta da matriz da base da câmera, que aplica uma mudança de base
-        // do mundo para a câmera.
-        // Multiplicando a translação com a rotação, obtemos a matriz View.
-        let matrix_cam = self.camera.gen_matrix();
+        // para o espaço da câmera.
+        let matrix_cam = camera.gen_matrix();
 
         // A matriz de perspectiva mapeia o volume de visão perspectiva,
         // que é o frustum, para o volume de visão ortográfica, que é
         // uma caixa alinhada aos eixos a partir do plano near até o far.
-        // Ela mantém os pontos no plano z = n inalterados e mapeia
-        // o grande retângulo em z = f, na parte de trás do volume de perspectiva,
-        // para o pequeno retângulo em z = f, na parte de trás do volume ortográfico.
-        let persp = Matrix4::new([
-            [n, 0.0, 0.0, 0.0],
-            [0.0, n, 0.0, 0.0],
-            [0.0, 0.0, (n + f), -(n * f)],
-            [0.0, 0.0, 1.0, 0.0],
-        ]);
-        // Note que é necessário fazer a desomogeneização após as transformações.
-
         // Do volume de visualização ortográfico para o volume de visualização
-        // canônico (o cubo [-1,1]):
-        // Fazemos a redimensionalização do volume ortográfico para o canônico.
-        // Para isso, basta apenas alterar os limites dos volumes da matriz
-        // dessa operação, com os limites do volume de origem sendo o retângulo
-        // que se estende do near até o far plane, e os limites do volume de destino
-        // sendo o cubo canônico.
-        // Esse processo é similar a redimensionalização de janela da matriz ViewPort.
-        let matrix_orth = Matrix4::new([
-            [2.0 / (r - l), 0.0, 0.0, -(r + l) / (r - l)],
-            [0.0, 2.0 / (t - b), 0.0, -(t + b) / (t - b)],
-            [0.0, 0.0, 2.0 / (n - f), -(n + f) / (n - f)],
-            [0.0, 0.0, 0.0, 1.0],
-        ]);
+        // canônico (o cubo [-1,1]), fazemos a redimensionalização dos limites
+        // da caixa para os do cubo, de forma similar à matriz ViewPort.
+        // Note que é necessário fazer a desomogeneização após as transformações.
+        let matrix_proj = Matrix4::perspective(l, r, b, t, n, f);
 
         // Do espaço canônico [-1,1] para o espaço de tela (janela):
         //        ___________________(1,1)         ___________________(width,height)
@@ -893,360 +1640,841 @@ impl Scene {
             [0.0, 0.0, 0.0, 1.0],
         ]);
 
-        let matrix_transf = matrix_viewport * matrix_orth * persp * matrix_cam;
+        let matrix_clip = matrix_proj * matrix_cam;
 
-        matrix_transf
+        (matrix_clip, matrix_viewport)
     }
 
     pub fn render(&mut self, canvas: &mut Canvas) {
-        canvas.clear();
+        match self.shading_model {
+            ShadingModel::Phong => self.render_with_shaders(canvas, &PhongShader, &PhongShader),
+            ShadingModel::Flat => self.render_with_shaders(canvas, &FlatShader, &FlatShader),
+            ShadingModel::Unlit => self.render_with_shaders(canvas, &UnlitShader, &UnlitShader),
+            ShadingModel::Normal => self.render_with_shaders(canvas, &NormalShader, &NormalShader),
+        }
+    }
 
-        let matrix_transf = self.gen_transformation_matrix();
+    // Renders the scene running `vertex_shader` on every vertex of the meshes
+    // and `fragment_shader` on every visible fragment.
+    pub fn render_with_shaders<VS, FS>(
+        &mut self,
+        canvas: &mut Canvas,
+        vertex_shader: &VS,
+        fragment_shader: &FS,
+    ) where
+        VS: VertexShader,
+        FS: FragmentShader<Varyings = VS::Varyings>,
+    {
+        let (matrix_clip, matrix_viewport) =
+            Self::gen_transformation_matrices(&mut self.camera, self.width, self.height);
+
+        self.draw_background(canvas);
+        self.update_environment_light();
 
         let camera_pos = self.camera.get_pos();
 
-        let func_planes = self.camera.gen_view_planes();
-
-        for obj in self.objects.iter_mut() {
-            let obj_vertices = &obj.vertices;
-            let obj_normals = &obj.normals_vertices;
-            let obj_texture_uv = obj.texture_vertices.as_ref().unwrap();
+        // The objects are culled with their volumes placed by the world
+        // matrices, so only the visible ones have their geometry transformed.
+        let frustum = self
+            .frustum_culling
+            .then(|| Frustum::from_matrix(&matrix_clip));
+        let world_matrices = self.object_world_matrices();
+        let visible_objects =
+            Self::visible_objects(&self.objects, &world_matrices, frustum.as_ref());
+        self.update_visible_world_geometry(&world_matrices, &visible_objects);
+
+        let mut stats = RenderStats::default();
+        for (obj, visible) in self.objects.iter().zip(visible_objects.iter()) {
+            if *visible {
+                stats.objects_drawn += 1;
+            } else {
+                stats.objects_culled += 1;
+                stats.meshes_culled += obj.opaque_meshes.len() + obj.transparent_meshes.len();
+            }
+        }
 
-            // Calcular VertexAttributes aq para remover cálculos redundantes
+        self.update_shadow_maps();
+        let uniforms = Uniforms {
+            camera_position: camera_pos,
+            ambient_light: self.ambient_light,
+            lights: &self.lights,
+            shadow_maps: self.shadow_cache.as_ref().map_or(&[], |cache| &cache.maps),
+            environment: self
+                .environment_light
+                .as_ref()
+                .map(|(_, light)| light.clone())
+                .filter(|_| self.environment_lighting),
+            environment_intensity: self.environment_intensity,
+        };
 
-            for (vertex, visual_info) in
-                obj.vertices.iter().zip(obj.vertices_visual_info.iter_mut())
+        // With OIT the canvas blends the transparent fragments in depth
+        // order by itself.
+        if !canvas.oit_enabled() {
+            for (obj, _) in self
+                .objects
+                .iter_mut()
+                .zip(visible_objects.iter())
+                .filter(|(_, visible)| **visible)
             {
-                let eye_dir = *vertex - camera_pos;
+                let obj_vertices = &obj.world.vertices;
 
-                visual_info.light = (*vertex - self.light_source).normalized();
-                visual_info.eye = eye_dir.normalized();
-                visual_info.depth = eye_dir.norm() as _;
-                visual_info.halfway = (visual_info.light + visual_info.eye).normalized();
-            }
+                for obj_mesh in obj.transparent_meshes.iter_mut() {
+                    // sort the triangles of the transparent meshes (painter algorithm)
+                    // TODO: essa ordenação precisa ser aplicada a todos os triangulos
+                    // de todos os objetos transparentes de maneira absoluta.
+                    // Talvez seja possivel apenas manter essa ordenação atual, relativa
+                    // a cada mesh, e complementar com a ordenação prévia das meshes
+                    // transparentes pela sua posição.
 
-            canvas.enable_depth_update();
-            for obj_mesh in obj.opaque_meshes.iter() {
-                let mesh_texture_idx = obj_mesh.texture_idx.unwrap();
-                // TODO: ta meio feio isso aq, tem que embelezar.
-                // criar um iterador no futuro tlvz
-                for (vertex_tri_idx, texture_tri_idx, normal_tri_idx) in obj_mesh.triangles.iter() {
-                    let indexed_tri_vertex = vertex_tri_idx.clone();
-                    let indexed_tri_normal = normal_tri_idx.clone();
-                    let indexed_tri_texture = texture_tri_idx.clone();
-
-                    let tri_vertices =
-                        IndexedMesh::vec3_list_from_indexed(indexed_tri_vertex, obj_vertices);
-                    let tri_normals =
-                        IndexedMesh::vec3_list_from_indexed(indexed_tri_normal, obj_normals);
-                    let tri_textures =
-                        IndexedMesh::vec3_list_from_indexed(indexed_tri_texture, obj_texture_uv);
-
-                    let a_vertex_idx = indexed_tri_vertex[0];
-                    let b_vertex_idx = indexed_tri_vertex[1];
-                    let c_vertex_idx = indexed_tri_vertex[2];
-
-                    let a_vertex = tri_vertices[0];
-                    let b_vertex = tri_vertices[1];
-                    let c_vertex = tri_vertices[2];
-
-                    let a_normal = tri_normals[0];
-                    let b_normal = tri_normals[1];
-                    let c_normal = tri_normals[2];
-
-                    let a_texture_coord = tri_textures[0];
-                    let b_texture_coord = tri_textures[1];
-                    let c_texture_coord = tri_textures[2];
-
-                    let a_light = obj.vertices_visual_info[a_vertex_idx].light;
-                    let b_light = obj.vertices_visual_info[b_vertex_idx].light;
-                    let c_light = obj.vertices_visual_info[c_vertex_idx].light;
-
-                    //let a_eye = obj.vertices_visual_info[a_vertex_idx].eye;
-                    //let b_eye = obj.vertices_visual_info[b_vertex_idx].eye;
-                    //let c_eye = obj.vertices_visual_info[c_vertex_idx].eye;
-
-                    let a_halfway = obj.vertices_visual_info[a_vertex_idx].halfway;
-                    let b_halfway = obj.vertices_visual_info[b_vertex_idx].halfway;
-                    let c_halfway = obj.vertices_visual_info[c_vertex_idx].halfway;
-
-                    let a_depth = obj.vertices_visual_info[a_vertex_idx].depth;
-                    let b_depth = obj.vertices_visual_info[b_vertex_idx].depth;
-                    let c_depth = obj.vertices_visual_info[c_vertex_idx].depth;
-
-                    let a_attr = VertexAttributes::new(
-                        Vec2::new(0., 0.),
-                        //Color::Green,
-                        a_depth,
-                        a_normal,
-                        a_light,
-                        //a_eye,
-                        a_halfway,
-                        a_texture_coord,
-                    );
-
-                    let b_attr = VertexAttributes::new(
-                        Vec2::new(0., 0.),
-                        //Color::Green,
-                        b_depth,
-                        b_normal,
-                        b_light,
-                        //b_eye,
-                        b_halfway,
-                        b_texture_coord,
-                    );
-
-                    let c_attr = VertexAttributes::new(
-                        Vec2::new(0., 0.),
-                        //Color::Green,
-                        c_depth,
-                        c_normal,
-                        c_light,
-                        //c_eye,
-                        c_halfway,
-                        c_texture_coord,
-                    );
-
-                    let original_tri = Triangle::new(
-                        [a_vertex, b_vertex, c_vertex],
-                        [a_attr, b_attr, c_attr],
-                        //Color::Green,
-                    );
-
-                    let tri_normal = Triangle::calc_normal(&original_tri);
-                    let tri_eye = camera_pos - original_tri.get_center();
-
-                    // Back-face culling
-                    if tri_eye.dot(tri_normal) <= 0.0 {
-                        // Renderizamos modelos poligonais fechados em que faces que
-                        // não estão viradas para a câmera são sobrepostas por faces
-                        // que estão viradas para a câmera. Portanto, se o ângulo entre
-                        // o vetor que sai do triângulo em direção à câmera e a normal
-                        // do triângulo for maior do que 90 graus, o triângulo não é renderizado.
-                        continue;
-                    }
+                    obj_mesh.draw_order.sort_by(|a, b| {
+                        let (a_vert_tri, _, _) = &obj_mesh.triangles[*a];
+                        let (b_vert_tri, _, _) = &obj_mesh.triangles[*b];
 
-                    // TODO: substituir por heapless::Vec
-                    // 12 é o número máximo possível de triângulos gerados após clipagem entre os 6 planos.
-                    let mut clipped_triangles: [Triangle; 12] = [Triangle::zeroed(); 12];
-                    let clipped_count = original_tri
-                        .clip_against_planes(&func_planes, clipped_triangles.as_mut_slice());
-                    for clipped_tri in clipped_triangles[..clipped_count].iter_mut() {
-                        // TODO: (performance) mover para esse laço a criação dos VertexAttr's.
-                        // 1) O caso onde o triangulo nao eh clipado -> VisualInfo já está
-                        // pré-calculado.
-                        // 2) O caso onde o triangulo nao eh totalmente clipado -> Recalcular
-                        // VisualInfo.
-                        // Justificativa: A maioria dos triangulos do mundo ou será totalmente
-                        // clippada ou nao será clippada. Por isso, teoricamente, vale a pena
-                        // recalcular os a VisualInfo triangulos parcialmente clippados, para
-                        // evitar as cópias dessas structs na funcao de clipping
-
-                        // Aplica todas as transformações que levam o ponto de coordenadas
-                        // de mundo (clipped_tri.vertices) para coordenadas de janela.
-                        let a_vec4 = matrix_transf * clipped_tri.vertices[0].as_vec4();
-                        let b_vec4 = matrix_transf * clipped_tri.vertices[1].as_vec4();
-                        let c_vec4 = matrix_transf * clipped_tri.vertices[2].as_vec4();
-
-                        let a_w = a_vec4.get_w();
-                        let b_w = b_vec4.get_w();
-                        let c_w = c_vec4.get_w();
+                        let a_verts =
+                            IndexedMesh::vec3_list_from_indexed(*a_vert_tri, obj_vertices);
 
-                        // Aplica a desomogeneização.
-                        let a_coord = a_vec4.as_vec2() / a_w;
-                        let b_coord = b_vec4.as_vec2() / b_w;
-                        let c_coord = c_vec4.as_vec2() / c_w;
-
-                        let clip_tri_vert_attr = &mut clipped_tri.vertices_attr;
-                        clip_tri_vert_attr[0].screen_coord = a_coord;
-                        clip_tri_vert_attr[1].screen_coord = b_coord;
-                        clip_tri_vert_attr[2].screen_coord = c_coord;
-                        // vis'ao ortogonal
-                        /*
-                        let camera_dir = self.camera.get_direction().normalized();
-                        let a_depth: f32 = camera_dir.dot(camera_pos - tri.points[0]).abs() as _;
-                        let b_depth: f32 = camera_dir.dot(camera_pos - tri.points[1]).abs() as _;
-                        let c_depth: f32 = camera_dir.dot(camera_pos - tri.points[2]).abs() as _;
-                        */
-
-                        let mesh_texture = match obj.textures.get(mesh_texture_idx) {
-                            Some(texture) => texture,
-                            None => &Texture::default(),
-                        };
+                        let b_verts =
+                            IndexedMesh::vec3_list_from_indexed(*b_vert_tri, obj_vertices);
 
-                        canvas.draw_triangle_with_attributes(
-                            &clip_tri_vert_attr[0],
-                            &clip_tri_vert_attr[1],
-                            &clip_tri_vert_attr[2],
-                            mesh_texture,
-                            None,
-                        );
-                    }
+                        let a_center = (a_verts[0] + a_verts[1] + a_verts[2]) / 3.0;
+                        let b_center = (b_verts[0] + b_verts[1] + b_verts[2]) / 3.0;
+
+                        let a_depth = a_center.dist(camera_pos);
+                        let b_depth = b_center.dist(camera_pos);
+
+                        a_depth.total_cmp(&b_depth).reverse()
+                    });
                 }
             }
+        }
 
-            canvas.disable_depth_update();
+        let default_texture = Texture::default();
+        let mut projected_triangles: Vec<ProjectedTriangle<VS::Varyings>> = Vec::new();
 
-            for obj_mesh in obj.transparent_meshes.iter_mut() {
-                let mesh_texture_idx = obj_mesh.texture_idx.unwrap();
+        let projection = Projection {
+            matrix_clip: &matrix_clip,
+            matrix_viewport: &matrix_viewport,
+            guard_band: if self.guard_band_enabled {
+                Self::GUARD_BAND
+            } else {
+                1.0
+            },
+            uniforms: &uniforms,
+        };
 
-                // sort the triangles of the transparent meshes (painter algorithm)
-                // TODO: essa ordenação precisa ser aplicada a todos os triangulos
-                // de todos os objetos transparentes de maneira absoluta.
-                // Talvez seja possivel apenas manter essa ordenação atual, relativa
-                // a cada mesh, e complementar com a ordenação prévia das meshes
-                // transparentes pela sua posição.
+        // The transparent meshes are drawn after the opaque meshes of every
+        // object. Otherwise an opaque mesh of a later object could cover
+        // fragments already blended (or, with OIT, already stored in the
+        // A-buffer, which the depth test can't remove) of a transparent mesh
+        // behind it.
+        let visible_meshes = |opaque: bool| {
+            self.objects
+                .iter()
+                .zip(visible_objects.iter())
+                .filter(|(_, visible)| **visible)
+                .flat_map(move |(obj, _)| {
+                    obj.meshes()
+                        .enumerate()
+                        .filter(move |(mesh_index, _)| {
+                            (*mesh_index < obj.opaque_meshes.len()) == opaque
+                        })
+                        .map(move |(mesh_index, mesh)| (obj, mesh_index, mesh))
+                })
+        };
 
-                obj_mesh.triangles.as_mut_slice().sort_by(|a, b| {
-                    let (a_vert_tri, _, _) = a;
-                    let (b_vert_tri, _, _) = b;
+        for (obj, mesh_index, obj_mesh) in visible_meshes(true) {
+            if !Self::is_mesh_visible(obj, obj_mesh, frustum.as_ref()) {
+                stats.meshes_culled += 1;
+                continue;
+            }
 
-                    let a_verts = IndexedMesh::vec3_list_from_indexed(*a_vert_tri, obj_vertices);
+            stats.meshes_drawn += 1;
+            Self::project_mesh(
+                obj,
+                obj_mesh,
+                mesh_index,
+                &default_texture,
+                true,
+                vertex_shader,
+                &projection,
+                &mut projected_triangles,
+            );
+        }
 
-                    let b_verts = IndexedMesh::vec3_list_from_indexed(*b_vert_tri, obj_vertices);
+        if self.depth_prepass {
+            // The opaque triangles are drawn twice: the first pass only writes
+            // the depth buffer and the second one shades just the fragments
+            // whose depth is equal to the closest one, so the overdraw of the
+            // opaque meshes doesn't cost any texture fetch nor shading.
+            let mut depth_pass: Vec<ProjectedTriangle<VS::Varyings>> = projected_triangles
+                .iter()
+                .map(|tri| ProjectedTriangle {
+                    color_write: false,
+                    ..*tri
+                })
+                .collect();
+
+            for tri in projected_triangles.iter_mut() {
+                tri.depth_update = false;
+                tri.depth_test = DepthTest::LessEqual;
+            }
 
-                    let a_center = (a_verts[0] + a_verts[1] + a_verts[2]) / 3.0;
-                    let b_center = (b_verts[0] + b_verts[1] + b_verts[2]) / 3.0;
+            depth_pass.append(&mut projected_triangles);
+            projected_triangles = depth_pass;
+        }
 
-                    let a_depth = a_center.dist(camera_pos);
-                    let b_depth = b_center.dist(camera_pos);
+        for (obj, mesh_index, obj_mesh) in visible_meshes(false) {
+            if !Self::is_mesh_visible(obj, obj_mesh, frustum.as_ref()) {
+                stats.meshes_culled += 1;
+                continue;
+            }
 
-                    a_depth.total_cmp(&b_depth).reverse()
-                });
+            stats.meshes_drawn += 1;
+            Self::project_mesh(
+                obj,
+                obj_mesh,
+                mesh_index,
+                &default_texture,
+                false,
+                vertex_shader,
+                &projection,
+                &mut projected_triangles,
+            );
+        }
 
-                for (vertex_tri_idx, texture_tri_idx, normal_tri_idx) in obj_mesh.triangles.iter() {
-                    let indexed_tri_vertex = vertex_tri_idx.clone();
-                    let indexed_tri_normal = normal_tri_idx.clone();
-                    let indexed_tri_texture = texture_tri_idx.clone();
-
-                    let tri_vertices =
-                        IndexedMesh::vec3_list_from_indexed(indexed_tri_vertex, obj_vertices);
-                    let tri_normals =
-                        IndexedMesh::vec3_list_from_indexed(indexed_tri_normal, obj_normals);
-                    let tri_textures =
-                        IndexedMesh::vec3_list_from_indexed(indexed_tri_texture, obj_texture_uv);
-
-                    let a_vertex_idx = indexed_tri_vertex[0];
-                    let b_vertex_idx = indexed_tri_vertex[1];
-                    let c_vertex_idx = indexed_tri_vertex[2];
-
-                    let a_vertex = tri_vertices[0];
-                    let b_vertex = tri_vertices[1];
-                    let c_vertex = tri_vertices[2];
-
-                    let a_normal = tri_normals[0];
-                    let b_normal = tri_normals[1];
-                    let c_normal = tri_normals[2];
-
-                    let a_texture_coord = tri_textures[0];
-                    let b_texture_coord = tri_textures[1];
-                    let c_texture_coord = tri_textures[2];
-
-                    let a_light = obj.vertices_visual_info[a_vertex_idx].light;
-                    let b_light = obj.vertices_visual_info[b_vertex_idx].light;
-                    let c_light = obj.vertices_visual_info[c_vertex_idx].light;
-
-                    //let a_eye = obj.vertices_visual_info[a_vertex_idx].eye;
-                    //let b_eye = obj.vertices_visual_info[b_vertex_idx].eye;
-                    //let c_eye = obj.vertices_visual_info[c_vertex_idx].eye;
-
-                    let a_halfway = obj.vertices_visual_info[a_vertex_idx].halfway;
-                    let b_halfway = obj.vertices_visual_info[b_vertex_idx].halfway;
-                    let c_halfway = obj.vertices_visual_info[c_vertex_idx].halfway;
-
-                    let a_depth = obj.vertices_visual_info[a_vertex_idx].depth;
-                    let b_depth = obj.vertices_visual_info[b_vertex_idx].depth;
-                    let c_depth = obj.vertices_visual_info[c_vertex_idx].depth;
-
-                    let a_attr = VertexAttributes::new(
-                        Vec2::new(0., 0.),
-                        //Color::Green,
-                        a_depth,
-                        a_normal,
-                        a_light,
-                        //a_eye,
-                        a_halfway,
-                        a_texture_coord,
-                    );
-
-                    let b_attr = VertexAttributes::new(
-                        Vec2::new(0., 0.),
-                        //Color::Green,
-                        b_depth,
-                        b_normal,
-                        b_light,
-                        //b_eye,
-                        b_halfway,
-                        b_texture_coord,
-                    );
-
-                    let c_attr = VertexAttributes::new(
-                        Vec2::new(0., 0.),
-                        //Color::Green,
-                        c_depth,
-                        c_normal,
-                        c_light,
-                        //c_eye,
-                        c_halfway,
-                        c_texture_coord,
-                    );
-
-                    let original_tri = Triangle::new(
-                        [a_vertex, b_vertex, c_vertex],
-                        [a_attr, b_attr, c_attr],
-                        //Color::Green,
-                    );
-
-                    // TODO: substituir por heapless::Vec
-                    let mut clipped_triangles: [Triangle; 12] = [Triangle::zeroed(); 12];
-                    let clipped_count = original_tri
-                        .clip_against_planes(&func_planes, clipped_triangles.as_mut_slice());
-                    for clipped_tri in clipped_triangles[..clipped_count].iter_mut() {
-                        // Aplica todas as transformações que levam o ponto de coordenadas
-                        // de mundo (clipped_tri.vertices) para coordenadas de janela.
-                        let a_vec4 = matrix_transf * clipped_tri.vertices[0].as_vec4();
-                        let b_vec4 = matrix_transf * clipped_tri.vertices[1].as_vec4();
-                        let c_vec4 = matrix_transf * clipped_tri.vertices[2].as_vec4();
-
-                        let a_w = a_vec4.get_w();
-                        let b_w = b_vec4.get_w();
-                        let c_w = c_vec4.get_w();
+        canvas.draw_projected_triangles(&projected_triangles, fragment_shader, &uniforms);
+        canvas.resolve();
 
-                        // Aplica a desomogeneização.
-                        let a_coord = a_vec4.as_vec2() / a_w;
-                        let b_coord = b_vec4.as_vec2() / b_w;
-                        let c_coord = c_vec4.as_vec2() / c_w;
-
-                        let clip_tri_vert_attr = &mut clipped_tri.vertices_attr;
-                        clip_tri_vert_attr[0].screen_coord = a_coord;
-                        clip_tri_vert_attr[1].screen_coord = b_coord;
-                        clip_tri_vert_attr[2].screen_coord = c_coord;
-
-                        /*
-                             // vis'ao ortogonal
-                             let camera_dir = self.camera.get_direction().normalized();
-                             let a_depth: f32 = camera_dir.dot(camera_pos - tri.points[0]).abs() as _;
-                             let b_depth: f32 = camera_dir.dot(camera_pos - tri.points[1]).abs() as _;
-                             let c_depth: f32 = camera_dir.dot(camera_pos - tri.points[2]).abs() as _;
-                        */
-
-                        let mesh_texture = match obj.textures.get(mesh_texture_idx) {
-                            Some(texture) => texture,
-                            None => &Texture::default(),
-                        };
+        self.render_stats = stats;
+    }
+
+    // Whether each object, placed by its matrix of `world_matrices`, may be
+    // inside the frustum. Without the frustum, when the culling is disabled,
+    // all of them are.
+    fn visible_objects(
+        objects: &[Object],
+        world_matrices: &[Matrix4],
+        frustum: Option<&Frustum>,
+    ) -> Vec<bool> {
+        objects
+            .iter()
+            .zip(world_matrices)
+            .map(|(obj, matrix)| {
+                frustum.is_none_or(|frustum| {
+                    frustum.intersects_bounds(matrix, &obj.bounding_sphere, &obj.bounds)
+                })
+            })
+            .collect()
+    }
+
+    fn is_mesh_visible(obj: &Object, mesh: &IndexedMesh, frustum: Option<&Frustum>) -> bool {
+        frustum.is_none_or(|frustum| {
+            frustum.intersects_bounds(&obj.world.matrix, &mesh.bounding_sphere, &mesh.bounds)
+        })
+    }
+
+    // Centro e raio de uma esfera que envolve todos os objetos da cena,
+    // calculada pelas caixas posicionadas pelas matrizes de mundo, já que a
+    // geometria dos objetos fora do frustum pode estar desatualizada.
+    fn bounding_sphere(&self) -> Option<(Vec3, f32)> {
+        let bounds = self
+            .objects
+            .iter()
+            .zip(self.object_world_matrices())
+            .map(|(obj, matrix)| obj.bounds.transformed(&matrix))
+            .reduce(|bounds, obj_bounds| bounds.union(&obj_bounds))?;
+
+        let center = bounds.center();
+        Some((center, bounds.half_extents().norm()))
+    }
 
-                        canvas.draw_triangle_with_attributes(
-                            &clip_tri_vert_attr[0],
-                            &clip_tri_vert_attr[1],
-                            &clip_tri_vert_attr[2],
-                            mesh_texture,
-                            None,
+    // Renders the shadow map of every light that casts shadows, in the
+    // order of `self.lights`.
+    // Renders the shadow maps again when the cached ones are out of date.
+    fn update_shadow_maps(&mut self) {
+        if !self.shadows_enabled {
+            self.shadow_cache = None;
+            return;
+        }
+
+        let world_matrices = self.object_world_matrices();
+        let casters: Vec<_> = self
+            .lights
+            .iter()
+            .map(|light| light.cast_shadows.then_some(light.kind))
+            .collect();
+
+        let up_to_date = self.shadow_cache.as_ref().is_some_and(|cache| {
+            cache.size == self.shadow_map_size
+                && cache.casters == casters
+                && cache.world_matrices == world_matrices
+        });
+
+        if !up_to_date {
+            self.shadow_cache = Some(ShadowCache {
+                casters,
+                world_matrices,
+                size: self.shadow_map_size,
+                maps: self.render_shadow_maps(),
+            });
+        }
+    }
+
+    fn render_shadow_maps(&mut self) -> Vec<Option<ShadowMap>> {
+        let Some((center, radius)) = self.bounding_sphere() else {
+            return vec![];
+        };
+        let radius = radius.max(1.0);
+
+        let lights = self.lights.clone();
+
+        lights
+            .iter()
+            .map(|light| {
+                if !light.cast_shadows {
+                    return None;
+                }
+
+                let shadow_map = match light.kind {
+                    LightKind::Directional { direction } => {
+                        // Uma câmera distante com um campo de visão estreito,
+                        // enquadrando a cena, aproxima a projeção ortográfica.
+                        let dist = 4.0 * radius;
+                        let fov = 2.0 * (radius / dist).asin().to_degrees();
+                        let position = center - direction.normalized() * dist;
+
+                        let mut camera = Self::shadow_camera(
+                            position,
+                            direction,
+                            fov,
+                            -(dist - radius),
+                            -(dist + radius),
                         );
+                        let face = self.render_shadow_face(&mut camera);
+
+                        ShadowMap::new(position, self.shadow_map_size, fov, vec![face])
+                    }
+
+                    LightKind::Spot {
+                        position,
+                        direction,
+                        range,
+                        outer_angle,
+                        ..
+                    } => {
+                        let fov = (2.0 * outer_angle).clamp(1.0, 170.0);
+
+                        let mut camera =
+                            Self::shadow_camera(position, direction, fov, -1.0, -range.max(2.0));
+                        let face = self.render_shadow_face(&mut camera);
+
+                        ShadowMap::new(position, self.shadow_map_size, fov, vec![face])
                     }
+
+                    LightKind::Point { position, range } => {
+                        let faces = CUBE_FACES
+                            .iter()
+                            .map(|(direction, up)| {
+                                let mut camera = Self::shadow_camera(
+                                    position,
+                                    Vec3::new(*direction),
+                                    90.0,
+                                    -1.0,
+                                    -range.max(2.0),
+                                );
+                                camera.set_up_direction(Vec3::new(*up));
+
+                                self.render_shadow_face(&mut camera)
+                            })
+                            .collect();
+
+                        ShadowMap::new(position, self.shadow_map_size, 90.0, faces)
+                    }
+                };
+
+                Some(shadow_map)
+            })
+            .collect()
+    }
+
+    fn shadow_camera(position: Vec3, direction: Vec3, fov: f32, near: f32, far: f32) -> Camera {
+        let mut camera = Camera::with_frustum(position, direction, 1.0, fov, near, far);
+
+        // O vetor "up" não pode ser paralelo à direção da câmera.
+        if direction.normalized().y().abs() > 0.99 {
+            camera.set_up_direction(Vec3::new([0.0, 0.0, 1.0]));
+        }
+
+        camera
+    }
+
+    // Depth-only pass of the opaque meshes, seen from `camera`.
+    fn render_shadow_face(&mut self, camera: &mut Camera) -> ShadowFace {
+        let size = self.shadow_map_size;
+        let (matrix_clip, matrix_viewport) = Self::gen_transformation_matrices(camera, size, size);
+
+        let uniforms = Uniforms {
+            camera_position: camera.get_pos(),
+            ambient_light: Vec3::zeros(),
+            lights: &[],
+            shadow_maps: &[],
+            environment: None,
+            environment_intensity: 0.0,
+        };
+
+        let projection = Projection {
+            matrix_clip: &matrix_clip,
+            matrix_viewport: &matrix_viewport,
+            guard_band: Self::GUARD_BAND,
+            uniforms: &uniforms,
+        };
+
+        let default_texture = Texture::default();
+        let mut projected_triangles = Vec::new();
+
+        let frustum = self
+            .frustum_culling
+            .then(|| Frustum::from_matrix(&matrix_clip));
+        // Os objetos fora do frustum da câmera também projetam sombras: são
+        // transformados aqui os que estão no volume da luz.
+        let world_matrices = self.object_world_matrices();
+        let visible_objects =
+            Self::visible_objects(&self.objects, &world_matrices, frustum.as_ref());
+        self.update_visible_world_geometry(&world_matrices, &visible_objects);
+
+        for (obj, _) in self
+            .objects
+            .iter()
+            .zip(visible_objects.iter())
+            .filter(|(_, visible)| **visible)
+        {
+            for (mesh_index, obj_mesh) in obj.opaque_meshes.iter().enumerate() {
+                if !Self::is_mesh_visible(obj, obj_mesh, frustum.as_ref()) {
+                    continue;
                 }
+
+                Self::project_mesh(
+                    obj,
+                    obj_mesh,
+                    mesh_index,
+                    &default_texture,
+                    true,
+                    &UnlitShader,
+                    &projection,
+                    &mut projected_triangles,
+                );
+            }
+        }
+
+        for tri in projected_triangles.iter_mut() {
+            tri.color_write = false;
+        }
+
+        self.shadow_canvas.init_depth(f32::INFINITY);
+        self.shadow_canvas
+            .draw_projected_triangles(&projected_triangles, &UnlitShader, &uniforms);
+
+        ShadowFace::new(
+            matrix_clip,
+            matrix_viewport,
+            self.shadow_canvas.depth_frame().to_vec(),
+        )
+    }
+
+    // Executa o vertex shader e aplica o back-face culling (apenas nas meshes
+    // opacas), a clipagem e a projeção nos triângulos da mesh, adicionando o
+    // resultado em `projected`. `mesh_index` é o índice da mesh na ordem de
+    // `Object::meshes`, escrito com o objeto e o triângulo no ID buffer.
+    #[allow(clippy::too_many_arguments)]
+    fn project_mesh<'a, VS: VertexShader>(
+        obj: &'a Object,
+        obj_mesh: &IndexedMesh,
+        mesh_index: usize,
+        default_texture: &'a Texture,
+        opaque: bool,
+        vertex_shader: &VS,
+        projection: &Projection,
+        projected: &mut Vec<ProjectedTriangle<'a, VS::Varyings>>,
+    ) {
+        let obj_vertices = &obj.world.vertices;
+        let obj_normals = &obj.world.normals;
+        let obj_tangents = &obj.world.tangents;
+        let obj_texture_uv = obj.texture_vertices.as_ref().unwrap();
+
+        let camera_pos = projection.uniforms.camera_position;
+
+        let mesh_texture_idx = obj_mesh.texture_idx.unwrap();
+        let mesh_texture = obj
+            .textures
+            .get(mesh_texture_idx)
+            .unwrap_or(default_texture);
+
+        // Saída do vertex shader de cada vértice da mesh, indexada pelos
+        // índices de posição, textura e normal, para que os vértices
+        // compartilhados pelos triângulos sejam sombreados uma só vez.
+        let mut shaded_vertices: HashMap<[usize; 3], VertexOutput<VS::Varyings>> =
+            HashMap::with_capacity(obj_mesh.triangles.len());
+
+        // TODO: ta meio feio isso aq, tem que embelezar.
+        // criar um iterador no futuro tlvz
+        for (triangle, (vertex_tri_idx, texture_tri_idx, normal_tri_idx)) in obj_mesh
+            .draw_order
+            .iter()
+            .map(|&triangle| (triangle, &obj_mesh.triangles[triangle]))
+        {
+            let shaded: [_; 3] = std::array::from_fn(|i| {
+                let key = [vertex_tri_idx[i], texture_tri_idx[i], normal_tri_idx[i]];
+
+                *shaded_vertices.entry(key).or_insert_with(|| {
+                    let vertex = VertexInput {
+                        position: obj_vertices[vertex_tri_idx[i]],
+                        normal: obj_normals[normal_tri_idx[i]],
+                        // A tangente nula desativa o normal map.
+                        tangent: if obj_mesh.normal_mapping {
+                            obj_tangents[normal_tri_idx[i]]
+                        } else {
+                            Vec4::zeros()
+                        },
+                        texture_coord: obj_texture_uv[texture_tri_idx[i]],
+                    };
+
+                    vertex_shader.shade(&vertex, projection.uniforms)
+                })
+            });
+
+            let [a, b, c] = shaded;
+            let original_tri = Triangle::new(
+                [a.position, b.position, c.position],
+                [a.varyings, b.varyings, c.varyings],
+            );
+
+            if opaque {
+                let tri_normal = Triangle::calc_normal(&original_tri);
+                let tri_eye = camera_pos - original_tri.get_center();
+
+                // Os triângulos dos objetos espelhados têm a orientação
+                // invertida.
+                let facing = if obj.world.mirrored {
+                    -tri_eye.dot(tri_normal)
+                } else {
+                    tri_eye.dot(tri_normal)
+                };
+
+                // Back-face culling
+                if facing <= 0.0 {
+                    // Renderizamos modelos poligonais fechados em que faces que
+                    // não estão viradas para a câmera são sobrepostas por faces
+                    // que estão viradas para a câmera. Portanto, se o ângulo entre
+                    // o vetor que sai do triângulo em direção à câmera e a normal
+                    // do triângulo for maior do que 90 graus, o triângulo não é renderizado.
+                    continue;
+                }
+            }
+
+            let clip_vertices: [_; 3] = std::array::from_fn(|i| {
+                let position = original_tri.vertices[i];
+
+                // Leva o ponto de coordenadas de mundo para coordenadas de clip.
+                ClipVertex {
+                    clip_coord: *projection.matrix_clip * position.as_vec4(),
+                    position,
+                    varyings: original_tri.vertices_attr[i],
+                }
+            });
+
+            let polygon = clip::clip_triangle(clip_vertices, projection.guard_band);
+            let id = obj.id.map(|object| PrimitiveId {
+                object,
+                mesh: mesh_index,
+                triangle,
+            });
+
+            for clipped_tri in polygon.triangles() {
+                let vertices = clipped_tri.map(|vertex| {
+                    // Aplica a transformação ViewPort, que leva o ponto para
+                    // coordenadas de janela.
+                    let projected = *projection.matrix_viewport * vertex.clip_coord;
+                    let w = projected.get_w();
+
+                    ProjectedVertex {
+                        // Aplica a desomogeneização.
+                        screen_coord: projected.as_vec2() / w,
+                        depth: vertex.position.dist(camera_pos),
+                        inv_w: w.recip(),
+                        varyings: vertex.varyings,
+                    }
+                });
+
+                projected.push(ProjectedTriangle {
+                    vertices,
+                    texture: mesh_texture,
+                    id,
+                    depth_update: opaque,
+                    depth_test: DepthTest::Less,
+                    color_write: true,
+                    alpha_test: mesh_texture.map_d.is_some(),
+                });
             }
         }
     }
 
     pub fn draw_indexed_mesh(&mut self, mesh: &IndexedMesh) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grey_maps_repeat_the_grey_component() {
+        let grey = TextureMap::new(vec![10, 200], 2, 1, 1);
+        assert_eq!(grey.get_rgb_slice(0.25, 0.5), [10; 3]);
+        assert_eq!(grey.get_rgba_slice(0.75, 0.5), [200, 200, 200, 255]);
+        assert!(grey.is_grayscale());
+
+        let grey_alpha = TextureMap::new(vec![10, 20, 200, 40], 2, 1, 2);
+        assert_eq!(grey_alpha.get_rgba_slice(0.25, 0.5), [10, 10, 10, 20]);
+        assert_eq!(grey_alpha.get_rgb_slice(0.75, 0.5), [200; 3]);
+
+        let color = grey_alpha.sample(0.75, 0.5, Vec2::new(0., 0.), Vec2::new(0., 0.));
+        assert_eq!(color, Vec3::new([200. / 255.; 3]));
+    }
+
+    #[test]
+    fn grey_maps_mipmap_alpha_linearly() {
+        let mut grey_alpha = TextureMap::new(vec![0, 0, 255, 255, 0, 0, 255, 255], 2, 2, 2);
+        grey_alpha.set_color_space(ColorSpace::Srgb);
+        grey_alpha.set_filter(TextureFilter::Trilinear);
+
+        let (img, width, height) = grey_alpha.mip_level(1);
+        assert_eq!((width, height), (1, 1));
+        // A cor é média em espaço linear e o alfa, direto.
+        assert_eq!(img, [srgb::linear_to_srgb(0.5), 128]);
+    }
+
+    #[test]
+    fn empty_maps_read_black() {
+        let mut empty = TextureMap::new_empty();
+
+        for wrap in [
+            WrapMode::Repeat,
+            WrapMode::MirroredRepeat,
+            WrapMode::ClampToEdge,
+        ] {
+            empty.set_wrap(wrap);
+
+            for filter in [
+                TextureFilter::Nearest,
+                TextureFilter::Bilinear,
+                TextureFilter::Trilinear,
+                TextureFilter::Anisotropic,
+            ] {
+                empty.set_filter(filter);
+
+                let duv = Vec2::new(0.1, 0.3);
+                assert_eq!(empty.sample(1.5, -0.5, duv, duv), Vec3::zeros());
+            }
+
+            assert_eq!(empty.get_rgb_slice(-2.0, 3.0), [0; 3]);
+            assert_eq!(empty.get_rgba_slice(0.5, 0.5), [0; 4]);
+        }
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let mut map = TextureMap::new(vec![0, 1, 2, 3], 4, 1, 1);
+        let texels = |map: &TextureMap| -> Vec<u8> {
+            (-5..9)
+                .map(|i| map.get_rgb_slice((i as f32 + 0.5) / 4.0, 0.5)[0])
+                .collect()
+        };
+
+        assert_eq!(texels(&map), [3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0]);
+
+        map.set_wrap(WrapMode::MirroredRepeat);
+        assert_eq!(texels(&map), [3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2, 1, 0, 0]);
+
+        map.set_wrap(WrapMode::ClampToEdge);
+        assert_eq!(texels(&map), [0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn channel_maps_of_grey_maps() {
+        let grey_alpha = TextureMap::new(vec![10, 20], 1, 1, 2);
+
+        assert_eq!(grey_alpha.channel_map('m').img, [20; 3]);
+        assert_eq!(grey_alpha.channel_map('g').img, [10; 3]);
+        assert_eq!(grey_alpha.channel_map('l').img, [10; 3]);
+
+        // Sem alfa, o canal matte é a luminância.
+        let grey = TextureMap::new(vec![10], 1, 1, 1);
+        assert_eq!(grey.channel_map('m').img, [10; 3]);
+    }
+
+    fn quad_object() -> Object {
+        let vertices = vec![
+            Vec3::new([-1.0, 0.0, -1.0]),
+            Vec3::new([1.0, 0.0, -1.0]),
+            Vec3::new([1.0, 0.0, 1.0]),
+            Vec3::new([-1.0, 0.0, 1.0]),
+        ];
+        let triangles = vec![
+            ([0, 2, 1], [0, 0, 0], [0, 0, 0]),
+            ([0, 3, 2], [0, 0, 0], [0, 0, 0]),
+        ];
+        let mesh = IndexedMesh::new("quad".into(), triangles, Some(0), &vertices);
+
+        Object::new(
+            "quad".into(),
+            vertices,
+            vec![Vec3::new([0.0, 1.0, 0.0])],
+            Some(vec![Vec3::zeros()]),
+            vec![mesh],
+            vec![Texture::default()],
+        )
+    }
+
+    #[test]
+    fn shadow_maps_are_cached() {
+        let mut scene = Scene::new(64, 64);
+        let info = scene.add_obj(quad_object());
+
+        let maps_ptr = |scene: &Scene| scene.shadow_cache.as_ref().unwrap().maps.as_ptr();
+
+        scene.update_shadow_maps();
+        let first = maps_ptr(&scene);
+
+        scene.update_shadow_maps();
+        assert_eq!(maps_ptr(&scene), first, "unchanged scene");
+
+        let mut transform = Transform::identity();
+        transform.translation = Vec3::new([0.0, 1.0, 0.0]);
+        scene.set_transform(info.node, transform);
+        scene.update_shadow_maps();
+        let moved = maps_ptr(&scene);
+        assert_ne!(moved, first, "moved object");
+
+        // A intensidade não muda a profundidade vista pela luz.
+        scene.light_mut(0).unwrap().intensity *= 2.0;
+        scene.update_shadow_maps();
+        assert_eq!(maps_ptr(&scene), moved, "changed intensity");
+
+        scene.light_mut(0).unwrap().cast_shadows = false;
+        scene.update_shadow_maps();
+        let relit = maps_ptr(&scene);
+        assert_ne!(relit, moved, "changed shadow casters");
+
+        scene.set_shadow_map_size(64);
+        scene.update_shadow_maps();
+        assert_ne!(maps_ptr(&scene), relit, "resized maps");
+    }
+
+    #[test]
+    fn rays_go_through_cutouts() {
+        let mut obj = quad_object();
+        let ray = Ray::new(Vec3::new([0.2, 5.0, 0.3]), Vec3::new([0.0, -1.0, 0.0]));
+        assert!(obj.raycast(&ray).is_some());
+
+        obj.textures[0].map_d = Some(TextureMap::new(vec![0], 1, 1, 1));
+        assert!(obj.raycast(&ray).is_none());
+    }
+
+    #[test]
+    fn screen_coordinates_match_the_raster() {
+        let mut scene = Scene::new(64, 48);
+        let (matrix_clip, matrix_viewport) =
+            Scene::gen_transformation_matrices(&mut scene.camera, 64, 48);
+
+        let point = Vec3::new([3.0, -2.0, 100.0]);
+        let clip = matrix_clip * point.as_vec4();
+        let raster = (matrix_viewport * (clip / clip.get_w())).as_vec2();
+
+        let screen = scene.project_point(point).unwrap();
+        assert!(screen.dist(raster) < 1e-3, "{:?} != {:?}", screen, raster);
+
+        // O raio pelo centro de um pixel volta ao ponto projetado nele.
+        let ray = scene.screen_ray(screen.x, screen.y);
+        let to_point = (point - scene.camera.get_pos()).normalized();
+        assert!(ray.dot(to_point) > 1.0 - 1e-6);
+    }
+
+    #[test]
+    fn culled_objects_are_not_transformed() {
+        let mut scene = Scene::new(64, 64);
+        scene.set_shadows(false);
+        let info = scene.add_obj(quad_object());
+
+        let mut canvas = Canvas::new(64, 64);
+        canvas.init_depth(f32::INFINITY);
+
+        scene.render(&mut canvas);
+        assert_eq!(scene.render_stats().objects_drawn, 1);
+        assert_eq!(scene.objects[0].world.matrix, Matrix4::identity());
+
+        // Atrás da câmera, o objeto é descartado antes da transformação.
+        let mut transform = Transform::identity();
+        transform.translation = Vec3::new([0.0, 0.0, 1000.0]);
+        scene.set_transform(info.node, transform);
+
+        scene.render(&mut canvas);
+        assert_eq!(scene.render_stats().objects_culled, 1);
+        assert_eq!(scene.objects[0].world.matrix, Matrix4::identity());
+
+        // Fora do alcance da luz, também não é transformado quando os
+        // shadow maps são refeitos.
+        transform.translation = Vec3::new([0.0, 0.0, 10000.0]);
+        scene.set_transform(info.node, transform);
+        scene.set_shadows(true);
+        scene.render(&mut canvas);
+        assert!(scene.shadow_cache.is_some());
+        assert_eq!(scene.objects[0].world.matrix, Matrix4::identity());
+
+        // Mas projeta sombra da luz que o alcança.
+        scene.light_mut(0).unwrap().kind = LightKind::Point {
+            position: Vec3::new([0.0, 10.0, 10000.0]),
+            range: 100.0,
+        };
+        scene.render(&mut canvas);
+        assert_eq!(scene.render_stats().objects_culled, 1);
+        assert_eq!(scene.objects[0].world.matrix, transform.matrix());
+    }
+
+    // Cube map of a light at the origin with the face +Y in shadow and the
+    // others lit.
+    fn half_shadowed_cube_map(size: usize) -> ShadowMap {
+        let faces = CUBE_FACES
+            .iter()
+            .enumerate()
+            .map(|(index, (direction, up))| {
+                let mut camera =
+                    Scene::shadow_camera(Vec3::zeros(), Vec3::new(*direction), 90.0, -0.1, -100.0);
+                camera.set_up_direction(Vec3::new(*up));
+
+                let (matrix_clip, matrix_viewport) =
+                    Scene::gen_transformation_matrices(&mut camera, size, size);
+                let depth = if index == 2 { 0.0 } else { f32::INFINITY };
+
+                ShadowFace::new(matrix_clip, matrix_viewport, vec![depth; size * size])
+            })
+            .collect();
+
+        ShadowMap::new(Vec3::zeros(), size, 90.0, faces)
+    }
+
+    #[test]
+    fn shadow_kernel_crosses_cube_faces() {
+        let shadow_map = half_shadowed_cube_map(16);
+
+        let visibility = |point: Vec3| {
+            let light_dir = -point.normalized();
+            shadow_map.visibility(point, light_dir, light_dir)
+        };
+
+        assert_eq!(visibility(Vec3::new([10.0, 1.0, 0.0])), 1.0);
+        assert_eq!(visibility(Vec3::new([1.0, 10.0, 0.0])), 0.0);
+
+        // Perto da aresta entre +X e +Y, parte do kernel cai na face +Y.
+        let edge = visibility(Vec3::new([10.0, 9.95, 0.0]));
+        assert!(edge > 0.0 && edge < 1.0, "visibility {}", edge);
+    }
+}
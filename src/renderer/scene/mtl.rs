@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+// O parser de MTL do crate `obj` não entende as opções das texturas
+// (ex.: `map_Kd -clamp on madeira.png`) nem as extensões PBR (Pr, Pm),
+// então o arquivo inteiro é lido aqui, uma única vez.
+// https://paulbourke.net/dataformats/mtl/
+// https://exocortex.com/blog/extending_wavefront_mtl_to_support_pbr
+
+pub struct TextureStatement {
+    pub filename: String,
+    pub clamp: bool,
+    // Multiplicador do bump map (-bm).
+    pub bump_multiplier: f32,
+    // Canal usado pelas texturas escalares (-imfchan r|g|b|m|l|z).
+    pub channel: Option<char>,
+}
+
+// Statements of a material. The ones omitted by the file are None, and the
+// loader picks their default values.
+#[derive(Default)]
+pub struct MaterialStatements {
+    pub name: String,
+    pub ka: Option<[f32; 3]>,
+    pub kd: Option<[f32; 3]>,
+    pub ks: Option<[f32; 3]>,
+    pub ke: Option<[f32; 3]>,
+    pub ns: Option<f32>,
+    pub illum: Option<i32>,
+    // Dissolve (d).
+    pub alpha: Option<f32>,
+    // Texture statements, indexed by the lowercase keyword (e.g. "map_kd").
+    pub textures: HashMap<String, TextureStatement>,
+    // Pr
+    pub roughness: Option<f32>,
+    // Pm
+    pub metallic: Option<f32>,
+}
+
+impl MaterialStatements {
+    // Ns usado quando o material omite o statement.
+    pub const DEFAULT_SHININESS: f32 = 10.0;
+
+    // Exportadores (ex.: o Blender) escrevem `Ns 0` quando o material não tem
+    // brilho definido; o expoente 0 espalharia o especular pela superfície
+    // inteira, então ele vale como omitido.
+    pub fn shininess(&self) -> f32 {
+        self.ns
+            .filter(|ns| *ns > 0.0)
+            .unwrap_or(Self::DEFAULT_SHININESS)
+    }
+}
+
+// Materials in the order of the file.
+pub type Materials = Vec<MaterialStatements>;
+
+pub fn parse_material_statements(reader: impl BufRead) -> Materials {
+    let mut materials = Materials::new();
+
+    for line in reader.lines().map_while(Result::ok) {
+        let mut words = line.split_whitespace();
+
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+
+        let keyword = keyword.to_lowercase();
+
+        if keyword == "newmtl" {
+            materials.push(MaterialStatements {
+                name: words.collect::<Vec<_>>().join(" "),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        // Statements fora de um material são ignorados.
+        let Some(material) = materials.last_mut() else {
+            continue;
+        };
+
+        let mut numbers = words.clone().map_while(|word| word.parse::<f32>().ok());
+
+        match keyword.as_str() {
+            "ka" | "kd" | "ks" | "ke" => {
+                // Só a forma RGB; um único valor vale para os três componentes.
+                let Some(r) = numbers.next() else {
+                    continue;
+                };
+                let g = numbers.next().unwrap_or(r);
+                let b = numbers.next().unwrap_or(g);
+
+                let color = Some([r, g, b]);
+                match keyword.as_str() {
+                    "ka" => material.ka = color,
+                    "kd" => material.kd = color,
+                    "ks" => material.ks = color,
+                    _ => material.ke = color,
+                }
+            }
+            "ns" => material.ns = numbers.next(),
+            "d" => material.alpha = numbers.next(),
+            "illum" => material.illum = words.next().and_then(|word| word.parse().ok()),
+            "pr" => material.roughness = numbers.next(),
+            "pm" => material.metallic = numbers.next(),
+            _ if keyword.starts_with("map_")
+                || keyword == "bump"
+                || keyword == "disp"
+                || keyword == "norm" =>
+            {
+                if let Some(statement) = parse_texture_statement(words.collect()) {
+                    material.textures.insert(keyword, statement);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    materials
+}
+
+fn parse_texture_statement(args: Vec<&str>) -> Option<TextureStatement> {
+    let mut clamp = false;
+    let mut bump_multiplier = 1.0;
+    let mut channel = None;
+
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') {
+        let option = args[i];
+        i += 1;
+
+        // Número de argumentos de cada opção.
+        let (min_args, max_args) = match option {
+            "-mm" => (2, 2),
+            "-o" | "-s" | "-t" => (1, 3),
+            _ => (1, 1),
+        };
+
+        let mut option_args = vec![];
+        while option_args.len() < max_args && i < args.len() {
+            let is_number = args[i].parse::<f32>().is_ok();
+
+            if option_args.len() >= min_args && !is_number {
+                break;
+            }
+
+            option_args.push(args[i]);
+            i += 1;
+        }
+
+        match option {
+            "-clamp" => clamp = option_args.first() == Some(&"on"),
+            "-bm" => {
+                if let Some(value) = option_args.first().and_then(|arg| arg.parse().ok()) {
+                    bump_multiplier = value;
+                }
+            }
+            "-imfchan" => channel = option_args.first().and_then(|arg| arg.chars().next()),
+            _ => {}
+        }
+    }
+
+    // O nome do arquivo pode conter espaços.
+    let filename = args[i..].join(" ");
+
+    if filename.is_empty() {
+        None
+    } else {
+        Some(TextureStatement {
+            filename,
+            clamp,
+            bump_multiplier,
+            channel,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn material_statements() {
+        let materials = parse_material_statements(
+            "\
+# comentário
+Kd 1 1 1
+newmtl madeira
+Ka 0.1 0.2 0.3
+Kd 0.5
+d 0.25
+Pr 0.7
+map_Kd -clamp on -s 2 2 madeira clara.png
+
+newmtl metal
+illum 3
+bump -bm 0.5 metal_bump.png
+"
+            .as_bytes(),
+        );
+
+        assert_eq!(materials.len(), 2);
+
+        let madeira = &materials[0];
+        assert_eq!(madeira.name, "madeira");
+        assert_eq!(madeira.ka, Some([0.1, 0.2, 0.3]));
+        assert_eq!(madeira.kd, Some([0.5; 3]));
+        assert_eq!(madeira.alpha, Some(0.25));
+        assert_eq!(madeira.roughness, Some(0.7));
+        assert_eq!(madeira.metallic, None);
+
+        let map_kd = &madeira.textures["map_kd"];
+        assert_eq!(map_kd.filename, "madeira clara.png");
+        assert!(map_kd.clamp);
+
+        let metal = &materials[1];
+        assert_eq!(metal.name, "metal");
+        assert_eq!(metal.kd, None);
+        assert_eq!(metal.illum, Some(3));
+
+        let bump = &metal.textures["bump"];
+        assert_eq!(bump.filename, "metal_bump.png");
+        assert_eq!(bump.bump_multiplier, 0.5);
+        assert!(!bump.clamp);
+    }
+
+    #[test]
+    fn omitted_statements() {
+        let materials = parse_material_statements(
+            "\
+newmtl vazio
+
+newmtl fosco
+Ns 0
+
+newmtl brilhante
+Ns 250
+Ke 0.5 0.25 0
+illum 2
+"
+            .as_bytes(),
+        );
+
+        let [vazio, fosco, brilhante] = &materials[..] else {
+            panic!("expected 3 materials");
+        };
+
+        assert_eq!(vazio.ns, None);
+        assert_eq!(vazio.ke, None);
+        assert_eq!(vazio.illum, None);
+        assert_eq!(vazio.shininess(), MaterialStatements::DEFAULT_SHININESS);
+
+        assert_eq!(fosco.ns, Some(0.0));
+        assert_eq!(fosco.shininess(), MaterialStatements::DEFAULT_SHININESS);
+
+        assert_eq!(brilhante.shininess(), 250.0);
+        assert_eq!(brilhante.ke, Some([0.5, 0.25, 0.0]));
+        assert_eq!(brilhante.illum, Some(2));
+    }
+
+    #[test]
+    fn texture_channel() {
+        let materials = parse_material_statements(
+            "\
+newmtl pedra
+map_Ns -imfchan r -clamp on pedra_spec.png
+map_d -imfchan m pedra_mascara.png
+map_Pr pedra_rough.png
+"
+            .as_bytes(),
+        );
+
+        let pedra = &materials[0];
+
+        let map_ns = &pedra.textures["map_ns"];
+        assert_eq!(map_ns.channel, Some('r'));
+        assert_eq!(map_ns.filename, "pedra_spec.png");
+        assert!(map_ns.clamp);
+
+        assert_eq!(pedra.textures["map_d"].channel, Some('m'));
+        assert_eq!(pedra.textures["map_d"].filename, "pedra_mascara.png");
+        assert_eq!(pedra.textures["map_pr"].channel, None);
+    }
+}
@@ -1,33 +1,67 @@
-use crate::renderer::linalg::Vec3;
+use crate::renderer::linalg::{Matrix4, Vec2, Vec3, Vec4, EPS};
+use crate::renderer::scene::bounds::{Aabb, BoundingSphere};
+use crate::renderer::scene::graph::{NodeId, Transform};
 use crate::renderer::scene::mesh::IndexedMesh;
 use crate::renderer::scene::mesh::IndexedTriangle;
 use crate::renderer::scene::mesh::IndexedTriangleNormal;
 use crate::renderer::scene::mesh::MeshInfo;
+use crate::renderer::scene::mtl;
+use crate::renderer::scene::raycast::{Bvh, BvhHit, Ray};
+use crate::renderer::scene::ColorSpace;
+use crate::renderer::scene::IlluminationModel;
+use crate::renderer::scene::MaterialModel;
 use crate::renderer::scene::Texture;
+use crate::renderer::scene::TextureFilter;
 use crate::renderer::scene::TextureMap;
-use crate::renderer::scene::VertexVisual;
+use crate::renderer::scene::WrapMode;
 
 use obj;
 
 pub struct ObjectInfo {
     pub id: u32,
     pub name: String,
+    pub node: NodeId,
+    pub parent: NodeId,
+    pub transform: Transform,
     pub mesh_info_list: Vec<MeshInfo>,
 }
 
+// Geometria de um objeto transformada pela matriz de mundo do seu nó.
+pub struct WorldGeometry {
+    pub matrix: Matrix4,
+    pub vertices: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub tangents: Vec<Vec4>,
+    // A matriz espelha o objeto, o que inverte a orientação dos triângulos.
+    pub mirrored: bool,
+}
+
+// The vertices, normals and tangents are in the object space; `world` holds
+// them in the world space of the last frame.
 pub struct Object {
     pub id: Option<u32>,
+    // Node of the scene graph that places the object in the scene.
+    pub node: Option<NodeId>,
     pub name: String,
     pub vertices: Vec<Vec3>,
     pub normals_vertices: Vec<Vec3>,
     pub texture_vertices: Option<Vec<Vec3>>,
-
-    pub vertices_visual_info: Vec<VertexVisual>,
+    // Tangent of each normal vertex (same indices as `normals_vertices`),
+    // with the handedness of the bitangent in w.
+    pub tangents: Vec<Vec4>,
 
     pub opaque_meshes: Vec<IndexedMesh>,
     pub transparent_meshes: Vec<IndexedMesh>,
 
     pub textures: Vec<Texture>,
+
+    // Volumes que envolvem o objeto, em coordenadas de objeto.
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    // BVH dos triângulos de todas as meshes, usada pelo ray casting.
+    bvh: Bvh,
+
+    pub world: WorldGeometry,
 }
 
 impl Object {
@@ -52,21 +86,239 @@ impl Object {
             }
         }
 
-        let vert_total = vertices.len();
+        let tangents = Self::gen_tangents(
+            &vertices,
+            &normals_vertices,
+            texture_vertices.as_deref(),
+            &meshes,
+        );
+
+        let world = WorldGeometry {
+            matrix: Matrix4::identity(),
+            vertices: vertices.clone(),
+            normals: normals_vertices.clone(),
+            tangents: tangents.clone(),
+            mirrored: false,
+        };
+
+        let bounds = Aabb::from_points(vertices.iter().copied());
+        let bounding_sphere = BoundingSphere::from_points(&vertices);
+        let bvh = Bvh::build(&vertices, opaque.iter().chain(transparent.iter()));
 
         Self {
             id: None,
+            node: None,
             name,
             vertices,
             normals_vertices,
             texture_vertices,
-
-            vertices_visual_info: vec![VertexVisual::zeroed(); vert_total],
+            tangents,
 
             opaque_meshes: opaque,
             transparent_meshes: transparent,
 
             textures,
+
+            bounds,
+            bounding_sphere,
+            bvh,
+
+            world,
+        }
+    }
+
+    // The opaque and then the transparent meshes, in the order of
+    // `mesh_info_list`.
+    pub fn meshes(&self) -> impl Iterator<Item = &IndexedMesh> {
+        self.opaque_meshes
+            .iter()
+            .chain(self.transparent_meshes.iter())
+    }
+
+    // Closest triangle hit by the ray, given in object space. The ray goes
+    // through the holes of the cutout masks (map_d), like the raster.
+    pub fn raycast(&self, ray: &Ray) -> Option<BvhHit> {
+        let meshes: Vec<&IndexedMesh> = self.meshes().collect();
+
+        // Pontos fora dos buracos da máscara.
+        let accept = |mesh_index: usize, triangle: usize, u: f32, v: f32| {
+            let mesh = meshes[mesh_index];
+            let (Some(texture), Some(uvs)) = (
+                mesh.texture_idx.and_then(|idx| self.textures.get(idx)),
+                self.texture_vertices.as_ref(),
+            ) else {
+                return true;
+            };
+
+            let (_, texture_tri, _) = mesh.triangles[triangle];
+            let [a, b, c] = IndexedMesh::vec3_list_from_indexed(texture_tri, uvs);
+            let uv = a * (1.0 - u - v) + b * u + c * v;
+
+            !texture.is_cut_out(Vec2::new(uv.x(), uv.y()))
+        };
+
+        self.bvh.intersect(ray, &self.vertices, &meshes, accept)
+    }
+
+    // Transforma a geometria para o espaço de mundo, caso a matriz de mundo
+    // tenha mudado desde o último frame. As normais são transformadas pela
+    // inversa transposta, que as mantém perpendiculares às superfícies
+    // mesmo com escalas não uniformes.
+    pub fn update_world_geometry(&mut self, matrix: Matrix4) {
+        if self.world.matrix == matrix {
+            return;
+        }
+
+        let normal_matrix = matrix.normal_matrix();
+        let mirrored = matrix.determinant() < 0.0;
+
+        let direction = |m: &Matrix4, v: Vec3| {
+            (*m * Vec4::new([v.x(), v.y(), v.z(), 0.0]))
+                .as_vec3()
+                .normalized()
+        };
+
+        self.world = WorldGeometry {
+            matrix,
+            vertices: self
+                .vertices
+                .iter()
+                .map(|v| (matrix * v.as_vec4()).as_vec3())
+                .collect(),
+            normals: self
+                .normals_vertices
+                .iter()
+                .map(|n| (normal_matrix * *n).normalized())
+                .collect(),
+            // O espelhamento inverte a orientação da bitangente.
+            tangents: self
+                .tangents
+                .iter()
+                .map(|t| {
+                    let dir = direction(&matrix, t.as_vec3());
+                    let handedness = if mirrored { -t.get_w() } else { t.get_w() };
+
+                    Vec4::new([dir.x(), dir.y(), dir.z(), handedness])
+                })
+                .collect(),
+            mirrored,
+        };
+    }
+
+    // Uniform scale that puts the vertex farthest from the origin of the
+    // object at `distance`.
+    pub fn fit_scale(&self, distance: f32) -> f32 {
+        let farthest = self.vertices.iter().map(|v| v.norm()).fold(0.0, f32::max);
+
+        if farthest > EPS {
+            distance / farthest
+        } else {
+            1.0
+        }
+    }
+
+    // Tangentes por vértice, na direção em que u cresce, acumuladas das faces
+    // que compartilham cada normal e ortogonalizadas em relação a ela
+    // (Gram-Schmidt). O sinal de w indica se a bitangente, na direção em que
+    // v cresce, é normal x tangente ou o oposto (UVs espelhadas).
+    // http://www.terathon.com/code/tangent.html
+    fn gen_tangents(
+        vertices: &[Vec3],
+        normals: &[Vec3],
+        texture_uv: Option<&[Vec3]>,
+        meshes: &[IndexedMesh],
+    ) -> Vec<Vec4> {
+        let mut tangents = vec![Vec3::zeros(); normals.len()];
+        let mut bitangents = vec![Vec3::zeros(); normals.len()];
+
+        if let Some(texture_uv) = texture_uv {
+            for (vertex_tri, texture_tri, normal_tri) in
+                meshes.iter().flat_map(|mesh| mesh.triangles.iter())
+            {
+                let [p0, p1, p2] = IndexedMesh::vec3_list_from_indexed(*vertex_tri, vertices);
+                let [t0, t1, t2] = IndexedMesh::vec3_list_from_indexed(*texture_tri, texture_uv);
+
+                let e1 = p1 - p0;
+                let e2 = p2 - p0;
+
+                let (du1, dv1) = (t1.x() - t0.x(), t1.y() - t0.y());
+                let (du2, dv2) = (t2.x() - t0.x(), t2.y() - t0.y());
+
+                let det = du1 * dv2 - du2 * dv1;
+                if det.abs() <= EPS {
+                    // UVs degeneradas.
+                    continue;
+                }
+
+                let tangent = (e1 * dv2 - e2 * dv1) / det;
+                let bitangent = (e2 * du1 - e1 * du2) / det;
+
+                for normal_idx in normal_tri.iter() {
+                    tangents[*normal_idx] += tangent;
+                    bitangents[*normal_idx] += bitangent;
+                }
+            }
+        }
+
+        normals
+            .iter()
+            .zip(tangents.iter().zip(bitangents.iter()))
+            .map(|(normal, (tangent, bitangent))| {
+                let mut t = (*tangent - *normal * normal.dot(*tangent)).normalized();
+
+                if !t.norm().is_finite() || t.norm() < 0.5 {
+                    // Sem UVs: qualquer vetor perpendicular à normal.
+                    let axis = if normal.x().abs() < 0.9 {
+                        Vec3::new([1.0, 0.0, 0.0])
+                    } else {
+                        Vec3::new([0.0, 1.0, 0.0])
+                    };
+                    t = normal.cross(axis).cross(*normal).normalized();
+                }
+
+                let handedness = if normal.cross(t).dot(*bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                Vec4::new([t.x(), t.y(), t.z(), handedness])
+            })
+            .collect()
+    }
+
+    // Enables or disables the normal map of the mesh, indexed as in
+    // `mesh_info_list`. Returns false when there is no such mesh.
+    pub fn set_normal_mapping(&mut self, mesh_index: usize, enabled: bool) -> bool {
+        let mesh = self
+            .opaque_meshes
+            .iter_mut()
+            .chain(self.transparent_meshes.iter_mut())
+            .nth(mesh_index);
+
+        match mesh {
+            Some(mesh) => {
+                mesh.normal_mapping = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Sets the reflectance model of the material of the mesh, indexed as in
+    // `mesh_info_list`. Returns false when there is no such mesh.
+    pub fn set_material_model(&mut self, mesh_index: usize, model: MaterialModel) -> bool {
+        let texture_idx = self
+            .meshes()
+            .nth(mesh_index)
+            .and_then(|mesh| mesh.texture_idx);
+
+        match texture_idx.and_then(|idx| self.textures.get_mut(idx)) {
+            Some(texture) => {
+                texture.material_model = model;
+                true
+            }
+            None => false,
         }
     }
 
@@ -128,17 +380,46 @@ impl Object {
         assert!(file.is_ok(), "Unable to open file {}", filename);
         let reader = BufReader::new(file.unwrap());
 
-        let mut obj_data = obj::ObjData::load_buf(reader).unwrap();
+        let obj_data = obj::ObjData::load_buf(reader).unwrap();
 
-        obj_data.material_libs.iter_mut().for_each(|mtllib| {
+        // As bibliotecas de materiais são lidas só pelo nosso parser; as
+        // meshes referenciam os materiais pelo nome.
+        let mut materials = mtl::Materials::new();
+
+        obj_data.material_libs.iter().for_each(|mtllib| {
             let mtl_path: PathBuf = add_file_path(&mtllib.filename);
             let fname = mtllib.filename.as_str();
-            let file = File::open(mtl_path);
+            let file = File::open(&mtl_path);
             assert!(file.is_ok(), "Unable to open file {}", fname);
-            _ = mtllib.reload(file.unwrap());
+
+            materials.extend(mtl::parse_material_statements(BufReader::new(
+                file.unwrap(),
+            )));
         });
 
-        let mut obj_vertices: Vec<Vec3> = obj_data
+        // Loads the texture map of the statement `keyword` of the material,
+        // with the options given in the MTL file.
+        let load_texture_map = |material: &mtl::MaterialStatements,
+                                keyword: &str,
+                                color_space: ColorSpace|
+         -> Option<TextureMap> {
+            let statement = material.textures.get(keyword)?;
+            let filename = &statement.filename;
+
+            println!("{}", filename);
+            let f_path = add_file_path(filename);
+            // O filtro de cada material pode ser trocado depois na GUI.
+            let mut texture_map =
+                TextureMap::load_from_file(&f_path, color_space, TextureFilter::Trilinear);
+
+            if statement.clamp {
+                texture_map.set_wrap(WrapMode::ClampToEdge);
+            }
+
+            Some(texture_map)
+        };
+
+        let obj_vertices: Vec<Vec3> = obj_data
             .position
             .iter()
             .map(|e| Vec3::new([e[0], e[1], e[2]]))
@@ -154,70 +435,122 @@ impl Object {
             .map(|e| Vec3::new([e[0], e[1], 0.0]))
             .collect::<_>();
 
-        // TODO: keep this ??
-        // rescaling test
-        if true {
-            let mut vertices_sorted = obj_vertices.iter().map(|e| e.norm()).collect::<Vec<_>>();
-            vertices_sorted
-                .as_mut_slice()
-                .sort_by(|a, b| a.partial_cmp(b).unwrap().reverse());
-            let vertex_max = vertices_sorted.first().unwrap();
-            dbg!(vertex_max);
-
-            let scale = 100.0;
-            let factor = scale / vertex_max;
-            obj_vertices.iter_mut().for_each(|e| *e = *e * factor);
-        }
-
         let mut textures: Vec<Texture> = Vec::from([Texture::default()]);
 
         let mut meshes: Vec<IndexedMesh> = Vec::new();
 
         //assert!(obj_data.objects.len() == 1);
-        for mtl in obj_data.material_libs.iter() {
-            for material in mtl.materials.iter() {
-                let name = material.name.clone();
-                println!("material {:?}", name);
-
-                let ka = material.ka.as_ref().unwrap();
-                let kd = material.kd.as_ref().unwrap();
-                let ks = material.ks.as_ref().unwrap();
-                let alpha = material.d.as_ref().unwrap_or(&1.0);
-
-                let map_ka = if let Some(map_ka_filename) = material.map_ka.as_ref() {
-                    println!("{}", map_ka_filename);
-                    let f_path = add_file_path(map_ka_filename);
-                    TextureMap::load_from_file(&f_path)
-                } else {
-                    TextureMap::default()
-                };
-
-                let map_kd = if let Some(map_kd_filename) = material.map_kd.as_ref() {
-                    println!("{}", map_kd_filename);
-                    let f_path = add_file_path(map_kd_filename);
-                    TextureMap::load_from_file(&f_path)
-                } else {
-                    TextureMap::default()
-                };
-
-                println!("ambient {:?}", ka);
-                println!("difuse {:?}", kd);
-                println!("specular {:?}", ks);
-                println!("d {}", alpha);
-
-                textures.push(Texture {
-                    name,
-
-                    ka: Vec3::new(*ka),
-                    kd: Vec3::new(*kd),
-                    ks: Vec3::new(*ks),
-
-                    alpha: *alpha,
-
-                    map_ka,
-                    map_kd,
+        for material in materials.iter() {
+            let name = material.name.clone();
+            println!("material {:?}", name);
+
+            // Valores usados quando o material omite o statement.
+            let ka = material.ka.unwrap_or([1.0, 1.0, 1.0]);
+            let kd = material.kd.unwrap_or([0.8, 0.8, 0.8]);
+            let ks = material.ks.unwrap_or([0.0, 0.0, 0.0]);
+            let ke = material.ke.unwrap_or([0.0, 0.0, 0.0]);
+            let ns = material.shininess();
+            let illum = material.illum.unwrap_or(2);
+            let alpha = material.alpha.unwrap_or(1.0);
+
+            let map_ka = load_texture_map(material, "map_ka", ColorSpace::Srgb)
+                .unwrap_or_else(TextureMap::default);
+            let map_kd = load_texture_map(material, "map_kd", ColorSpace::Srgb)
+                .unwrap_or_else(TextureMap::default);
+            let map_ks = load_texture_map(material, "map_ks", ColorSpace::Srgb);
+            let map_ke = load_texture_map(material, "map_ke", ColorSpace::Srgb);
+
+            // Scalar maps read a single channel: the one given by -imfchan
+            // or, by default, the luminance for map_Ns and the alpha (the
+            // matte) for map_d.
+            let load_scalar_map = |keyword: &str, channel: char| {
+                let channel = material
+                    .textures
+                    .get(keyword)
+                    .and_then(|st| st.channel)
+                    .unwrap_or(channel);
+
+                load_texture_map(material, keyword, ColorSpace::Linear)
+                    .map(|map| map.channel_map(channel))
+            };
+
+            let map_ns = load_scalar_map("map_ns", 'l');
+            let map_d = load_scalar_map("map_d", 'm');
+
+            // Materiais com algum parâmetro PBR usam o modelo PBR. Os
+            // mapas multiplicam os parâmetros, que valem 1 quando só o
+            // mapa é dado.
+            let map_roughness = load_scalar_map("map_pr", 'l');
+            let map_metallic = load_scalar_map("map_pm", 'l');
+            let roughness = material.roughness;
+            let metallic = material.metallic;
+
+            let is_pbr = roughness.is_some()
+                || metallic.is_some()
+                || map_roughness.is_some()
+                || map_metallic.is_some();
+            let material_model = if is_pbr {
+                MaterialModel::Pbr
+            } else {
+                MaterialModel::Phong
+            };
+
+            let roughness = roughness.unwrap_or(if map_roughness.is_some() { 1.0 } else { 0.5 });
+            let metallic = metallic.unwrap_or(if map_metallic.is_some() { 1.0 } else { 0.0 });
+
+            // O normal map pode vir de qualquer um desses statements;
+            // bump costuma ser um height map, convertido aqui.
+            let bump_keyword = ["norm", "map_bump", "bump"]
+                .into_iter()
+                .find(|keyword| material.textures.contains_key(*keyword))
+                .unwrap_or("map_bump");
+
+            let map_bump =
+                load_texture_map(material, bump_keyword, ColorSpace::Linear).map(|map| {
+                    if map.is_grayscale() {
+                        map.height_to_normal_map()
+                    } else {
+                        map
+                    }
                 });
-            }
+            let bump_multiplier = material
+                .textures
+                .get(bump_keyword)
+                .map_or(1.0, |st| st.bump_multiplier);
+
+            println!("ambient {:?}", ka);
+            println!("difuse {:?}", kd);
+            println!("specular {:?}", ks);
+            println!("d {}", alpha);
+
+            textures.push(Texture {
+                name,
+
+                ka: Vec3::new(ka),
+                kd: Vec3::new(kd),
+                ks: Vec3::new(ks),
+                ke: Vec3::new(ke),
+                ns,
+                illum: IlluminationModel::from_illum(illum),
+
+                material_model,
+                roughness,
+                metallic,
+
+                alpha,
+
+                map_ka,
+                map_kd,
+                map_ks,
+                map_ke,
+                map_ns,
+                map_d,
+                map_roughness,
+                map_metallic,
+
+                map_bump,
+                bump_multiplier,
+            });
         }
 
         // FIXME: we need to support loading separately more
@@ -367,7 +700,7 @@ impl Object {
 
                 if mesh_missing_texture {
                     // add dummy global texture coord
-                    // (PT): isso aq serve pra ter oque armazenar no struct VertexAttributes dps,
+                    // (PT): isso aq serve pra ter oque armazenar no VertexInput dps,
                     // pra nao precisar ficar usando um monte de Option por ai
                     let new_dummy_texture_indx = obj_texture_uv.len();
                     obj_texture_uv.push(Vec3::zeros());
@@ -401,9 +734,9 @@ impl Object {
                         let b_idx = indexed_tri[1];
                         let c_idx = indexed_tri[2];
 
-                        gen_normals[a_idx] = gen_normals[a_idx] + normal;
-                        gen_normals[b_idx] = gen_normals[b_idx] + normal;
-                        gen_normals[c_idx] = gen_normals[c_idx] + normal;
+                        gen_normals[a_idx] += normal;
+                        gen_normals[b_idx] += normal;
+                        gen_normals[c_idx] += normal;
                     }
 
                     for normal in gen_normals.iter_mut() {
@@ -433,11 +766,12 @@ impl Object {
                     .map(|(vert, text, norm)| (*vert, text.unwrap(), norm.unwrap()))
                     .collect::<_>();
 
-                meshes.push(IndexedMesh {
-                    name: group.name.clone(),
-                    triangles: mesh_triangles,
-                    texture_idx: texture_idx_match,
-                });
+                meshes.push(IndexedMesh::new(
+                    group.name.clone(),
+                    mesh_triangles,
+                    texture_idx_match,
+                    &obj_vertices,
+                ));
             }
         }
 
@@ -453,22 +787,40 @@ impl Object {
         )
     }
 
+    // Sets the filter of every map of the material of the mesh, indexed as
+    // in `mesh_info_list`. Returns false when there is no such mesh.
+    pub fn set_texture_filter(&mut self, mesh_index: usize, filter: TextureFilter) -> bool {
+        let texture_idx = self
+            .meshes()
+            .nth(mesh_index)
+            .and_then(|mesh| mesh.texture_idx);
+
+        match texture_idx.and_then(|idx| self.textures.get_mut(idx)) {
+            Some(texture) => {
+                texture.set_filter(filter);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn mesh_info_list(&self) -> Vec<MeshInfo> {
         let mut ret: Vec<MeshInfo> = vec![];
 
         for mesh_list in [&self.opaque_meshes, &self.transparent_meshes] {
             for mesh in mesh_list {
-                let texture_name = if mesh.texture_idx.is_some() {
-                    let idx = mesh.texture_idx.unwrap();
-                    Some(self.textures[idx].name.clone())
-                } else {
-                    None
-                };
+                let texture = mesh.texture_idx.map(|idx| &self.textures[idx]);
 
                 ret.push(MeshInfo {
                     name: mesh.name.clone(),
                     triangle_count: mesh.triangles.len() as _,
-                    texture_name,
+                    texture_name: texture.map(|texture| texture.name.clone()),
+                    has_normal_map: texture.is_some_and(|texture| texture.map_bump.is_some()),
+                    normal_mapping: mesh.normal_mapping,
+                    material_model: texture
+                        .map_or(MaterialModel::Phong, |texture| texture.material_model),
+                    texture_filter: texture
+                        .map_or(TextureFilter::Nearest, |texture| texture.map_kd.filter()),
                 });
             }
         }
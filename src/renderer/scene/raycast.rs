@@ -0,0 +1,451 @@
+use crate::renderer::linalg::{Matrix4, Vec2, Vec3, Vec4, EPS};
+use crate::renderer::scene::bounds::Aabb;
+use crate::renderer::scene::mesh::IndexedMesh;
+
+// Quantidade máxima de triângulos nas folhas da BVH.
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    // Não precisa ser unitária: as distâncias são medidas em múltiplos dela.
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    // O raio levado por uma matriz afim. Como a direção não é normalizada,
+    // os pontos do raio transformado têm o mesmo parâmetro t.
+    pub fn transformed(&self, matrix: &Matrix4) -> Self {
+        let d = self.direction;
+
+        Self {
+            origin: (*matrix * self.origin.as_vec4()).as_vec3(),
+            direction: (*matrix * Vec4::new([d.x(), d.y(), d.z(), 0.0])).as_vec3(),
+        }
+    }
+
+    // Interseção com o triângulo pelo algoritmo de Möller–Trumbore. Retorna
+    // (t, u, v), com os dois lados do triângulo aceitos.
+    pub fn intersect_triangle(&self, tri: [Vec3; 3]) -> Option<(f32, f32, f32)> {
+        let (t, u, v) = self.intersect_triangle_plane(tri)?;
+        let inside = u >= 0.0 && v >= 0.0 && u + v <= 1.0;
+
+        (inside && t > EPS).then_some((t, u, v))
+    }
+
+    // Resolve origin + t * direction = (1 - u - v) * a + u * b + v * c pela
+    // regra de Cramer: o ponto em que o raio cruza o plano do triângulo,
+    // dentro dele ou não. None quando o raio é paralelo ao plano.
+    pub fn intersect_triangle_plane(&self, [a, b, c]: [Vec3; 3]) -> Option<(f32, f32, f32)> {
+        let edge_1 = b - a;
+        let edge_2 = c - a;
+
+        let p = self.direction.cross(edge_2);
+        let det = edge_1.dot(p);
+        if det.abs() <= EPS {
+            return None;
+        }
+        let inv_det = det.recip();
+
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+
+        let q = s.cross(edge_1);
+        let v = self.direction.dot(q) * inv_det;
+        let t = edge_2.dot(q) * inv_det;
+
+        Some((t, u, v))
+    }
+
+    // Parâmetro t em que o raio entra na caixa (0 quando começa dentro
+    // dela), pelo método dos slabs.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut t_min = 0.0_f32;
+        let mut t_max = f32::INFINITY;
+
+        for i in 0..3 {
+            // Paralelo aos planos do slab: dentro dele em todo o raio ou em
+            // nenhum ponto. A divisão daria 0 * inf = NaN com a origem no
+            // plano de uma face.
+            if self.direction[i] == 0.0 {
+                if self.origin[i] < aabb.min[i] || self.origin[i] > aabb.max[i] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inv_d = self.direction[i].recip();
+            let t0 = (aabb.min[i] - self.origin[i]) * inv_d;
+            let t1 = (aabb.max[i] - self.origin[i]) * inv_d;
+
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+
+            if t_min > t_max {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+// Resultado de `Scene::pick`.
+#[derive(Clone, Debug)]
+pub struct PickHit {
+    pub object_id: u32,
+    // Índice da mesh, na ordem de `Object::mesh_info_list`, e o seu nome.
+    pub mesh_index: usize,
+    pub mesh_name: String,
+    // Índice do triângulo em `IndexedMesh::triangles`.
+    pub triangle: usize,
+    // Ponto atingido, em coordenadas de mundo.
+    pub point: Vec3,
+}
+
+// Resultado de `Scene::inspect`: a superfície vista em um pixel.
+#[derive(Clone, Debug)]
+pub struct SurfaceInfo {
+    pub object_name: String,
+    pub mesh_name: String,
+    pub material_name: String,
+    // Coordenadas de textura e normal (de mundo, sem o normal map)
+    // interpoladas no ponto visto. Não são as varyings do rasterizador: o
+    // triângulo é intersectado de novo pelo raio do centro do pixel, com as
+    // coordenadas baricêntricas limitadas ao triângulo, então podem diferir
+    // um pouco das usadas no shading, principalmente nas bordas.
+    pub uv: Vec2,
+    pub normal: Vec3,
+}
+
+// Triângulo mais próximo atingido por um raio na BVH de um objeto.
+#[derive(Clone, Copy, Debug)]
+pub struct BvhHit {
+    pub t: f32,
+    // Índice da mesh, na ordem de `Object::mesh_info_list`.
+    pub mesh_index: usize,
+    pub triangle: usize,
+}
+
+enum BvhNodeKind {
+    // Triângulos `start..start + count` de `Bvh::triangles`.
+    Leaf { start: usize, count: usize },
+    // O filho da esquerda é o nó seguinte a este.
+    Inner { right: usize },
+}
+
+struct BvhNode {
+    bounds: Aabb,
+    kind: BvhNodeKind,
+}
+
+// Hierarquia de volumes envolventes sobre os triângulos de um objeto, em
+// coordenadas de objeto. Os nós são caixas alinhadas aos eixos, divididas
+// na mediana dos centros dos triângulos ao longo do maior eixo.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // Mesh e triângulo dentro da mesh, agrupados pelas folhas.
+    triangles: Vec<(usize, usize)>,
+}
+
+// Triângulo durante a construção.
+struct BuildTriangle {
+    mesh_index: usize,
+    triangle: usize,
+    bounds: Aabb,
+    center: Vec3,
+}
+
+impl Bvh {
+    pub fn build<'a>(vertices: &[Vec3], meshes: impl Iterator<Item = &'a IndexedMesh>) -> Self {
+        let mut build_triangles: Vec<BuildTriangle> = meshes
+            .enumerate()
+            .flat_map(|(mesh_index, mesh)| {
+                mesh.triangles
+                    .iter()
+                    .enumerate()
+                    .map(move |(triangle, (vertex_tri, _, _))| (mesh_index, triangle, *vertex_tri))
+            })
+            .map(|(mesh_index, triangle, vertex_tri)| {
+                let bounds = Aabb::from_points(vertex_tri.map(|idx| vertices[idx]));
+
+                BuildTriangle {
+                    mesh_index,
+                    triangle,
+                    bounds,
+                    center: bounds.center(),
+                }
+            })
+            .collect();
+
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            triangles: Vec::with_capacity(build_triangles.len()),
+        };
+
+        if !build_triangles.is_empty() {
+            bvh.build_node(&mut build_triangles);
+        }
+
+        bvh
+    }
+
+    // Adiciona o nó dos triângulos e, recursivamente, os seus filhos.
+    // Retorna o índice do nó.
+    fn build_node(&mut self, triangles: &mut [BuildTriangle]) -> usize {
+        let bounds = triangles
+            .iter()
+            .skip(1)
+            .fold(triangles[0].bounds, |bounds, tri| bounds.union(&tri.bounds));
+
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds,
+            kind: BvhNodeKind::Leaf {
+                start: self.triangles.len(),
+                count: triangles.len(),
+            },
+        });
+
+        if triangles.len() <= MAX_LEAF_TRIANGLES {
+            self.triangles
+                .extend(triangles.iter().map(|tri| (tri.mesh_index, tri.triangle)));
+            return index;
+        }
+
+        let centers = Aabb::from_points(triangles.iter().map(|tri| tri.center));
+        let size = centers.max - centers.min;
+        let axis = (0..3).max_by(|a, b| size[*a].total_cmp(&size[*b])).unwrap();
+
+        let middle = triangles.len() / 2;
+        triangles.select_nth_unstable_by(middle, |a, b| a.center[axis].total_cmp(&b.center[axis]));
+
+        let (left, right) = triangles.split_at_mut(middle);
+        self.build_node(left);
+        let right = self.build_node(right);
+
+        self.nodes[index].kind = BvhNodeKind::Inner { right };
+        index
+    }
+
+    // Triângulo mais próximo da origem do raio, que deve estar em
+    // coordenadas de objeto. `vertices` e `meshes` são os mesmos da
+    // construção. Os pontos em que `accept(mesh_index, triangle, u, v)` é
+    // falso, como os buracos dos materiais com alpha test, são atravessados.
+    pub fn intersect(
+        &self,
+        ray: &Ray,
+        vertices: &[Vec3],
+        meshes: &[&IndexedMesh],
+        accept: impl Fn(usize, usize, f32, f32) -> bool,
+    ) -> Option<BvhHit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest: Option<BvhHit> = None;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+
+            match ray.intersect_aabb(&node.bounds) {
+                Some(t) if closest.is_none_or(|hit| t < hit.t) => {}
+                _ => continue,
+            }
+
+            match node.kind {
+                BvhNodeKind::Leaf { start, count } => {
+                    for &(mesh_index, triangle) in &self.triangles[start..start + count] {
+                        let (vertex_tri, _, _) = meshes[mesh_index].triangles[triangle];
+                        let tri = IndexedMesh::vec3_list_from_indexed(vertex_tri, vertices);
+
+                        if let Some((t, u, v)) = ray.intersect_triangle(tri) {
+                            if closest.is_none_or(|hit| t < hit.t)
+                                && accept(mesh_index, triangle, u, v)
+                            {
+                                closest = Some(BvhHit {
+                                    t,
+                                    mesh_index,
+                                    triangle,
+                                });
+                            }
+                        }
+                    }
+                }
+                BvhNodeKind::Inner { right } => {
+                    stack.push(right);
+                    stack.push(index + 1);
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::test_utils::Rng;
+
+    fn v(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3::new([x, y, z])
+    }
+
+    fn triangle() -> [Vec3; 3] {
+        [v(0., 0., 0.), v(2., 0., 0.), v(0., 2., 0.)]
+    }
+
+    #[test]
+    fn ray_triangle_intersection() {
+        let ray = Ray::new(v(0.5, 0.25, 3.), v(0., 0., -2.));
+        let (t, u, v_) = ray.intersect_triangle(triangle()).unwrap();
+        assert!((t - 1.5).abs() < 1e-5);
+        assert!((u - 0.25).abs() < 1e-5);
+        assert!((v_ - 0.125).abs() < 1e-5);
+
+        // Os dois lados são aceitos.
+        let below = Ray::new(v(0.5, 0.25, -3.), v(0., 0., 1.));
+        assert!(below.intersect_triangle(triangle()).is_some());
+
+        // Fora do triângulo, atrás da origem e paralelo ao plano.
+        let outside = Ray::new(v(1.5, 1.5, 3.), v(0., 0., -1.));
+        assert!(outside.intersect_triangle(triangle()).is_none());
+        let (_, u, v_) = outside.intersect_triangle_plane(triangle()).unwrap();
+        assert!(u + v_ > 1.0);
+
+        let behind = Ray::new(v(0.5, 0.25, 3.), v(0., 0., 1.));
+        assert!(behind.intersect_triangle(triangle()).is_none());
+
+        let parallel = Ray::new(v(0.5, 0.25, 3.), v(1., 0., 0.));
+        assert!(parallel.intersect_triangle_plane(triangle()).is_none());
+    }
+
+    #[test]
+    fn ray_aabb_slabs() {
+        let aabb = Aabb::from_points([v(-1., -1., -1.), v(1., 1., 1.)]);
+
+        let t = Ray::new(v(-3., 0.5, 0.), v(2., 0., 0.))
+            .intersect_aabb(&aabb)
+            .unwrap();
+        assert!((t - 1.).abs() < 1e-5);
+
+        // Começando dentro da caixa.
+        assert_eq!(
+            Ray::new(v(0., 0., 0.), v(0., 1., 0.)).intersect_aabb(&aabb),
+            Some(0.)
+        );
+
+        // Direção nula nos eixos y e z, dentro e fora dos slabs.
+        assert!(Ray::new(v(-3., 0.5, 0.5), v(1., 0., 0.))
+            .intersect_aabb(&aabb)
+            .is_some());
+        assert!(Ray::new(v(-3., 1.5, 0.5), v(1., 0., 0.))
+            .intersect_aabb(&aabb)
+            .is_none());
+        // Origem no plano de uma face.
+        assert!(Ray::new(v(-3., 1., 0.), v(1., 0., 0.))
+            .intersect_aabb(&aabb)
+            .is_some());
+
+        // A caixa atrás da origem e um raio que passa ao lado.
+        assert!(Ray::new(v(3., 0., 0.), v(1., 0., 0.))
+            .intersect_aabb(&aabb)
+            .is_none());
+        assert!(Ray::new(v(-3., 0., 0.), v(1., 1.5, 0.))
+            .intersect_aabb(&aabb)
+            .is_none());
+    }
+
+    // Uma mesh com triângulos aleatórios, pequenos e espalhados.
+    fn random_mesh(rng: &mut Rng, count: usize) -> (Vec<Vec3>, IndexedMesh) {
+        let vertices: Vec<Vec3> = (0..count)
+            .flat_map(|_| {
+                let center = rng.vec3(10.0);
+                [
+                    center + rng.vec3(1.0),
+                    center + rng.vec3(1.0),
+                    center + rng.vec3(1.0),
+                ]
+            })
+            .collect();
+
+        let triangles = (0..count)
+            .map(|i| ([3 * i, 3 * i + 1, 3 * i + 2], [0; 3], [0; 3]))
+            .collect();
+        let mesh = IndexedMesh::new("random".into(), triangles, None, &vertices);
+
+        (vertices, mesh)
+    }
+
+    fn brute_force(ray: &Ray, vertices: &[Vec3], mesh: &IndexedMesh) -> Option<(f32, usize)> {
+        mesh.triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(triangle, (vertex_tri, _, _))| {
+                let tri = IndexedMesh::vec3_list_from_indexed(*vertex_tri, vertices);
+                ray.intersect_triangle(tri).map(|(t, _, _)| (t, triangle))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let (vertices, mesh) = random_mesh(&mut rng, 200);
+        let bvh = Bvh::build(&vertices, [&mesh].into_iter());
+
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = rng.vec3(15.0);
+            let ray = Ray::new(origin, rng.vec3(10.0) - origin);
+
+            let expected = brute_force(&ray, &vertices, &mesh);
+            let hit = bvh.intersect(&ray, &vertices, &[&mesh], |_, _, _, _| true);
+
+            match (hit, expected) {
+                (Some(hit), Some((t, triangle))) => {
+                    assert_eq!(hit.mesh_index, 0);
+                    assert_eq!(hit.triangle, triangle);
+                    assert_eq!(hit.t, t);
+                    hits += 1;
+                }
+                (None, None) => {}
+                _ => panic!("BVH {:?} != brute force {:?}", hit, expected),
+            }
+        }
+
+        assert!(hits > 50, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn bvh_skips_rejected_hits() {
+        // Dois triângulos, um na frente do outro.
+        let vertices = [triangle(), triangle().map(|p| p - v(0., 0., 1.))].concat();
+        let triangles = vec![([0, 1, 2], [0; 3], [0; 3]), ([3, 4, 5], [0; 3], [0; 3])];
+        let mesh = IndexedMesh::new("pair".into(), triangles, None, &vertices);
+        let bvh = Bvh::build(&vertices, [&mesh].into_iter());
+
+        let ray = Ray::new(v(0.5, 0.5, 1.), v(0., 0., -1.));
+
+        let front = bvh.intersect(&ray, &vertices, &[&mesh], |_, _, _, _| true);
+        assert_eq!(front.unwrap().triangle, 0);
+
+        let back = bvh.intersect(&ray, &vertices, &[&mesh], |_, triangle, _, _| triangle != 0);
+        assert_eq!(back.unwrap().triangle, 1);
+        assert!((back.unwrap().t - 2.).abs() < 1e-5);
+
+        assert!(bvh
+            .intersect(&ray, &vertices, &[&mesh], |_, _, _, _| false)
+            .is_none());
+    }
+}
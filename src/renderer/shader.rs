@@ -0,0 +1,704 @@
+use std::ops::{Add, Mul};
+use std::sync::Arc;
+
+use super::environment::EnvironmentLight;
+use super::light::Light;
+use super::linalg::{Vec3, Vec4};
+use super::scene::{IlluminationModel, MaterialModel, Texture, TextureMap};
+use super::shadow::ShadowMap;
+
+// Valores calculados por vértice pelo VertexShader e interpolados (com
+// correção de perspectiva) para cada fragmento. Também são interpolados na
+// clipagem dos triângulos, por isso precisam apenas de soma e escala.
+pub trait Varyings: Copy + Send + Sync + Add<Output = Self> + Mul<f32, Output = Self> {}
+
+impl<T> Varyings for T where T: Copy + Send + Sync + Add<Output = T> + Mul<f32, Output = T> {}
+
+// Vertex of a mesh, in world coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct VertexInput {
+    pub position: Vec3,
+    pub normal: Vec3,
+    // Direction in which u grows, with the handedness of the bitangent in w.
+    // A null tangent disables the normal map.
+    pub tangent: Vec4,
+    pub texture_coord: Vec3,
+}
+
+// Values that don't change while a frame is drawn.
+pub struct Uniforms<'a> {
+    pub camera_position: Vec3,
+    // Linear color of the light that reaches every surface.
+    pub ambient_light: Vec3,
+    pub lights: &'a [Light],
+    // Shadow map of each light, in the same order as `lights`.
+    // Empty when the shadows are disabled.
+    pub shadow_maps: &'a [Option<ShadowMap>],
+    // Lighting from the background, which replaces the ambient light.
+    pub environment: Option<Arc<EnvironmentLight>>,
+    pub environment_intensity: f32,
+}
+
+impl Uniforms<'_> {
+    // Fraction of the light `index` that reaches the point.
+    pub fn shadow(&self, index: usize, point: Vec3, normal: Vec3, light_dir: Vec3) -> f32 {
+        match self.shadow_maps.get(index) {
+            Some(Some(shadow_map)) => shadow_map.visibility(point, normal, light_dir),
+            _ => 1.0,
+        }
+    }
+
+    // Light reflected by a white Lambertian surface with the normal
+    // `normal`, besides the scene lights.
+    pub fn ambient(&self, normal: Vec3) -> Vec3 {
+        match self.environment.as_ref() {
+            Some(environment) => environment.diffuse(normal) * self.environment_intensity,
+            None => self.ambient_light,
+        }
+    }
+
+    // Light reflected in the direction `dir` by a surface with the GGX
+    // `roughness`, besides the scene lights.
+    pub fn reflection(&self, dir: Vec3, roughness: f32) -> Vec3 {
+        match self.environment.as_ref() {
+            Some(environment) => environment.specular(dir, roughness) * self.environment_intensity,
+            None => Vec3::zeros(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct VertexOutput<V> {
+    // Position in world coordinates, which is clipped and projected
+    // by the pipeline.
+    pub position: Vec3,
+    pub varyings: V,
+}
+
+pub trait VertexShader: Sync {
+    type Varyings: Varyings;
+
+    fn shade(&self, vertex: &VertexInput, uniforms: &Uniforms) -> VertexOutput<Self::Varyings>;
+}
+
+pub struct FragmentInput<'a, V> {
+    pub varyings: V,
+    pub depth: f32,
+    pub texture: &'a Texture,
+    pub uniforms: &'a Uniforms<'a>,
+
+    // Interpola as varyings no ponto deslocado (dx, dy) pixels do fragmento.
+    neighbor: &'a dyn Fn(f32, f32) -> V,
+}
+
+impl<'a, V: Varyings> FragmentInput<'a, V> {
+    pub fn new(
+        varyings: V,
+        depth: f32,
+        texture: &'a Texture,
+        uniforms: &'a Uniforms<'a>,
+        neighbor: &'a dyn Fn(f32, f32) -> V,
+    ) -> Self {
+        Self {
+            varyings,
+            depth,
+            texture,
+            uniforms,
+            neighbor,
+        }
+    }
+
+    // Screen-space derivatives of the varyings, computed only when
+    // the shader asks for them.
+    pub fn ddx(&self) -> V {
+        (self.neighbor)(1.0, 0.0) + self.varyings * -1.0
+    }
+
+    pub fn ddy(&self) -> V {
+        (self.neighbor)(0.0, 1.0) + self.varyings * -1.0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FragmentOutput {
+    // Linear color, not limited to [0, 1].
+    pub color: Vec3,
+    pub opacity: f32,
+}
+
+pub trait FragmentShader: Sync {
+    type Varyings: Varyings;
+
+    // Returns None to discard the fragment.
+    fn shade(&self, input: &FragmentInput<Self::Varyings>) -> Option<FragmentOutput>;
+}
+
+macro_rules! impl_varyings_ops {
+    ($name:ident { $($field:ident),* }) => {
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self {
+                    $($field: self.$field + rhs.$field),*
+                }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: f32) -> Self {
+                Self {
+                    $($field: self.$field * rhs),*
+                }
+            }
+        }
+    };
+}
+
+fn color_multiply(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new([a.x() * b.x(), a.y() * b.y(), a.z() * b.z()])
+}
+
+fn sample_map(map: &TextureMap, texture_coord: Vec3, ddx: Vec3, ddy: Vec3) -> Vec3 {
+    map.sample(
+        texture_coord.x(),
+        texture_coord.y(),
+        ddx.as_vec2(),
+        ddy.as_vec2(),
+    )
+}
+
+// The optional maps multiply the material parameters, so a missing map
+// is white.
+fn sample_optional_map(
+    map: Option<&TextureMap>,
+    texture_coord: Vec3,
+    ddx: Vec3,
+    ddy: Vec3,
+) -> Vec3 {
+    map.map_or(Vec3::new([1.0, 1.0, 1.0]), |map| {
+        sample_map(map, texture_coord, ddx, ddy)
+    })
+}
+
+// Whether the fragment falls in a hole of the material's cutout mask
+// (map_d).
+fn is_cut_out(texture: &Texture, texture_coord: Vec3, ddx: Vec3, ddy: Vec3) -> bool {
+    texture
+        .map_d
+        .as_ref()
+        .is_some_and(|map_d| sample_map(map_d, texture_coord, ddx, ddy).x() < Texture::ALPHA_CUTOFF)
+}
+
+// Perturba a normal interpolada com o normal map (tangent space) do material.
+fn apply_normal_map(
+    texture: &Texture,
+    texture_coord: Vec3,
+    ddx: Vec3,
+    ddy: Vec3,
+    normal: Vec3,
+    tangent: Vec4,
+) -> Vec3 {
+    let Some(map_bump) = texture.map_bump.as_ref() else {
+        return normal;
+    };
+
+    let handedness = tangent.get_w();
+    if handedness == 0.0 {
+        return normal;
+    }
+
+    let normal = normal.normalized();
+
+    // A interpolação desfaz a ortogonalidade entre a tangente e a normal.
+    let tangent = tangent.as_vec3();
+    let tangent = (tangent - normal * normal.dot(tangent)).normalized();
+    if !tangent.x().is_finite() {
+        return normal;
+    }
+
+    let bitangent = normal.cross(tangent) * handedness.signum();
+
+    let texel = sample_map(map_bump, texture_coord, ddx, ddy);
+    let scale = texture.bump_multiplier;
+
+    let x = (texel.x() * 2.0 - 1.0) * scale;
+    let y = (texel.y() * 2.0 - 1.0) * scale;
+    let z = texel.z() * 2.0 - 1.0;
+
+    (tangent * x + bitangent * y + normal * z).normalized()
+}
+
+// Cor da superfície iluminada pelas luzes da cena, usada pelos shaders
+// Phong e Flat, com o modelo de reflectância do material.
+#[allow(clippy::too_many_arguments)]
+fn lighting(
+    texture: &Texture,
+    texture_coord: Vec3,
+    ddx: Vec3,
+    ddy: Vec3,
+    position: Vec3,
+    normal: Vec3,
+    surface_normal: Vec3,
+    uniforms: &Uniforms,
+) -> FragmentOutput {
+    let shade = match texture.material_model {
+        MaterialModel::Phong => phong,
+        MaterialModel::Pbr => pbr,
+    };
+
+    shade(
+        texture,
+        texture_coord,
+        ddx,
+        ddy,
+        position,
+        normal,
+        surface_normal,
+        uniforms,
+    )
+}
+
+// Modelo de Blinn-Phong somando a
+// contribuição de cada luz da cena ao termo ambiente e à emissão do
+// material.
+#[allow(clippy::too_many_arguments)]
+fn phong(
+    texture: &Texture,
+    texture_coord: Vec3,
+    ddx: Vec3,
+    ddy: Vec3,
+    position: Vec3,
+    normal: Vec3,
+    surface_normal: Vec3,
+    uniforms: &Uniforms,
+) -> FragmentOutput {
+    // Expoente mínimo: texels escuros do map_Ns levariam o expoente a 0, e o
+    // brilho especular cobriria toda a superfície.
+    const MIN_SHININESS: f32 = 1.0;
+
+    let sample = |map: Option<&TextureMap>| sample_optional_map(map, texture_coord, ddx, ddy);
+
+    let diffuse_color = sample(Some(&texture.map_kd));
+    let ambient_color = sample(Some(&texture.map_ka));
+
+    let c_r = color_multiply(diffuse_color, texture.kd); // diffuse reflectance
+    let c_a = color_multiply(ambient_color, texture.ka); // ambient reflectance
+    let c_e = color_multiply(sample(texture.map_ke.as_ref()), texture.ke); // emission
+
+    if texture.illum == IlluminationModel::Color {
+        return FragmentOutput {
+            color: c_r + c_e,
+            opacity: texture.alpha,
+        };
+    }
+
+    // specular reflectance
+    let c_s = match texture.illum {
+        IlluminationModel::Specular | IlluminationModel::Reflection => {
+            color_multiply(sample(texture.map_ks.as_ref()), texture.ks)
+        }
+        _ => Vec3::new([0.0, 0.0, 0.0]),
+    };
+    let shininess = (texture.ns * sample(texture.map_ns.as_ref()).x()).max(MIN_SHININESS);
+
+    let normal = normal.normalized();
+    let eye = (uniforms.camera_position - position).normalized();
+
+    let mut color = color_multiply(c_r, color_multiply(c_a, uniforms.ambient(normal))) + c_e;
+
+    if texture.illum == IlluminationModel::Reflection {
+        // Rugosidade GGX equivalente ao expoente de Phong.
+        let roughness = (2.0 / (shininess + 2.0)).powf(0.25);
+        let reflected = normal * (2.0 * normal.dot(eye)) - eye;
+
+        color += color_multiply(c_s, uniforms.reflection(reflected, roughness));
+    }
+
+    for (index, light) in uniforms.lights.iter().enumerate() {
+        let Some((light_dir, radiance)) = light.incident(position) else {
+            continue;
+        };
+
+        let diffuse = normal.dot(light_dir);
+        if diffuse <= 0.0 {
+            continue;
+        }
+
+        // O bias das sombras depende da superfície, não da normal perturbada
+        // pelo normal map.
+        let visibility = uniforms.shadow(index, position, surface_normal, light_dir);
+        if visibility <= 0.0 {
+            continue;
+        }
+
+        let halfway = (light_dir + eye).normalized();
+        let specular = normal.dot(halfway).max(0.0).powf(shininess);
+
+        color += color_multiply(radiance * visibility, c_r * diffuse + c_s * specular);
+    }
+
+    FragmentOutput {
+        color,
+        opacity: texture.alpha,
+    }
+}
+
+// Metallic-roughness model: Lambert diffuse plus the Cook-Torrance
+// specular, with the GGX distribution, the height-correlated Smith
+// visibility and the Schlick Fresnel.
+// https://google.github.io/filament/Filament.md.html#materialsystem
+#[allow(clippy::too_many_arguments)]
+fn pbr(
+    texture: &Texture,
+    texture_coord: Vec3,
+    ddx: Vec3,
+    ddy: Vec3,
+    position: Vec3,
+    normal: Vec3,
+    surface_normal: Vec3,
+    uniforms: &Uniforms,
+) -> FragmentOutput {
+    use std::f32::consts::PI;
+
+    // Com rugosidade 0 a distribuição GGX vira um pico infinito.
+    const MIN_ROUGHNESS: f32 = 0.045;
+    // Reflectância dos dielétricos na incidência normal.
+    const DIELECTRIC_F0: f32 = 0.04;
+
+    let sample = |map: Option<&TextureMap>| sample_optional_map(map, texture_coord, ddx, ddy);
+
+    let base_color = color_multiply(sample(Some(&texture.map_kd)), texture.kd);
+    let emission = color_multiply(sample(texture.map_ke.as_ref()), texture.ke);
+
+    if texture.illum == IlluminationModel::Color {
+        return FragmentOutput {
+            color: base_color + emission,
+            opacity: texture.alpha,
+        };
+    }
+
+    let roughness =
+        (texture.roughness * sample(texture.map_roughness.as_ref()).x()).clamp(MIN_ROUGHNESS, 1.0);
+    let metallic = (texture.metallic * sample(texture.map_metallic.as_ref()).x()).clamp(0.0, 1.0);
+
+    // Os metais não têm reflexão difusa e refletem especularmente com a
+    // cor base.
+    let c_diff = base_color * (1.0 - metallic);
+    let f0 = Vec3::new([DIELECTRIC_F0; 3]) * (1.0 - metallic) + base_color * metallic;
+
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+
+    let normal = normal.normalized();
+    let eye = (uniforms.camera_position - position).normalized();
+    let n_dot_v = normal.dot(eye).max(1e-4);
+
+    // Luz do ambiente, com a aproximação analítica da integral da BRDF
+    // especular de Karis.
+    // https://www.unrealengine.com/en-US/blog/physically-based-shading-on-mobile
+    let env_brdf = {
+        let c0 = [-1.0, -0.0275, -0.572, 0.022];
+        let c1 = [1.0, 0.0425, 1.04, -0.04];
+        let r: [f32; 4] = std::array::from_fn(|i| roughness * c0[i] + c1[i]);
+
+        let a004 = (r[0] * r[0]).min((-9.28 * n_dot_v).exp2()) * r[0] + r[1];
+        (a004 * -1.04 + r[2], a004 * 1.04 + r[3])
+    };
+
+    let reflected = normal * (2.0 * normal.dot(eye)) - eye;
+    let ambient_specular = f0 * env_brdf.0 + Vec3::new([env_brdf.1; 3]);
+
+    let mut color = color_multiply(c_diff, uniforms.ambient(normal))
+        + color_multiply(ambient_specular, uniforms.reflection(reflected, roughness))
+        + emission;
+
+    for (index, light) in uniforms.lights.iter().enumerate() {
+        let Some((light_dir, radiance)) = light.incident(position) else {
+            continue;
+        };
+
+        let n_dot_l = normal.dot(light_dir);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+
+        let visibility = uniforms.shadow(index, position, surface_normal, light_dir);
+        if visibility <= 0.0 {
+            continue;
+        }
+
+        let halfway = (light_dir + eye).normalized();
+        let n_dot_h = normal.dot(halfway).max(0.0);
+        let v_dot_h = eye.dot(halfway).max(0.0);
+
+        let d = {
+            let f = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+            alpha2 / (PI * f * f)
+        };
+
+        let v = {
+            let ggx_v = n_dot_l * (n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2).sqrt();
+            let ggx_l = n_dot_v * (n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2).sqrt();
+            0.5 / (ggx_v + ggx_l)
+        };
+
+        let fresnel = f0 + (Vec3::new([1.0; 3]) - f0) * (1.0 - v_dot_h).powi(5);
+
+        let specular = fresnel * (d * v);
+        let diffuse = color_multiply(Vec3::new([1.0; 3]) - fresnel, c_diff) / PI;
+
+        // As luzes são escaladas por π, para que uma superfície difusa branca
+        // tenha a mesma cor que no modelo de Phong.
+        color += color_multiply(radiance * (visibility * PI * n_dot_l), diffuse + specular);
+    }
+
+    FragmentOutput {
+        color,
+        opacity: texture.alpha,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PhongVaryings {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    pub texture_coord: Vec3,
+}
+
+impl_varyings_ops!(PhongVaryings {
+    position,
+    normal,
+    tangent,
+    texture_coord
+});
+
+// Iluminação de Phong interpolada por fragmento.
+pub struct PhongShader;
+
+impl VertexShader for PhongShader {
+    type Varyings = PhongVaryings;
+
+    fn shade(&self, vertex: &VertexInput, _uniforms: &Uniforms) -> VertexOutput<PhongVaryings> {
+        VertexOutput {
+            position: vertex.position,
+            varyings: PhongVaryings {
+                position: vertex.position,
+                normal: vertex.normal,
+                tangent: vertex.tangent,
+                texture_coord: vertex.texture_coord,
+            },
+        }
+    }
+}
+
+impl FragmentShader for PhongShader {
+    type Varyings = PhongVaryings;
+
+    fn shade(&self, input: &FragmentInput<PhongVaryings>) -> Option<FragmentOutput> {
+        let varyings = &input.varyings;
+        let ddx = input.ddx().texture_coord;
+        let ddy = input.ddy().texture_coord;
+
+        if is_cut_out(input.texture, varyings.texture_coord, ddx, ddy) {
+            return None;
+        }
+
+        let shading_normal = apply_normal_map(
+            input.texture,
+            varyings.texture_coord,
+            ddx,
+            ddy,
+            varyings.normal,
+            varyings.tangent,
+        );
+
+        Some(lighting(
+            input.texture,
+            varyings.texture_coord,
+            ddx,
+            ddy,
+            varyings.position,
+            shading_normal,
+            varyings.normal.normalized(),
+            input.uniforms,
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FlatVaryings {
+    pub position: Vec3,
+    pub tangent: Vec4,
+    pub texture_coord: Vec3,
+}
+
+impl_varyings_ops!(FlatVaryings {
+    position,
+    tangent,
+    texture_coord
+});
+
+// Phong com a normal da face, obtida das derivadas da posição.
+pub struct FlatShader;
+
+impl VertexShader for FlatShader {
+    type Varyings = FlatVaryings;
+
+    fn shade(&self, vertex: &VertexInput, _uniforms: &Uniforms) -> VertexOutput<FlatVaryings> {
+        VertexOutput {
+            position: vertex.position,
+            varyings: FlatVaryings {
+                position: vertex.position,
+                tangent: vertex.tangent,
+                texture_coord: vertex.texture_coord,
+            },
+        }
+    }
+}
+
+impl FragmentShader for FlatShader {
+    type Varyings = FlatVaryings;
+
+    fn shade(&self, input: &FragmentInput<FlatVaryings>) -> Option<FragmentOutput> {
+        let varyings = &input.varyings;
+        let ddx = input.ddx();
+        let ddy = input.ddy();
+
+        if is_cut_out(
+            input.texture,
+            varyings.texture_coord,
+            ddx.texture_coord,
+            ddy.texture_coord,
+        ) {
+            return None;
+        }
+
+        let mut normal = ddx.position.cross(ddy.position).normalized();
+
+        // The face normal must point to the camera.
+        if normal.dot(input.uniforms.camera_position - varyings.position) < 0.0 {
+            normal = normal * -1.0;
+        }
+
+        let shading_normal = apply_normal_map(
+            input.texture,
+            varyings.texture_coord,
+            ddx.texture_coord,
+            ddy.texture_coord,
+            normal,
+            varyings.tangent,
+        );
+
+        Some(lighting(
+            input.texture,
+            varyings.texture_coord,
+            ddx.texture_coord,
+            ddy.texture_coord,
+            varyings.position,
+            shading_normal,
+            normal,
+            input.uniforms,
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TextureVaryings {
+    pub texture_coord: Vec3,
+}
+
+impl_varyings_ops!(TextureVaryings { texture_coord });
+
+// Cor difusa do material, sem iluminação.
+pub struct UnlitShader;
+
+impl VertexShader for UnlitShader {
+    type Varyings = TextureVaryings;
+
+    fn shade(&self, vertex: &VertexInput, _uniforms: &Uniforms) -> VertexOutput<TextureVaryings> {
+        VertexOutput {
+            position: vertex.position,
+            varyings: TextureVaryings {
+                texture_coord: vertex.texture_coord,
+            },
+        }
+    }
+}
+
+impl FragmentShader for UnlitShader {
+    type Varyings = TextureVaryings;
+
+    fn shade(&self, input: &FragmentInput<TextureVaryings>) -> Option<FragmentOutput> {
+        let texture = input.texture;
+        let texture_coord = input.varyings.texture_coord;
+        let ddx = input.ddx().texture_coord;
+        let ddy = input.ddy().texture_coord;
+
+        // Também descarta os recortes nos passes de profundidade e de sombra,
+        // que usam este shader.
+        if is_cut_out(texture, texture_coord, ddx, ddy) {
+            return None;
+        }
+
+        let diffuse_color = sample_map(&texture.map_kd, texture_coord, ddx, ddy);
+
+        Some(FragmentOutput {
+            color: color_multiply(diffuse_color, texture.kd),
+            opacity: texture.alpha,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NormalVaryings {
+    pub normal: Vec3,
+    pub texture_coord: Vec3,
+}
+
+impl_varyings_ops!(NormalVaryings {
+    normal,
+    texture_coord
+});
+
+// Visualização das normais: cada componente em [-1, 1] vira um canal de cor.
+pub struct NormalShader;
+
+impl VertexShader for NormalShader {
+    type Varyings = NormalVaryings;
+
+    fn shade(&self, vertex: &VertexInput, _uniforms: &Uniforms) -> VertexOutput<NormalVaryings> {
+        VertexOutput {
+            position: vertex.position,
+            varyings: NormalVaryings {
+                normal: vertex.normal,
+                texture_coord: vertex.texture_coord,
+            },
+        }
+    }
+}
+
+impl FragmentShader for NormalShader {
+    type Varyings = NormalVaryings;
+
+    fn shade(&self, input: &FragmentInput<NormalVaryings>) -> Option<FragmentOutput> {
+        if is_cut_out(
+            input.texture,
+            input.varyings.texture_coord,
+            input.ddx().texture_coord,
+            input.ddy().texture_coord,
+        ) {
+            return None;
+        }
+
+        let normal = input.varyings.normal.normalized();
+
+        Some(FragmentOutput {
+            color: normal * 0.5 + Vec3::new([0.5, 0.5, 0.5]),
+            opacity: 1.0,
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use super::linalg::{Matrix4, Vec3};
+
+// Direção e vetor "up" das câmeras de cada face de um cube shadow map,
+// na ordem +X, -X, +Y, -Y, +Z, -Z.
+pub const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+// Depth buffer rendered from the light, with the transformations used
+// to render it.
+pub struct ShadowFace {
+    matrix_clip: Matrix4,
+    matrix_viewport: Matrix4,
+    // Distance of the closest surface to the light, per texel.
+    depth: Vec<f32>,
+}
+
+impl ShadowFace {
+    pub fn new(matrix_clip: Matrix4, matrix_viewport: Matrix4, depth: Vec<f32>) -> Self {
+        Self {
+            matrix_clip,
+            matrix_viewport,
+            depth,
+        }
+    }
+}
+
+// Shadow map of a light: one face for directional and spot lights and
+// six (a cube map, see CUBE_FACES) for point lights.
+pub struct ShadowMap {
+    // Position of the cameras the faces were rendered from.
+    position: Vec3,
+    size: usize,
+    // Angle covered by a texel, in radians. Scales the depth bias with the
+    // distance to the light.
+    texel_angle: f32,
+    faces: Vec<ShadowFace>,
+}
+
+impl ShadowMap {
+    // Raio do kernel do Percentage-Closer Filtering: (2r + 1)^2 texels.
+    pub const PCF_RADIUS: isize = 1;
+
+    pub fn new(position: Vec3, size: usize, fov: f32, faces: Vec<ShadowFace>) -> Self {
+        assert!(faces.len() == 1 || faces.len() == CUBE_FACES.len());
+        assert!(faces.iter().all(|face| face.depth.len() == size * size));
+
+        Self {
+            position,
+            size,
+            texel_angle: fov.to_radians() / size as f32,
+            faces,
+        }
+    }
+
+    // Face of the cube map whose frustum contains `dir`: the one of the
+    // axis with the largest component.
+    fn face(&self, dir: Vec3) -> &ShadowFace {
+        if self.faces.len() == 1 {
+            return &self.faces[0];
+        }
+
+        let (x, y, z) = (dir.x().abs(), dir.y().abs(), dir.z().abs());
+
+        let index = if x >= y && x >= z {
+            if dir.x() > 0.0 {
+                0
+            } else {
+                1
+            }
+        } else if y >= z {
+            if dir.y() > 0.0 {
+                2
+            } else {
+                3
+            }
+        } else if dir.z() > 0.0 {
+            4
+        } else {
+            5
+        };
+
+        &self.faces[index]
+    }
+
+    // Fraction of the PCF kernel around `point` that is lit, where `normal`
+    // is the surface normal and `light_dir` the direction to the light.
+    // The points outside of the shadow map are lit.
+    pub fn visibility(&self, point: Vec3, normal: Vec3, light_dir: Vec3) -> f32 {
+        let texel = (point - self.position).norm() * self.texel_angle;
+
+        // Afasta o ponto da superfície e compensa a inclinação dela em relação
+        // à luz, evitando que a superfície faça sombra em si mesma (shadow acne).
+        let cos_theta = normal.dot(light_dir).clamp(0.1, 1.0);
+        let slope = (1.0 - cos_theta * cos_theta).sqrt() / cos_theta;
+
+        let point = point + normal * texel;
+        let bias = texel * (1.0 + slope);
+        let dist = (point - self.position).norm() - bias;
+
+        // As amostras do kernel são deslocadas no plano perpendicular à
+        // direção da luz, um texel por passo. Cada uma escolhe a sua face, então
+        // o kernel continua na face vizinha nas bordas do cube map.
+        let (tangent, bitangent) = Self::perpendicular_axes(point - self.position);
+
+        let mut lit = 0;
+        let mut total = 0;
+
+        for dy in -Self::PCF_RADIUS..=Self::PCF_RADIUS {
+            for dx in -Self::PCF_RADIUS..=Self::PCF_RADIUS {
+                let offset = tangent * (dx as f32 * texel) + bitangent * (dy as f32 * texel);
+
+                if self.depth(point + offset).is_none_or(|depth| dist <= depth) {
+                    lit += 1;
+                }
+                total += 1;
+            }
+        }
+
+        lit as f32 / total as f32
+    }
+
+    // Depth of the texel of the shadow map seen in the direction of `point`,
+    // or None when it's outside of the map.
+    fn depth(&self, point: Vec3) -> Option<f32> {
+        let face = self.face(point - self.position);
+
+        let clip = face.matrix_clip * point.as_vec4();
+        let w = clip.get_w();
+
+        if w <= 0.0 || clip.x().abs() > w || clip.get_y().abs() > w {
+            return None;
+        }
+
+        let screen = face.matrix_viewport * clip;
+        let screen = screen.as_vec2() / w;
+
+        let size = self.size as isize;
+        let x = (screen.x.round() as isize).clamp(0, size - 1) as usize;
+        let y = (screen.y.round() as isize).clamp(0, size - 1) as usize;
+
+        Some(face.depth[y * self.size + x])
+    }
+
+    // Two unit vectors perpendicular to `dir` and to each other.
+    fn perpendicular_axes(dir: Vec3) -> (Vec3, Vec3) {
+        let dir = dir.normalized();
+        let axis = if dir.x().abs() < 0.9 {
+            Vec3::new([1.0, 0.0, 0.0])
+        } else {
+            Vec3::new([0.0, 1.0, 0.0])
+        };
+
+        let tangent = dir.cross(axis).normalized();
+        (tangent, dir.cross(tangent))
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::OnceLock;
+
+// Conversões entre o espaço sRGB (usado pelas imagens e pela janela) e o
+// espaço linear (usado no cálculo da iluminação), feitas com tabelas.
+// https://en.wikipedia.org/wiki/SRGB#Transformation
+
+const LINEAR_TABLE_SIZE: usize = 4096;
+
+fn decode(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn encode(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+
+    TABLE.get_or_init(|| std::array::from_fn(|i| decode(i as f32 / 255.0)))
+}
+
+fn linear_to_srgb_table() -> &'static [u8; LINEAR_TABLE_SIZE] {
+    static TABLE: OnceLock<[u8; LINEAR_TABLE_SIZE]> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let c = i as f32 / (LINEAR_TABLE_SIZE - 1) as f32;
+            (encode(c) * 255.0).round() as u8
+        })
+    })
+}
+
+// Returns the linear value in [0, 1] of an 8-bit sRGB component.
+pub fn srgb_to_linear(c: u8) -> f32 {
+    srgb_to_linear_table()[c as usize]
+}
+
+// Returns the 8-bit sRGB component of a linear value, clamped to [0, 1].
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let idx = (c.clamp(0.0, 1.0) * (LINEAR_TABLE_SIZE - 1) as f32).round() as usize;
+
+    linear_to_srgb_table()[idx]
+}
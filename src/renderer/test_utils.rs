@@ -0,0 +1,20 @@
+use super::linalg::Vec3;
+
+// Gerador xorshift, para que os dados aleatórios dos testes sejam os mesmos
+// a cada execução.
+pub struct Rng(pub u64);
+
+impl Rng {
+    // Valor em [0, 1).
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % 1_000_000) as f32 / 1_000_000.0
+    }
+
+    // Vetor com as coordenadas em [-scale, scale).
+    pub fn vec3(&mut self, scale: f32) -> Vec3 {
+        Vec3::new(std::array::from_fn(|_| (self.next() * 2.0 - 1.0) * scale))
+    }
+}